@@ -0,0 +1,105 @@
+use std::io::Cursor;
+
+use rodio::{Decoder, Source};
+
+use crate::cli::Announce;
+
+/// 内置的提示音（22050 Hz 单声道，两声 “叮咚”）
+const CHIME_WAV: &[u8] = include_bytes!("../assets/chime.wav");
+/// 提示音和朗读相对当前音量的音量
+const ANNOUNCE_VOLUME: f32 = 0.5;
+
+/// 播放歌曲之前的提示：chime 为内置提示音（只在两首歌之间播放，第一首之前不播放），
+/// tts 播放预加载时合成的朗读 speech（合成失败时改为提示音）。不需要提示时返回 None
+pub fn announcement(mode: Announce, speech: Option<Box<dyn Source<Item = i16> + Send>>, first_track: bool) -> Option<Box<dyn Source<Item = i16> + Send>> {
+    let chime = || if first_track { None } else { chime() };
+    match mode {
+        Announce::Off => None,
+        Announce::Chime => chime(),
+        Announce::Tts => speech.or_else(chime),
+    }
+}
+
+fn chime() -> Option<Box<dyn Source<Item = i16> + Send>> {
+    let decoder = Decoder::new(Cursor::new(CHIME_WAV)).ok()?;
+    Some(Box::new(decoder.amplify(ANNOUNCE_VOLUME)))
+}
+
+/// 合成朗读 “标题，艺术家” 的音频。需要启动外部程序并等待，在预加载线程中调用，不阻塞播放界面
+#[cfg(feature = "tts")]
+pub fn speech(title: &str, artist: &str) -> Option<Box<dyn Source<Item = i16> + Send>> {
+    // 没有艺术家标签时只读标题
+    let text = if matches!(artist, "未知" | "未知作者") { title.to_string() } else { format!("{}，{}", title, artist) };
+    let wav = match tts::speak_to_wav(&text) {
+        Ok(wav) => wav,
+        Err(e) => {
+            tracing::warn!(error = %e, "朗读失败，改为播放提示音");
+            return None;
+        }
+    };
+    let decoder = Decoder::new(Cursor::new(wav)).ok()?;
+    Some(Box::new(decoder.amplify(ANNOUNCE_VOLUME)))
+}
+
+// 未启用 tts 功能时不朗读（启动时已提示）
+#[cfg(not(feature = "tts"))]
+pub fn speech(_title: &str, _artist: &str) -> Option<Box<dyn Source<Item = i16> + Send>> {
+    None
+}
+
+/// 调用系统的语音合成生成 WAV：macOS 使用 say，Windows 使用 System.Speech，其他系统使用 espeak
+#[cfg(feature = "tts")]
+mod tts {
+    use std::io;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 临时文件的序号：多个预加载线程可能同时合成
+    static NEXT_OUTPUT: AtomicUsize = AtomicUsize::new(0);
+
+    /// 把文字合成为 WAV 数据
+    pub fn speak_to_wav(text: &str) -> io::Result<Vec<u8>> {
+        let serial = NEXT_OUTPUT.fetch_add(1, Ordering::Relaxed);
+        let output = std::env::temp_dir().join(format!("mddplayer-tts-{}-{}.wav", std::process::id(), serial));
+        let status = command(text, &output).stdout(Stdio::null()).stderr(Stdio::null()).status();
+        let result = match status {
+            Ok(status) if status.success() => std::fs::read(&output),
+            Ok(status) => Err(io::Error::other(format!("语音合成程序退出: {}", status))),
+            Err(e) => Err(e),
+        };
+        let _ = std::fs::remove_file(&output);
+        result
+    }
+
+    #[cfg(target_os = "macos")]
+    fn command(text: &str, output: &Path) -> Command {
+        let mut command = Command::new("say");
+        command.args(["--file-format=WAVE", "--data-format=LEI16@22050", "-o"]).arg(output).arg(text);
+        command
+    }
+
+    // 文字和输出路径通过环境变量传给 PowerShell，避免引号转义问题
+    #[cfg(windows)]
+    fn command(text: &str, output: &Path) -> Command {
+        let mut command = Command::new("powershell");
+        command
+            .args([
+                "-NoProfile",
+                "-Command",
+                "Add-Type -AssemblyName System.Speech; \
+                 $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+                 $s.SetOutputToWaveFile($env:MDDPLAYER_TTS_OUTPUT); $s.Speak($env:MDDPLAYER_TTS_TEXT); $s.Dispose()",
+            ])
+            .env("MDDPLAYER_TTS_OUTPUT", output)
+            .env("MDDPLAYER_TTS_TEXT", text);
+        command
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    fn command(text: &str, output: &Path) -> Command {
+        let mut command = Command::new("espeak");
+        command.arg("-w").arg(output).arg(text);
+        command
+    }
+}
@@ -1,35 +1,643 @@
 // 引入 clap 库的 Parser 宏，用于自动生成命令行解析逻辑
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
+// 生成 shell 自动补全脚本
+use clap_complete::Shell;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
 // --- 常量定义 ---
 pub const NAME: &str = "东东播放器";
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const URL: &str = "github.com/xa1st/mddplayer";
 
-/// 命令行参数结构体
+/// 播放列表排序方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortMode {
+    /// 按文件名自然排序（数字按数值比较）
+    Name,
+    /// 按碟号、音轨号标签排序，无标签的按文件名排在后面
+    Track,
+    /// 按专辑名排序，专辑内按碟号、音轨号排序，没有专辑标签的保持原顺序排在最后
+    Album,
+}
+
+/// 状态栏配色主题
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorTheme {
+    /// 不输出颜色（管道、不支持颜色的终端）
+    None,
+    Default,
+    /// 适合深色背景
+    Dark,
+    /// 适合浅色背景
+    Light,
+    /// Solarized 配色（需要终端支持 24 位色）
+    Solarized,
+}
+
+/// 随机模式的洗牌方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShuffleMode {
+    /// 完全随机
+    Plain,
+    /// 随机后打散，同一艺术家（没有标签时按目录）的歌尽量不连着播放
+    Spread,
+}
+
+/// 播放列表分组方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    /// 按专辑艺术家和专辑标签分组，没有专辑标签的按所在目录
+    Album,
+    /// 按所在目录分组，适合没有标签的音乐库
+    Dir,
+}
+
+/// 判断重复歌曲的方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DedupMode {
+    /// 同一个文件（规范化路径相同）
+    Path,
+    /// 标题、艺术家相同且时长相差不超过 2 秒，保留无损或码率更高的文件
+    Tag,
+    /// 开头 10 秒的响度起伏相近（不同格式、码率的同一首歌也能识别），播放时在后台检查
+    Audio,
+}
+
+/// 每首歌开始前的提示
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Announce {
+    /// 不提示
+    Off,
+    /// 两首歌之间播放一声提示音
+    Chime,
+    /// 朗读歌名和艺术家（需要编译时启用 tts 功能）
+    Tts,
+}
+
+/// 按 D 键移除当前歌曲时的处理方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoveMode {
+    /// 立即跳到下一首
+    Skip,
+    /// 播完当前歌曲后再移除
+    Finish,
+}
+
+/// 均衡器预设
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EqPreset {
+    /// 增强低音，适合耳机
+    Bass,
+    /// 削减高音，适合声音刺耳的小音箱
+    Treble,
+    /// 突出人声
+    Vocal,
+    /// 不做处理
+    Flat,
+    /// 略微提升高频，适合古典乐
+    Classical,
+}
+
+/// 标签编码修复方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodingFix {
+    /// 自动识别 GBK/Big5/Shift-JIS
+    Auto,
+    Gbk,
+    Big5,
+    /// 不修复
+    Off,
+}
+
+/// 转码输出格式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Wav,
+    Flac,
+}
+
+/// 歌曲切换时的开始方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoAdvance {
+    /// 自动开始播放
+    Auto,
+    /// 每首歌加载后暂停，按 → 开始播放
+    Manual,
+}
+
+/// 歌曲文件的读取方式
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferMode {
+    /// 边播放边从文件读取
+    Stream,
+    /// 预加载时整首读入内存，适合不稳定的网络存储
+    Full,
+}
+
+/// 日志级别
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+/// 命令行入口：不带子命令时直接按播放参数解析，兼容 `mddplayer <路径>` 的用法
 /// 使用 #[derive(Parser)] 自动从结构体定义生成解析器
 #[derive(Parser, Debug)]
 // 设置程序信息，用于 --help 或 -V
-#[clap(author, version = VERSION, about = NAME, long_about = None)]
-// 命令行参数定义
+#[clap(author, version = VERSION, about = NAME, long_about = None, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub play: Args,
+}
+
+/// 子命令
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 播放音频文件、目录或播放列表（默认子命令，可以省略）
+    Play(Args),
+    /// 查看文件的标签、时长和编码信息，不播放
+    Info {
+        /// 音频文件路径
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+        /// 以单行 JSON 输出，便于脚本处理
+        #[arg(long)]
+        json: bool,
+        /// 修复被误读为 latin-1 的 GBK/Big5 标签（乱码）
+        #[arg(long = "fix-encoding", value_enum, default_value = "auto")]
+        fix_encoding: EncodingFix,
+        /// 文件大小使用二进制前缀（KiB、MiB），默认使用十进制前缀（KB、MB）
+        #[arg(long = "binary-sizes")]
+        binary_sizes: bool,
+    },
+    /// 按播放时相同的流程解析输入并列出播放列表，不播放
+    List {
+        #[command(flatten)]
+        args: Args,
+        /// 以 JSON 数组输出
+        #[arg(long)]
+        json: bool,
+        /// 不输出列表，改为把每首歌的路径、标题、艺术家、专辑、时长、文件大小、编码和码率写入该 CSV 文件
+        #[arg(long, value_hint = ValueHint::FilePath, conflicts_with = "json")]
+        csv: Option<PathBuf>,
+    },
+    /// 按播放时相同的顺序和过滤条件把播放列表解码、拼接为一个 WAV 文件，不使用音频设备
+    Render {
+        #[command(flatten)]
+        args: Args,
+        /// 输出的 WAV 文件
+        #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+        /// 输出的采样率（Hz），默认为各首歌中最高的采样率，其他歌曲线性重采样
+        #[arg(long, value_parser = clap::value_parser!(u32).range(8000..=384000))]
+        rate: Option<u32>,
+        /// 歌曲之间插入的静音秒数
+        #[arg(long, default_value = "0", value_parser = parse_gap)]
+        gap: Duration,
+    },
+    /// 生成 shell 自动补全脚本并输出到标准输出
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// 播放参数结构体
+#[derive(clap::Args, Debug)]
 pub struct Args {
-    /// 音频文件或目录路径
-    #[arg(index = 1)]
-    pub file: Option<String>,
+    /// 音频文件、目录、播放列表或通配符，可以指定多个，按顺序拼接
+    #[arg(index = 1, num_args = 0.., value_hint = ValueHint::AnyPath)]
+    pub files: Vec<PathBuf>,
     
     /// 启用纯净模式，不显示程序说明模式（如操作指南）
     #[clap(short = 's', long = "simple")]
     pub clean: bool,
 
+    /// 详细模式：在标准错误输出预加载耗时和完整的错误信息，退出后输出本次的警告日志
+    #[clap(long = "verbose")]
+    pub verbose: bool,
+
+    /// 将带时间戳的运行日志追加写入该文件
+    #[clap(long = "log", value_hint = ValueHint::FilePath)]
+    pub log: Option<PathBuf>,
+
+    /// 日志级别
+    #[clap(long = "log-level", value_enum, default_value = "info")]
+    pub log_level: LogLevel,
+
+    /// 启动时把进程 PID 写入该文件，退出时删除，便于脚本发送信号
+    #[clap(long = "pid-file", value_hint = ValueHint::FilePath)]
+    pub pid_file: Option<PathBuf>,
+
+    /// 从上次退出时的歌曲和位置继续播放，并在退出时保存状态（播放列表或随机/顺序模式改变时从头开始）
+    #[clap(long = "resume")]
+    pub resume: bool,
+
+    /// 在该路径创建 Unix 域套接字，接受 next/prev/pause/play/toggle/volume/goto/quit/status/playlist 命令
+    #[clap(long = "socket", value_hint = ValueHint::FilePath)]
+    pub socket: Option<PathBuf>,
+
+    /// 在该地址启动 HTTP 控制服务，例如 127.0.0.1:8090（需要编译时启用 http 功能）
+    #[clap(long = "http", value_name = "ADDR")]
+    pub http: Option<SocketAddr>,
+
+    /// HTTP 控制服务的访问令牌，请求需带 `Authorization: Bearer <令牌>`（监听非本机地址时必须设置）
+    #[clap(long = "http-token", value_name = "TOKEN", requires = "http")]
+    pub http_token: Option<String>,
+
+    /// 无终端模式：不控制终端，每首歌输出一行，只能通过信号停止（输出不是终端时自动启用）
+    #[clap(long = "headless")]
+    pub headless: bool,
+
+    /// 在任务栏图标上显示播放进度（Windows Terminal/ConEmu，检测到 WT_SESSION 时自动开启）
+    #[clap(long = "taskbar-progress")]
+    pub taskbar_progress: bool,
+
     /// 启用随机模式，不使用则为顺序模式
     #[clap(short = 'r', long = "random")]
     pub random: bool,
+
+    /// 随机模式的洗牌方式：plain 完全随机，spread 避免同一艺术家的歌连着播放
+    #[clap(long = "shuffle", value_enum, default_value = "plain")]
+    pub shuffle: ShuffleMode,
     
+    /// 扫描目录和匹配通配符时包含隐藏文件和目录（以 . 开头），默认跳过
+    #[clap(long = "include-hidden")]
+    pub include_hidden: bool,
+
+    /// 输入的目录中有播放列表文件（.m3u/.m3u8/.txt）时，播放前选择其中一个或全部音频文件；
+    /// 不指定时只在目录中只有播放列表、没有音频文件时选择。非交互模式下列出候选的播放列表后退出
+    #[clap(long = "pick")]
+    pub pick: bool,
+
+    /// 扫描目录和匹配通配符时跳过包含 .nomedia 文件的目录，默认开启，--ignore-nomedia false 关闭
+    #[clap(long = "ignore-nomedia", default_value_t = true, action = clap::ArgAction::Set)]
+    pub ignore_nomedia: bool,
+
+    /// 去掉重复的歌曲：path 按文件路径（不写值时的默认），tag 按标题、艺术家和时长，audio 在播放时后台比较开头 10 秒的音频
+    #[clap(long = "dedup", value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "path")]
+    pub dedup: Option<DedupMode>,
+
+    /// 播放时每秒检查该播放列表文件（.txt/.m3u），文件被修改后把新增的歌曲追加到播放列表末尾，
+    /// 状态栏提示新增的数量；文件中删除的歌曲不会从播放列表中移除
+    #[clap(long = "watch-playlist", value_name = "FILE")]
+    pub watch_playlist: Option<PathBuf>,
+
+    /// 去掉标题和艺术家相同（不区分大小写）的歌曲，只保留第一次出现的文件，例如同一首歌的 MP3、FLAC 和 OGG 版本。
+    /// 播放前读取所有歌曲的标签，没有标题标签的歌曲保留
+    #[clap(long = "skip-duplicates-by-title")]
+    pub skip_duplicates_by_title: bool,
+
+    /// 播放前检查每个条目（文件存在、扩展名受支持、能识别出音轨），有问题时列出并退出；
+    /// 不指定时只去掉不存在的文件
+    #[clap(long = "strict")]
+    pub strict: bool,
+
+    /// 只播放时长不少于该值的歌曲，例如 90、1:30、1h30m（时长未知的歌曲保留）
+    #[clap(long = "min-duration", value_parser = parse_duration)]
+    pub min_duration: Option<Duration>,
+
+    /// 只播放时长不超过该值的歌曲，格式同 --min-duration
+    #[clap(long = "max-duration", value_parser = parse_duration)]
+    pub max_duration: Option<Duration>,
+
+    /// 修复被误读为 latin-1 的 GBK/Big5 标签（乱码）
+    #[clap(long = "fix-encoding", value_enum, default_value = "auto")]
+    pub fix_encoding: EncodingFix,
+
+    /// 只播放整理后播放列表的前 N 首（随机模式下为随机抽取 N 首）
+    #[clap(long = "max-tracks", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_tracks: Option<usize>,
+
+    /// 播放列表排序方式（随机模式下无效）
+    #[clap(long = "sort", value_enum, default_value = "name")]
+    pub sort: SortMode,
+
+    /// 按专辑或目录分组：组内按音轨号/文件名顺序播放，随机模式下只打乱各组的顺序
+    #[clap(long = "group-by", value_enum, default_value_if("album_mode", "true", "album"))]
+    pub group_by: Option<GroupBy>,
+
+    /// 专辑模式，等同于 --group-by album：按专辑整张播放，随机模式下只打乱专辑顺序
+    #[clap(long = "album-mode", conflicts_with = "group_by")]
+    pub album_mode: bool,
+
+    /// 随机循环播放时每轮沿用同一个随机顺序，而不是重新洗牌
+    #[clap(long = "no-reshuffle")]
+    pub no_reshuffle: bool,
+
     /// 是否循环播放
     #[clap(short = 'l', long = "loop")] 
     pub is_loop: bool, 
+
+    /// 循环播放整个列表的次数（不指定时 --loop 为无限循环）
+    #[clap(long = "loop-count", value_parser = clap::value_parser!(u32).range(1..))]
+    pub loop_count: Option<u32>,
     
+    /// 每首歌连续播放的遍数（例如跟读练习），手动切歌后重新计数
+    #[clap(long = "repeat-each", value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat_each: u32,
+
+    /// 手动切歌：每首歌播完后暂停等待，按 → 播放下一首、← 重播
+    #[clap(long = "manual-advance")]
+    pub manual_advance: bool,
+
+    /// 自动切歌后是否立即播放：manual 时每首歌加载后暂停，按 → 开始
+    #[clap(long = "auto-advance", value_enum, default_value = "auto")]
+    pub auto_advance: AutoAdvance,
+
+    /// 试听模式：每首歌只播放结尾的这么多秒（时长未知的歌曲播放开头的这么多秒），然后自动切到下一首
+    #[clap(long = "preview", alias = "tail-time", value_parser = clap::value_parser!(u64).range(1..))]
+    pub preview: Option<u64>,
+
+    /// 窗口标题模板，可用 {title} {artist} {album} {index} {total} {volume} {state} {bar} {name} {version}，
+    /// 默认为 "{title}-{artist}-{name}v{version}"
+    #[clap(long = "window-title")]
+    pub window_title_template: Option<String>,
+
+    /// 在 tmux/screen 中运行时，切歌后把所在窗口改名为 “标题 - 艺术家”，退出时恢复原来的窗口名
+    #[clap(long = "tmux-title")]
+    pub tmux_title: bool,
+
+    /// 状态栏配色主题，未指定时按 NO_COLOR、COLORTERM 环境变量自动选择
+    #[clap(long = "color", value_enum, value_name = "THEME")]
+    pub color: Option<ColorTheme>,
+
+    /// 在状态栏显示整个播放列表的收听进度 “[总37%]”（宽度足够时附带剩余时长），后台探测各歌曲的时长
+    #[clap(long = "playlist-progress")]
+    pub playlist_progress: bool,
+
+    /// --playlist-progress 中时长未知的歌曲按该秒数估算（进度前显示 “≈”）
+    #[clap(long = "assumed-duration", value_name = "SECS", default_value_t = 240, value_parser = clap::value_parser!(u64).range(1..))]
+    pub assumed_duration: u64,
+
+    /// 每首歌开始播放时发出终端提示音
+    #[clap(long = "bell-on-track-change")]
+    pub bell_on_track_change: bool,
+
+    /// 歌曲加载失败时发出终端提示音
+    #[clap(long = "bell-on-error")]
+    pub bell_on_error: bool,
+
+    /// 播放列表结束时（循环之前）自动把加载失败的歌曲追加到末尾重试，每首最多重试 2 次；
+    /// 不指定时询问是否重试（无终端模式下不重试）
+    #[clap(long = "retry-failed")]
+    pub retry_failed: bool,
+
+    /// 实际播放时长低于总时长的该百分比时，提示文件可能损坏（0 为关闭检查）
+    #[clap(long = "early-end-threshold", default_value = "90", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub early_end_threshold: u8,
+
+    /// 文件大小使用二进制前缀（KiB、MiB），默认使用十进制前缀（KB、MB）
+    #[clap(long = "binary-sizes")]
+    pub binary_sizes: bool,
+
+    /// 使用指定名称的输出设备，而不是系统默认设备（默认设备不可用时会自动尝试其他设备）
+    #[clap(long = "output-device")]
+    pub output_device: Option<String>,
+
+    /// 输出缓冲区大小（帧数），较小的值可以降低延迟，过小可能导致爆音；设备不支持时使用默认值
+    #[clap(long = "audio-buffer-size", value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(16..=65536))]
+    pub audio_buffer_size: Option<u32>,
+
     /// 播放音量
     #[clap(short = 'v', long = "volume", default_value = "75")]
     pub volume: u8,
-}
\ No newline at end of file
+
+    /// 播放倍速（0.5-3.0），播放中可按 +/- 调整；变速时音调随之改变
+    #[clap(long = "speed", default_value = "1.0", value_parser = parse_speed)]
+    pub speed: f32,
+
+    /// 把多声道混成单声道后从每个声道输出（例如只有一只耳机能用时）
+    #[clap(long = "mono")]
+    pub mono: bool,
+
+    /// 左右平衡（-1.0 全左，0 居中，1.0 全右），播放中可按 </> 调整
+    #[clap(long = "balance", default_value = "0", value_parser = parse_balance, allow_hyphen_values = true)]
+    pub balance: f32,
+
+    /// 每首歌开始前的提示：chime 在两首歌之间播放提示音，tts 朗读歌名和艺术家；按任意键跳过
+    #[clap(long = "announce", value_enum, default_value = "off")]
+    pub announce: Announce,
+
+    /// 按 D 键移除当前歌曲时：skip 立即跳到下一首（默认），finish 播完后再移除
+    #[clap(long = "remove-mode", value_enum, default_value = "skip")]
+    pub remove_mode: RemoveMode,
+
+    /// 按 D 键移除的歌曲记录到输入（播放列表文件或目录）旁边的 excluded.txt，下次启动时跳过其中的歌曲
+    #[clap(long = "use-excluded")]
+    pub use_excluded: bool,
+
+    /// 不启用括号粘贴模式（默认启用，误粘贴的文本会被整体丢弃，不会被当作按键）；终端不支持、出现乱码时使用
+    #[clap(long = "no-bracket-paste")]
+    pub no_bracket_paste: bool,
+
+    /// 不启用鼠标（默认滚轮向上/向下切换上一首/下一首，左键单击暂停/播放）；在 tmux 等需要鼠标选择文字的环境中使用
+    #[clap(long = "no-mouse")]
+    pub no_mouse: bool,
+
+    /// 不记忆每首歌的音量调整（默认播放中调整的音量会按歌曲记住，下次播放时自动应用，最多 ±30%）
+    #[clap(long = "no-volume-memory")]
+    pub no_volume_memory: bool,
+
+    /// 均衡器预设：bass 增强低音，treble 削减高音，vocal 突出人声，classical 提升高频，flat 不处理
+    #[clap(long = "equalizer", value_enum)]
+    pub equalizer: Option<EqPreset>,
+
+    /// 每首歌的音量在当前音量基础上随机浮动的百分比（如 5 表示 ±5%）
+    #[clap(long = "randomize-volume", value_parser = clap::value_parser!(u8).range(1..=50))]
+    pub randomize_volume: Option<u8>,
+
+    /// 响度均衡：按每首歌开头一小段的 RMS 把音量调向 -18 dBFS（近似值，开头不代表整首歌的响度）
+    #[clap(long = "loudness-normalize")]
+    pub loudness_normalize: bool,
+
+    /// 音量上限（1-100），初始音量和调节后的音量都不会超过该值
+    #[clap(long = "max-volume", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub max_volume: Option<u8>,
+
+    /// 不播放，而是将音频转码为指定格式写到标准输出（需重定向到文件或管道）
+    #[clap(long = "output-format", value_enum)]
+    pub output_format: Option<OutputFormat>,
+
+    /// 启动时将整理后的播放列表保存到该文件（.m3u/.m3u8 或 .txt），W 键也保存到这里
+    #[clap(long = "save-playlist", value_hint = ValueHint::FilePath)]
+    pub save_playlist: Option<PathBuf>,
+
+    /// 播放前把播放列表中所有歌曲的元数据导出到该文件（扩展名为 .json 时导出 JSON，否则为 CSV）；
+    /// 没有指定要播放的文件时导出当前目录并退出
+    #[clap(long = "export-metadata", value_hint = ValueHint::FilePath)]
+    pub export_metadata: Option<PathBuf>,
+
+    /// 从第一首文件名或标题包含该文本的歌曲开始播放（不区分大小写）
+    #[clap(long = "from-title")]
+    pub from_title: Option<String>,
+
+    /// 播放历史保留的条数（按 H 键查看），至少为 1
+    #[clap(long = "history-size", default_value = "20", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub history_size: usize,
+
+    /// 歌曲文件的读取方式：full 时预加载整首读入内存，播放中不再读取文件
+    #[clap(long = "buffer", value_enum, default_value = "stream")]
+    pub buffer: BufferMode,
+
+    /// --buffer full 时单个文件读入内存的大小上限（MB），超过时改为流式读取
+    #[clap(long = "buffer-cap", default_value = "200", value_parser = clap::value_parser!(u64).range(1..=16384))]
+    pub buffer_cap: u64,
+
+    /// 提前预加载的歌曲数量（1-5），网络存储等高延迟场景下可调大
+    #[clap(long = "preload", default_value = "1", value_parser = clap::value_parser!(u8).range(1..=5))]
+    pub preload: u8,
+
+    /// 网络流打开失败时的重试次数（0-10），间隔从 100ms 起每次加倍
+    #[clap(long = "network-retry", default_value = "3", value_parser = clap::value_parser!(u32).range(0..=10))]
+    pub network_retry: u32,
+}
+
+// 解析 --speed，限制在 0.5-3.0 之间
+fn parse_speed(value: &str) -> Result<f32, String> {
+    let speed: f32 = value.parse().map_err(|_| format!("'{}' 不是有效的数字", value))?;
+    if (0.5..=3.0).contains(&speed) {
+        Ok(speed)
+    } else {
+        Err("倍速应在 0.5 到 3.0 之间".to_string())
+    }
+}
+
+// 解析 render --gap（秒，可以是小数），限制在 0-60 秒之间
+fn parse_gap(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = value.parse().map_err(|_| format!("'{}' 不是有效的数字", value))?;
+    if (0.0..=60.0).contains(&seconds) {
+        Ok(Duration::from_secs_f64(seconds))
+    } else {
+        Err("间隔应在 0 到 60 秒之间".to_string())
+    }
+}
+
+// 解析 --balance，限制在 -1.0 到 1.0 之间
+fn parse_balance(value: &str) -> Result<f32, String> {
+    let balance: f32 = value.parse().map_err(|_| format!("'{}' 不是有效的数字", value))?;
+    if (-1.0..=1.0).contains(&balance) {
+        Ok(balance)
+    } else {
+        Err("平衡应在 -1.0 到 1.0 之间".to_string())
+    }
+}
+
+/// 解析时长参数：秒数（90）、分:秒（1:30）、时:分:秒（1:02:03），或带单位的 1h30m、2m5s、45s
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("'{}' 不是有效的时长（例如 90、1:30、1h30m）", value);
+    let text = value.trim().to_ascii_lowercase();
+    if text.contains(':') {
+        let parts: Vec<&str> = text.split(':').collect();
+        if parts.len() > 3 {
+            return Err(invalid());
+        }
+        let mut secs: u64 = 0;
+        for part in parts {
+            let part = part.parse::<u64>().map_err(|_| invalid())?;
+            secs = secs.checked_mul(60).and_then(|secs| secs.checked_add(part)).ok_or_else(invalid)?;
+        }
+        return Ok(Duration::from_secs(secs));
+    }
+    if let Ok(secs) = text.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let mut secs: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let part = number.parse::<u64>().map_err(|_| invalid())?;
+        secs = part.checked_mul(unit).and_then(|part| secs.checked_add(part)).ok_or_else(invalid)?;
+        number.clear();
+    }
+    // 最后一段缺少单位（例如 1h30）时有歧义，不接受
+    if !number.is_empty() || text.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// 将指定 shell 的自动补全脚本写入 out（命令行中为标准输出）
+pub fn print_completions(shell: Shell, out: &mut impl Write) {
+    let mut command = Cli::command();
+    let bin_name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, bin_name, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_in_every_format() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1:30"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1:02:03"), Ok(Duration::from_secs(3723)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(" 2M5S "), Ok(Duration::from_secs(125)));
+        assert_eq!(parse_duration("45s"), Ok(Duration::from_secs(45)));
+    }
+
+    #[test]
+    fn malformed_durations_are_rejected() {
+        for value in ["", "1:2:3:4", "1h30", "1x", "-5", "1:aa", "h"] {
+            assert!(parse_duration(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn overflowing_durations_are_rejected() {
+        for value in ["99999999999999999h", "18446744073709551615:00", "18446744073709551615s1s", "99999999999999999999"] {
+            assert!(parse_duration(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn completions_cover_flags_and_subcommands() {
+        let subcommands: Vec<String> = Cli::command().get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+        assert!(subcommands.contains(&"completions".to_string()));
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            print_completions(*shell, &mut script);
+            let script = String::from_utf8(script).unwrap();
+            // fish 的长选项写作 `-l volume`
+            let volume = if *shell == Shell::Fish { "-l volume" } else { "--volume" };
+            assert!(script.contains(volume), "{} 的补全脚本缺少 --volume", shell);
+            for name in &subcommands {
+                assert!(script.contains(name.as_str()), "{} 的补全脚本缺少子命令 {}", shell, name);
+            }
+        }
+    }
+
+    #[test]
+    fn history_size_must_be_positive() {
+        assert_eq!(Cli::try_parse_from(["mddplayer", "a.mp3"]).unwrap().play.history_size, 20);
+        assert_eq!(Cli::try_parse_from(["mddplayer", "--history-size", "1", "a.mp3"]).unwrap().play.history_size, 1);
+        assert!(Cli::try_parse_from(["mddplayer", "--history-size", "0", "a.mp3"]).is_err());
+    }
+
+    #[test]
+    fn network_retry_is_bounded() {
+        for (value, accepted) in [("0", true), ("10", true), ("11", false), ("4294967295", false)] {
+            assert_eq!(Cli::try_parse_from(["mddplayer", "--network-retry", value, "a.mp3"]).is_ok(), accepted, "{}", value);
+        }
+    }
+}
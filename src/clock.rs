@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+/// 当前歌曲的播放位置。位置由实际经过的播放时间（不含暂停）按倍速换算为歌曲内的位置（媒体时间），
+/// 不依赖 sink：重建输出设备、定位或调整倍速后都以时钟的位置为准，状态栏、窗口标题和 --resume 保存的位置都从这里读取
+pub struct PlaybackClock {
+    // 开始计时的时刻和当时在歌曲内的位置（从中间开始播放时跳过的部分计入播放时间）
+    start: Instant,
+    start_offset: Duration,
+    paused_duration: Duration,
+    // 当前这次暂停开始的时刻
+    pause_started: Option<Instant>,
+    // 最近一次调整倍速或定位时的 (实际播放时间, 歌曲内位置)
+    anchor: (Duration, Duration),
+    speed: f32,
+    // 最近一次调整倍速或定位之前已经播放的歌曲内时长（定位跳过的部分不计入）
+    listened_before_anchor: Duration,
+}
+
+// 各方法的 now 为调用时的时刻（Instant::now()），由调用者传入
+impl PlaybackClock {
+    /// 从歌曲内 start_offset 处开始计时；paused 为 true 时以暂停状态开始
+    pub fn new(start_offset: Duration, speed: f32, paused: bool, now: Instant) -> Self {
+        PlaybackClock {
+            start: now,
+            start_offset,
+            paused_duration: Duration::ZERO,
+            pause_started: paused.then_some(now),
+            anchor: (start_offset, start_offset),
+            speed,
+            listened_before_anchor: Duration::ZERO,
+        }
+    }
+
+    /// 记下暂停或继续播放的时刻。已经处于该状态时不做任何事，
+    /// 因此可以在每次刷新时用 sink 的状态调用，补记其他途径（例如重建输出设备）造成的变化
+    pub fn set_paused(&mut self, paused: bool, now: Instant) {
+        if paused {
+            self.pause_started.get_or_insert(now);
+        } else if let Some(start) = self.pause_started.take() {
+            self.paused_duration += now.saturating_duration_since(start);
+        }
+    }
+
+    /// 实际经过的播放时间（不含暂停时间，包括开始时跳过的部分），暂停期间停在暂停的那一刻
+    pub fn wall_time(&self, now: Instant) -> Duration {
+        let end = self.pause_started.unwrap_or(now);
+        self.start_offset + end.saturating_duration_since(self.start).saturating_sub(self.paused_duration)
+    }
+
+    /// 歌曲内的当前位置
+    pub fn position(&self, now: Instant) -> Duration {
+        media_position(self.wall_time(now), self.anchor, self.speed)
+    }
+
+    /// 本首歌实际收听的歌曲内时长（按倍速换算，与歌曲时长可以直接比较）。
+    /// 不含暂停时间、开始时跳过的部分和定位跳过的部分
+    pub fn listened(&self, now: Instant) -> Duration {
+        self.listened_before_anchor + self.position(now).saturating_sub(self.anchor.1)
+    }
+
+    /// 调整倍速：之前的部分按原来的倍速换算，之后按新的倍速
+    pub fn set_speed(&mut self, speed: f32, now: Instant) {
+        self.listened_before_anchor = self.listened(now);
+        self.anchor = (self.wall_time(now), self.position(now));
+        self.speed = speed;
+    }
+
+    /// 定位到歌曲内的 position
+    pub fn seek(&mut self, position: Duration, now: Instant) {
+        self.listened_before_anchor = self.listened(now);
+        self.anchor = (self.wall_time(now), position);
+    }
+}
+
+/// 倍速播放时把实际经过的播放时间换算为歌曲内的位置（媒体时间）。
+/// anchor 为最近一次调整倍速或定位时的 (实际播放时间, 歌曲内位置)
+pub fn media_position(wall_time: Duration, anchor: (Duration, Duration), speed: f32) -> Duration {
+    anchor.1 + wall_time.saturating_sub(anchor.0).mul_f32(speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn counts_from_the_start_offset() {
+        let t0 = Instant::now();
+        let clock = PlaybackClock::new(secs(30), 1.0, false, t0);
+        assert_eq!(clock.position(t0), secs(30));
+        assert_eq!(clock.position(t0 + secs(5)), secs(35));
+        // 开始时跳过的部分不算收听
+        assert_eq!(clock.listened(t0 + secs(5)), secs(5));
+    }
+
+    #[test]
+    fn pauses_stop_the_clock() {
+        let t0 = Instant::now();
+        let mut clock = PlaybackClock::new(Duration::ZERO, 1.0, true, t0);
+        assert_eq!(clock.position(t0 + secs(3)), Duration::ZERO);
+        clock.set_paused(false, t0 + secs(3));
+        clock.set_paused(true, t0 + secs(13));
+        // 每次刷新都会用 sink 的状态调用，重复调用不改变暂停开始的时刻
+        clock.set_paused(true, t0 + secs(15));
+        assert_eq!(clock.position(t0 + secs(20)), secs(10));
+        clock.set_paused(false, t0 + secs(25));
+        clock.set_paused(false, t0 + secs(26));
+        assert_eq!(clock.position(t0 + secs(30)), secs(15));
+        assert_eq!(clock.listened(t0 + secs(30)), secs(15));
+    }
+
+    #[test]
+    fn speed_changes_apply_from_the_switch() {
+        let t0 = Instant::now();
+        let mut clock = PlaybackClock::new(Duration::ZERO, 1.0, false, t0);
+        clock.set_speed(2.0, t0 + secs(10));
+        assert_eq!(clock.position(t0 + secs(15)), secs(20));
+        clock.set_speed(0.5, t0 + secs(15));
+        assert_eq!(clock.position(t0 + secs(19)), secs(22));
+        // 收听时长按歌曲内的时长计
+        assert_eq!(clock.listened(t0 + secs(19)), secs(22));
+    }
+
+    #[test]
+    fn seeks_do_not_count_as_listened() {
+        let t0 = Instant::now();
+        let mut clock = PlaybackClock::new(Duration::ZERO, 1.0, false, t0);
+        clock.seek(secs(60), t0 + secs(10));
+        assert_eq!(clock.position(t0 + secs(12)), secs(62));
+        assert_eq!(clock.listened(t0 + secs(12)), secs(12));
+        clock.seek(secs(5), t0 + secs(12));
+        assert_eq!(clock.position(t0 + secs(14)), secs(7));
+        assert_eq!(clock.listened(t0 + secs(14)), secs(14));
+    }
+
+    // 重建输出设备时新的 sink 从时钟的位置开始播放并沿用暂停状态，时钟本身不重置，
+    // 之后每次刷新用新 sink 的状态调用 set_paused
+    #[test]
+    fn position_carries_across_a_rebuild() {
+        let t0 = Instant::now();
+        let mut clock = PlaybackClock::new(Duration::ZERO, 1.0, false, t0);
+        clock.set_speed(1.5, t0 + secs(10));
+        clock.set_paused(true, t0 + secs(20));
+        let rebuilt_at = clock.position(t0 + secs(22));
+        assert_eq!(rebuilt_at, secs(25));
+        // 重建后的刷新：新 sink 仍处于暂停
+        clock.set_paused(true, t0 + secs(23));
+        assert_eq!(clock.position(t0 + secs(29)), rebuilt_at);
+        clock.set_paused(false, t0 + secs(30));
+        assert_eq!(clock.position(t0 + secs(32)), secs(28));
+        // 重建后定位和调整倍速照常生效
+        clock.seek(secs(20), t0 + secs(32));
+        clock.set_speed(1.0, t0 + secs(36));
+        assert_eq!(clock.position(t0 + secs(40)), secs(30));
+        assert_eq!(clock.listened(t0 + secs(40)), secs(28 + 10));
+    }
+}
@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use rodio::{Decoder, Source};
+
+use crate::cli::EncodingFix;
+use crate::metadata::{get_title_artist_info, get_total_duration, read_tag_details};
+use crate::utils::{parallel_map, PlaylistEntry};
+
+/// 标签相同时允许的时长误差
+const TAG_DURATION_TOLERANCE: Duration = Duration::from_secs(2);
+/// 按音频去重时解码并比较的开头秒数（从第一个非静音采样算起）
+const AUDIO_FINGERPRINT_SECONDS: u64 = 10;
+/// 音频指纹把这段时间分成 129 段，每位记录一段的能量是否比前一段高
+const FINGERPRINT_BITS: usize = 128;
+/// 两个指纹不同的位数不超过该值时视为同一首歌（容忍有损编码、音量和采样率不同带来的差异）
+const FINGERPRINT_TOLERANCE: u32 = 16;
+/// 开头低于该幅度（约 -40 dBFS）的采样视为静音，计算指纹前去掉（不同编码器在开头补的静音长度不同）
+const SILENCE_AMPLITUDE: i32 = 328;
+/// 视为无损格式的扩展名
+const LOSSLESS_EXTENSIONS: &[&str] = &["flac", "wav", "ape", "wv", "aiff", "aif"];
+
+/// 一组重复文件中保留和移除的文件
+pub struct Duplicate {
+    pub kept: PathBuf,
+    pub dropped: PathBuf,
+}
+
+/// 按规范化路径去重，同一个文件只保留第一次出现的位置，返回移除的数量
+pub fn dedup_by_path(entries: &mut Vec<PlaylistEntry>) -> usize {
+    let before = entries.len();
+    let mut seen = HashSet::new();
+    entries.retain(|entry| seen.insert(std::fs::canonicalize(&entry.path).unwrap_or_else(|_| entry.path.clone())));
+    before - entries.len()
+}
+
+/// 按标签判断重复时比较的内容
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagKey {
+    pub title: String,
+    pub artist: String,
+    pub duration: Duration,
+}
+
+/// 规范化标签文本：转为小写，标点和连续空白合并为一个空格
+pub fn normalize_tag_text(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 标题、艺术家相同且时长相差不超过 2 秒时视为同一首歌（时长未知的不比较）
+pub fn same_tags(a: &TagKey, b: &TagKey) -> bool {
+    a.title == b.title
+        && a.artist == b.artist
+        && !a.duration.is_zero()
+        && !b.duration.is_zero()
+        && a.duration.abs_diff(b.duration) <= TAG_DURATION_TOLERANCE
+}
+
+/// 读取文件的去重标签，没有标题标签时返回 None（不参与按标签去重）
+pub fn tag_key(path: &Path, fix: EncodingFix) -> Option<TagKey> {
+    let (title, artist) = get_title_artist_info(path, fix);
+    if title == "未知" || title == "未知音乐名" {
+        return None;
+    }
+    Some(TagKey {
+        title: normalize_tag_text(&title),
+        artist: normalize_tag_text(&artist),
+        duration: get_total_duration(path),
+    })
+}
+
+/// 选择保留哪个文件时比较的音质信息
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileQuality {
+    pub lossless: bool,
+    /// 码率（kbps），未知时为 None
+    pub bitrate: Option<u32>,
+}
+
+/// 比较两个文件的音质：无损优先，其次码率高的优先；Greater 表示 a 更好
+pub fn compare_quality(a: &FileQuality, b: &FileQuality) -> Ordering {
+    a.lossless.cmp(&b.lossless).then_with(|| a.bitrate.cmp(&b.bitrate))
+}
+
+fn file_quality(path: &Path) -> FileQuality {
+    let lossless = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| LOSSLESS_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+    let bitrate = read_tag_details(path, EncodingFix::Off).ok().and_then(|details| details.bitrate);
+    FileQuality { lossless, bitrate }
+}
+
+/// 按标签去重：重复的歌曲只保留音质最好的文件，放在这首歌第一次出现的位置。
+/// 读取标签的进度输出到标准错误，返回每个被移除的文件及保留的文件
+pub fn dedup_by_tag(entries: &mut Vec<PlaylistEntry>, fix: EncodingFix) -> Vec<Duplicate> {
+    let total = entries.len();
+    let mut kept: Vec<(PlaylistEntry, Option<TagKey>, Option<FileQuality>)> = Vec::with_capacity(total);
+    // (标题, 艺术家) -> kept 中对应的下标，同名的歌曲可能有多个时长不同的版本
+    let mut by_name: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (index, entry) in std::mem::take(entries).into_iter().enumerate() {
+        eprint!("\r检查重复... {}/{}", index + 1, total);
+        let Some(key) = tag_key(&entry.path, fix) else {
+            kept.push((entry, None, None));
+            continue;
+        };
+        let name = (key.title.clone(), key.artist.clone());
+        let existing = by_name
+            .get(&name)
+            .and_then(|indices| indices.iter().copied().find(|&i| kept[i].1.as_ref().is_some_and(|k| same_tags(k, &key))));
+        let Some(existing) = existing else {
+            by_name.entry(name).or_default().push(kept.len());
+            kept.push((entry, Some(key), None));
+            continue;
+        };
+        let existing_quality = match kept[existing].2 {
+            Some(quality) => quality,
+            None => {
+                let quality = file_quality(&kept[existing].0.path);
+                kept[existing].2 = Some(quality);
+                quality
+            }
+        };
+        let quality = file_quality(&entry.path);
+        if compare_quality(&quality, &existing_quality) == Ordering::Greater {
+            let old = std::mem::replace(&mut kept[existing], (entry, Some(key), Some(quality)));
+            duplicates.push(Duplicate { kept: kept[existing].0.path.clone(), dropped: old.0.path });
+        } else {
+            duplicates.push(Duplicate { kept: kept[existing].0.path.clone(), dropped: entry.path });
+        }
+    }
+    if total > 0 {
+        eprintln!();
+    }
+    *entries = kept.into_iter().map(|(entry, _, _)| entry).collect();
+    duplicates
+}
+
+/// 按标题和艺术家去重（不区分大小写，不比较时长和音质）：多个格式的同一首歌只保留第一次出现的文件。
+/// 多线程预先读取所有歌曲的标签，没有标题标签的歌曲保留，返回移除的数量
+pub fn dedup_by_title(entries: &mut Vec<PlaylistEntry>, fix: EncodingFix) -> usize {
+    let paths: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+    let keys = parallel_map(&paths, "读取标签", |path| title_key(path, fix));
+    let before = entries.len();
+    let mut seen = HashSet::new();
+    let mut keys = keys.into_iter();
+    entries.retain(|_| keys.next().flatten().is_none_or(|key| seen.insert(key)));
+    before - entries.len()
+}
+
+/// --skip-duplicates-by-title 比较的 (标题, 艺术家)，转为小写；没有标题标签时返回 None（不参与去重）
+pub fn title_key(path: &Path, fix: EncodingFix) -> Option<(String, String)> {
+    let (title, artist) = get_title_artist_info(path, fix);
+    (title != "未知" && title != "未知音乐名").then(|| (title.to_lowercase(), artist.to_lowercase()))
+}
+
+/// 根据交错采样计算音频指纹：去掉开头的静音后，把 10 秒（不足时为全部）分成 129 段，
+/// 每位表示一段的能量是否比前一段高。只记录响度的起伏，和编码格式、码率、音量、采样率无关；
+/// 采样太少无法分段时返回 None
+pub fn fingerprint_samples(samples: &[i16], channels: u16, sample_rate: u32) -> Option<u128> {
+    let channels = channels.max(1) as usize;
+    // 混合为单声道
+    let mono: Vec<i32> = samples.chunks(channels).map(|frame| frame.iter().map(|&s| s as i32).sum::<i32>() / frame.len() as i32).collect();
+    let start = mono.iter().position(|sample| sample.abs() >= SILENCE_AMPLITUDE)?;
+    let window = (mono.len() - start).min(AUDIO_FINGERPRINT_SECONDS as usize * sample_rate as usize);
+    let segment = window / (FINGERPRINT_BITS + 1);
+    if segment == 0 {
+        return None;
+    }
+    let energies: Vec<f64> = mono[start..start + segment * (FINGERPRINT_BITS + 1)]
+        .chunks(segment)
+        .map(|chunk| chunk.iter().map(|&sample| (sample as f64).powi(2)).sum())
+        .collect();
+    Some(energies.windows(2).fold(0u128, |bits, pair| (bits << 1) | (pair[1] > pair[0]) as u128))
+}
+
+/// 两个音频指纹是否属于同一首歌
+pub fn similar_fingerprints(a: u128, b: u128) -> bool {
+    (a ^ b).count_ones() <= FINGERPRINT_TOLERANCE
+}
+
+/// 解码开头一段并计算音频指纹（见 fingerprint_samples），无法解码时返回 None
+pub fn audio_fingerprint(path: &Path) -> Option<u128> {
+    let decoder = Decoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+    let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+    // 多解码几秒，留给开头的静音
+    let limit = (AUDIO_FINGERPRINT_SECONDS + 5) * sample_rate as u64 * channels as u64;
+    let samples: Vec<i16> = decoder.take(limit as usize).collect();
+    fingerprint_samples(&samples, channels, sample_rate)
+}
+
+/// 在后台线程中按播放列表顺序计算每首歌的音频指纹，发现重复时把音质较差的文件发送给播放循环跳过
+pub fn start_audio_dedup(playlist: Vec<PathBuf>, tx: Sender<Duplicate>) {
+    thread::spawn(move || {
+        // 每首不同的歌保留一个文件：(指纹, 路径, 音质)
+        let mut seen: Vec<(u128, PathBuf, FileQuality)> = Vec::new();
+        for path in playlist {
+            let Some(fingerprint) = audio_fingerprint(&path) else { continue };
+            let quality = file_quality(&path);
+            let Some(existing) = seen.iter().position(|(kept, _, _)| similar_fingerprints(*kept, fingerprint)) else {
+                seen.push((fingerprint, path, quality));
+                continue;
+            };
+            let (_, kept_path, kept_quality) = seen[existing].clone();
+            if kept_path == path {
+                continue;
+            }
+            let duplicate = if compare_quality(&quality, &kept_quality) == Ordering::Greater {
+                seen[existing] = (fingerprint, path.clone(), quality);
+                Duplicate { kept: path, dropped: kept_path }
+            } else {
+                Duplicate { kept: kept_path, dropped: path }
+            };
+            if tx.send(duplicate).is_err() {
+                // 主线程已退出
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag_key(title: &str, artist: &str, seconds: u64) -> TagKey {
+        TagKey { title: normalize_tag_text(title), artist: normalize_tag_text(artist), duration: Duration::from_secs(seconds) }
+    }
+
+    #[test]
+    fn tag_text_ignores_case_and_punctuation() {
+        assert_eq!(normalize_tag_text("  Hello,   World! "), "hello world");
+        assert_eq!(normalize_tag_text("Don't Stop (Live)"), "don t stop live");
+        assert_eq!(normalize_tag_text("晴天 - 周杰伦"), "晴天 周杰伦");
+        assert_eq!(normalize_tag_text("..."), "");
+    }
+
+    #[test]
+    fn same_tags_allow_two_seconds_of_difference() {
+        let song = tag_key("Hello World", "Artist", 200);
+        assert!(same_tags(&song, &tag_key("hello, world", "ARTIST", 202)));
+        assert!(!same_tags(&song, &tag_key("Hello World", "Artist", 203)));
+        assert!(!same_tags(&song, &tag_key("Hello World", "Other", 200)));
+        // 时长未知时不视为重复
+        assert!(!same_tags(&tag_key("Hello World", "Artist", 0), &tag_key("Hello World", "Artist", 0)));
+    }
+
+    #[test]
+    fn lossless_beats_higher_bitrate() {
+        let flac = FileQuality { lossless: true, bitrate: Some(900) };
+        let mp3_320 = FileQuality { lossless: false, bitrate: Some(320) };
+        let mp3_128 = FileQuality { lossless: false, bitrate: Some(128) };
+        let unknown = FileQuality { lossless: false, bitrate: None };
+        assert_eq!(compare_quality(&flac, &mp3_320), Ordering::Greater);
+        assert_eq!(compare_quality(&FileQuality { bitrate: None, ..flac }, &mp3_320), Ordering::Greater);
+        assert_eq!(compare_quality(&mp3_128, &mp3_320), Ordering::Less);
+        assert_eq!(compare_quality(&unknown, &mp3_128), Ordering::Less);
+        assert_eq!(compare_quality(&mp3_320, &mp3_320), Ordering::Equal);
+    }
+
+    // 按响度包络 envelope(t) 调制的 440 Hz 正弦波（立体声），开头有 lead_in 秒静音，叠加幅度为 noise 的伪随机噪声
+    fn synth(envelope: fn(f64) -> f64, sample_rate: u32, lead_in: f64, gain: f64, noise: i32) -> Vec<i16> {
+        let mut seed: u32 = 12345;
+        let frames = ((12.0 + lead_in) * sample_rate as f64) as usize;
+        (0..frames)
+            .flat_map(|frame| {
+                let t = frame as f64 / sample_rate as f64 - lead_in;
+                let clean = if t < 0.0 { 0.0 } else { envelope(t) * (t * 440.0 * std::f64::consts::TAU).sin() * 20000.0 * gain };
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let sample = (clean as i32 + (seed >> 16) as i32 % (noise + 1)).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    fn song_a(t: f64) -> f64 {
+        0.2 + 0.8 * ((t * 1.7).sin() * (t * 0.45).cos()).abs()
+    }
+
+    fn song_b(t: f64) -> f64 {
+        0.2 + 0.8 * (t * 2.9 + 1.0).sin().abs()
+    }
+
+    #[test]
+    fn fingerprint_tolerates_encoding_differences() {
+        let original = fingerprint_samples(&synth(song_a, 44100, 0.0, 1.0, 0), 2, 44100).unwrap();
+        // 另一种格式的同一首歌：采样率不同、音量更低、开头多了编码器补的静音、带有量化噪声
+        let other_copy = fingerprint_samples(&synth(song_a, 48000, 0.05, 0.7, 200), 2, 48000).unwrap();
+        assert!(similar_fingerprints(original, other_copy), "{} 位不同", (original ^ other_copy).count_ones());
+        let other_song = fingerprint_samples(&synth(song_b, 44100, 0.0, 1.0, 0), 2, 44100).unwrap();
+        assert!(!similar_fingerprints(original, other_song), "{} 位不同", (original ^ other_song).count_ones());
+    }
+
+    #[test]
+    fn silence_has_no_fingerprint() {
+        assert_eq!(fingerprint_samples(&[0; 88200], 2, 44100), None);
+        assert_eq!(fingerprint_samples(&[1000; 100], 1, 44100), None);
+    }
+}
@@ -0,0 +1,753 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+// 引入 unicode_width 库
+use unicode_width::UnicodeWidthStr;
+// 终端光标控制
+use crossterm::{cursor, execute};
+use crossterm::style::{Color, ResetColor, SetForegroundColor};
+
+use crate::cli::{NAME, VERSION};
+use crate::theme::Theme;
+use crate::utils::{format_duration, format_duration_precise, truncate_string};
+
+/// 默认的窗口标题模板
+pub const DEFAULT_WINDOW_TITLE: &str = "{title}-{artist}-{name}v{version}";
+
+/// 渲染状态栏所需的全部数据，渲染过程不涉及任何 IO，便于测试
+pub struct DisplayState<'a> {
+    pub current_index: usize,
+    pub total_tracks: usize,
+    pub is_random: bool,
+    pub loop_mode: LoopMode,
+    /// 指定循环次数时的 (当前轮次, 总轮次)
+    pub cycle: Option<(u32, u32)>,
+    /// 重复播放同一首歌时的 (当前遍数, 总遍数)，单曲循环时总遍数为 None
+    pub repeat: Option<(u32, Option<u32>)>,
+    pub title: &'a str,
+    pub artist: &'a str,
+    pub track_path: &'a str,
+    /// 碟号和音轨号，两者都有且终端宽度不小于 80 时显示为 “D1T03”
+    pub disc_number: Option<u32>,
+    pub track_number: Option<u32>,
+    pub current_time: Duration,
+    /// 定位后短暂以 “MM:SS.mmm” 显示播放时间（比平时宽 4 列，状态栏按实际宽度选择显示级别）
+    pub precise_time: bool,
+    pub total_duration: Duration,
+    /// 音量（静音时为静音前的音量）
+    pub volume: f32,
+    /// 静音时音量显示为 “静音 68%”
+    pub muted: bool,
+    /// 暂停时播放时间前显示 “⏸”
+    pub paused: bool,
+    /// 音量上限，设置后音量显示为 “当前/上限%”
+    pub max_volume: Option<f32>,
+    /// 播放倍速，不为 1 时显示为 “[1.5x]”
+    pub speed: f32,
+    /// 终端宽度（列数），状态栏会截断或补齐到该宽度
+    pub terminal_width: usize,
+    /// 临时提示（见 Osd），有值时代替歌名显示
+    pub osd_message: Option<&'a str>,
+    /// 临时错误提示（例如定位失败时的 “Seek N/A”），有值时以红色代替播放时间显示
+    pub progress_message: Option<&'a str>,
+    /// 配色主题，None 时不输出颜色
+    pub theme: Option<Theme>,
+    /// 整个播放列表的收听进度（--playlist-progress），None 时不显示
+    pub playlist_progress: Option<PlaylistProgress>,
+}
+
+/// 整个播放列表的收听进度，显示为 “[总37%]”，宽度足够时附带剩余时长 “[总37% 剩2h41m]”；
+/// 有歌曲的时长未知（按估算值计算）时显示为 “[总≈37%]”
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlaylistProgress {
+    pub percent: u8,
+    pub remaining: Duration,
+    pub estimated: bool,
+}
+
+// 剩余时长：不到一小时为 “41m”，否则为 “2h41m”
+fn format_remaining(remaining: Duration) -> String {
+    let minutes = remaining.as_secs().div_ceil(60);
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h{:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// 倍速文本：最多两位小数并去掉末尾的 0，如 “1.25”、“1.5”、“2”（避免 1.35000002 这样的浮点误差）
+pub fn format_speed(speed: f32) -> String {
+    let text = format!("{:.2}", speed);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// 播放模式，播放中按 R 依次切换
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopMode {
+    /// 播完列表后停止
+    Off,
+    /// 循环播放整个列表
+    List,
+    /// 单曲循环：一直重复当前歌曲，直到手动切歌
+    Track,
+}
+
+impl LoopMode {
+    pub fn next(self) -> LoopMode {
+        match self {
+            LoopMode::Off => LoopMode::List,
+            LoopMode::List => LoopMode::Track,
+            LoopMode::Track => LoopMode::Off,
+        }
+    }
+
+    /// 状态栏中的模式标记
+    fn indicator(self) -> &'static str {
+        match self {
+            LoopMode::Off => "单",
+            LoopMode::List => "循",
+            LoopMode::Track => "复",
+        }
+    }
+
+    /// 切换模式时的提示
+    pub fn message(self) -> &'static str {
+        match self {
+            LoopMode::Off => "顺序播放",
+            LoopMode::List => "列表循环",
+            LoopMode::Track => "单曲循环",
+        }
+    }
+}
+
+/// 终端较窄时状态栏逐级省略的内容
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusTier {
+    /// 显示全部内容
+    Full,
+    /// 省略文件格式
+    NoExtension,
+    /// 再省略播放模式
+    NoMode,
+    /// 再省略总时长
+    NoTotal,
+    /// 只显示 “MM:SS ▶”（暂停时为 “MM:SS ⏸”）
+    Minimal,
+}
+
+// 除 Minimal 外，至少要给歌名留出的宽度
+const MIN_MUSIC_INFO_WIDTH: usize = 6;
+// 显示碟号和音轨号所需的最小终端宽度
+const DISC_TRACK_MIN_WIDTH: usize = 80;
+
+/// 生成状态栏文本（已补齐到终端宽度）
+pub fn render_progress_line(state: &DisplayState) -> String {
+    let terminal_width = state.terminal_width;
+    let tier = select_status_tier(state);
+    let (mut segments, suffix) = status_segments(state, tier);
+    let theme = state.theme.as_ref();
+    if tier == StatusTier::Minimal {
+        // 连最简的内容也放不下时截断，不再着色
+        let width = segments_width(&segments);
+        if width > terminal_width {
+            let text: String = segments.into_iter().map(|(text, _)| text).collect();
+            return pad_to_width(&text, terminal_width);
+        }
+        return paint(&segments, theme) + &" ".repeat(terminal_width - width);
+    }
+
+    let music_info_width = terminal_width.saturating_sub(segments_width(&segments) + 2 + segments_width(&suffix));
+    // 宽度按显示列数计算（中日韩文字和 emoji 占 2 列）；标签里的制表符、换行等控制字符会打乱单行状态栏，替换为空格
+    let title = replace_control_chars(state.title);
+    let music_info_content = format!("{}-{}", title, replace_control_chars(state.artist));
+    let music_info = if let Some(message) = state.osd_message {
+        truncate_string(message, music_info_width)
+    } else if music_info_width < 15 {
+        truncate_string(&title, music_info_width)
+    } else {
+        truncate_string(&music_info_content, music_info_width)
+    };
+
+    segments.push(("[".to_string(), None));
+    segments.push((music_info, Some(Part::Title)));
+    segments.push(("]".to_string(), None));
+    segments.extend(suffix);
+    // 颜色控制序列不占宽度，按文字的宽度补齐
+    let padding = " ".repeat(terminal_width.saturating_sub(segments_width(&segments)));
+    paint(&segments, theme) + &padding
+}
+
+// 状态栏中按主题着色的部分；Error 为代替播放时间的错误提示，固定为红色
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Part {
+    TrackCount,
+    Mode,
+    Title,
+    Time,
+    Volume,
+    Error,
+}
+
+// 状态栏中的一段文字和它所属的部分（None 为不着色）
+type Segment = (String, Option<Part>);
+
+fn segments_width(segments: &[Segment]) -> usize {
+    segments.iter().map(|(text, _)| text.as_str().width()).sum()
+}
+
+// 拼接各段文字；有主题时着色的段之前设置前景色，之后恢复默认颜色
+fn paint(segments: &[Segment], theme: Option<&Theme>) -> String {
+    segments
+        .iter()
+        .map(|(text, part)| match (theme, part) {
+            (Some(theme), Some(part)) => {
+                let color = match part {
+                    Part::TrackCount => theme.track_count,
+                    Part::Mode => theme.mode,
+                    Part::Title => theme.title,
+                    Part::Time => theme.time,
+                    Part::Volume => theme.volume,
+                    Part::Error => Color::Red,
+                };
+                format!("{}{}{}", SetForegroundColor(color), text, ResetColor)
+            }
+            _ => text.clone(),
+        })
+        .collect()
+}
+
+/// 按终端宽度选择状态栏的显示级别：从完整显示开始逐级省略，直到固定部分加上最少的歌名宽度能放下
+pub fn select_status_tier(state: &DisplayState) -> StatusTier {
+    [StatusTier::Full, StatusTier::NoExtension, StatusTier::NoMode, StatusTier::NoTotal]
+        .into_iter()
+        .find(|&tier| {
+            let (prefix, suffix) = status_segments(state, tier);
+            segments_width(&prefix) + 2 + segments_width(&suffix) + MIN_MUSIC_INFO_WIDTH <= state.terminal_width
+        })
+        .unwrap_or(StatusTier::Minimal)
+}
+
+// 状态栏中歌名之前和之后的部分；Minimal 级别只有前一部分
+fn status_segments(state: &DisplayState, tier: StatusTier) -> (Vec<Segment>, Vec<Segment>) {
+    let (current_time_str, time_part) = match state.progress_message {
+        Some(message) => (message.to_string(), Part::Error),
+        None if state.precise_time => (format_duration_precise(state.current_time), Part::Time),
+        None => (format_duration(state.current_time), Part::Time),
+    };
+    if tier == StatusTier::Minimal {
+        let state_str = if state.paused { "⏸" } else { "▶" };
+        return (vec![(current_time_str, Some(time_part)), (format!(" {}", state_str), None)], Vec::new());
+    }
+    let mut prefix = vec![(format!("[{}/{}]", state.current_index + 1, state.total_tracks), Some(Part::TrackCount))];
+    // 指定了循环次数时，附带显示当前轮次
+    if let Some((current_cycle, total_cycles)) = state.cycle {
+        prefix.push((format!("[Cycle {}/{}]", current_cycle, total_cycles), Some(Part::Mode)));
+    }
+    match state.repeat {
+        Some((pass, Some(total))) => prefix.push((format!("[{}/{}遍]", pass, total), Some(Part::Mode))),
+        Some((pass, None)) => prefix.push((format!("[{}遍]", pass), Some(Part::Mode))),
+        None => {}
+    }
+    if matches!(tier, StatusTier::Full | StatusTier::NoExtension) {
+        let random_str = if state.is_random { "随" } else { "顺" };
+        prefix.push((format!("[{}|{}]", random_str, state.loop_mode.indicator()), Some(Part::Mode)));
+        if let Some(progress) = state.playlist_progress {
+            let approx = if progress.estimated { "≈" } else { "" };
+            let text = if tier == StatusTier::Full {
+                format!("[总{}{}% 剩{}]", approx, progress.percent, format_remaining(progress.remaining))
+            } else {
+                format!("[总{}{}%]", approx, progress.percent)
+            };
+            prefix.push((text, Some(Part::TrackCount)));
+        }
+    }
+    if tier == StatusTier::Full {
+        let ext = state.track_path.split('.').next_back().unwrap_or("未知").to_uppercase();
+        prefix.push((format!("[{}]", ext), None));
+        if let (Some(disc), Some(track), true) = (state.disc_number, state.track_number, state.terminal_width >= DISC_TRACK_MIN_WIDTH) {
+            prefix.push((format!("[D{}T{:02}]", disc, track), None));
+        }
+    }
+
+    let time_str = if tier == StatusTier::NoTotal || state.progress_message.is_some() {
+        current_time_str
+    } else {
+        format!("{}/{}", current_time_str, format_duration(state.total_duration))
+    };
+    let time_str = if state.paused { format!("⏸ {}", time_str) } else { time_str };
+    // 设置了音量上限时显示为 “当前/上限%”
+    let volume_str = match state.max_volume {
+        Some(cap) => format!("{:.0}/{:.0}%", state.volume * 100.0, cap * 100.0),
+        None => format!("{:.0}%", state.volume * 100.0),
+    };
+    // 静音时同时显示取消静音后恢复的音量，和把音量调到 0% 区分开
+    let volume_str = if state.muted { format!("静音 {}", volume_str) } else { volume_str };
+    let mut suffix = vec![
+        ("[".to_string(), None),
+        (time_str, Some(time_part)),
+        ("][".to_string(), None),
+        (volume_str, Some(Part::Volume)),
+        ("]".to_string(), None),
+    ];
+    if state.speed != 1.0 {
+        suffix.push((format!("[{}x]", format_speed(state.speed)), None));
+    }
+    (prefix, suffix)
+}
+
+// 把控制字符替换为空格（控制字符在终端里的实际宽度无法确定）
+fn replace_control_chars(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+/// 状态栏上的临时提示（音量、静音、暂停等状态变化），显示一段时间后恢复为歌名。
+/// 新的提示会替换旧的；是否过期由播放循环每次刷新时检查，不使用单独的计时线程
+pub struct Osd {
+    message: Option<StatusMessage>,
+    duration: Duration,
+}
+
+impl Osd {
+    pub fn new(duration: Duration) -> Self {
+        Osd { message: None, duration }
+    }
+
+    /// 显示一条提示（替换正在显示的提示）
+    pub fn show(&mut self, message: impl Into<String>) {
+        self.message = Some(StatusMessage::new(message, self.duration));
+    }
+
+    /// 显示一条提示，持续 duration（不使用默认的显示时长）
+    pub fn show_for(&mut self, message: impl Into<String>, duration: Duration) {
+        self.message = Some(StatusMessage::new(message, duration));
+    }
+
+    /// 当前应显示的提示，已过期时返回 None
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().filter(|message| message.is_active()).map(|message| message.text.as_str())
+    }
+
+    /// 清除已过期的提示，刚刚过期时返回 true（需要立即刷新状态栏）
+    pub fn expire(&mut self) -> bool {
+        if self.message.is_some() && self.message().is_none() {
+            self.message = None;
+            return true;
+        }
+        false
+    }
+}
+
+/// 显示到指定时刻为止的状态栏文字（Osd 的提示、定位失败的错误提示等）
+pub struct StatusMessage {
+    pub text: String,
+    pub until: Instant,
+}
+
+impl StatusMessage {
+    /// 从现在起显示 duration 时长
+    pub fn new(text: impl Into<String>, duration: Duration) -> Self {
+        StatusMessage { text: text.into(), until: Instant::now() + duration }
+    }
+
+    /// 是否还在显示期内
+    pub fn is_active(&self) -> bool {
+        Instant::now() < self.until
+    }
+}
+
+/// 窗口标题中的播放状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    Muted,
+}
+
+/// 窗口标题模板可用的数据
+pub struct TitleInfo<'a> {
+    pub title: &'a str,
+    pub artist: &'a str,
+    pub album: &'a str,
+    pub index: usize,
+    pub total: usize,
+    pub volume: f32,
+    pub state: PlaybackState,
+    /// 播放位置和总时长，用于 {bar}
+    pub position: Duration,
+    pub duration: Duration,
+}
+
+/// 窗口标题中 {bar} 进度条的格数
+const TITLE_BAR_WIDTH: usize = 10;
+
+/// 纯 ASCII 的进度条，例如 `[===>.     ]`：已播放的格为 `=`，最后一格为 `>`，播放过半格时下一格为 `.`。
+/// 窗口标题不能包含控制序列，只用 ASCII 字符；总时长未知时为空进度条
+pub fn format_progress_bar(current: Duration, total: Duration, width: usize) -> String {
+    let progress = if total.is_zero() { 0.0 } else { (current.as_secs_f64() / total.as_secs_f64()).clamp(0.0, 1.0) };
+    let cells = progress * width as f64;
+    let full = cells as usize;
+    let mut bar = String::with_capacity(width + 2);
+    bar.push('[');
+    if full >= width {
+        bar.push_str(&"=".repeat(width));
+    } else {
+        if full > 0 {
+            bar.push_str(&"=".repeat(full - 1));
+            bar.push('>');
+        }
+        let half = cells - full as f64 >= 0.5;
+        if half {
+            bar.push('.');
+        }
+        bar.push_str(&" ".repeat(width - full - half as usize));
+    }
+    bar.push(']');
+    bar
+}
+
+/// 替换窗口标题模板中的占位符：{title} {artist} {album} {index} {total} {volume} {state} {bar} {name} {version}
+pub fn render_title_template(tmpl: &str, info: &TitleInfo) -> String {
+    let state = match info.state {
+        PlaybackState::Playing => "播放中",
+        PlaybackState::Paused => "已暂停",
+        PlaybackState::Muted => "已静音",
+    };
+    tmpl.replace("{title}", info.title)
+        .replace("{artist}", info.artist)
+        .replace("{album}", info.album)
+        .replace("{index}", &(info.index + 1).to_string())
+        .replace("{total}", &info.total.to_string())
+        .replace("{volume}", &format!("{:.0}", info.volume * 100.0))
+        .replace("{state}", state)
+        .replace("{bar}", &format_progress_bar(info.position, info.duration, TITLE_BAR_WIDTH))
+        .replace("{name}", NAME)
+        .replace("{version}", VERSION)
+}
+
+/// 生成窗口标题：模板中没有 {state} 时，暂停和静音以 “[暂停]” “[静音]” 前缀表示
+pub fn window_title(tmpl: &str, info: &TitleInfo) -> String {
+    let title = render_title_template(tmpl, info);
+    if tmpl.contains("{state}") {
+        return title;
+    }
+    match info.state {
+        PlaybackState::Playing => title,
+        PlaybackState::Paused => format!("[暂停]{}", title),
+        PlaybackState::Muted => format!("[静音]{}", title),
+    }
+}
+
+/// 终端状态守卫：离开作用域时关闭括号粘贴模式、鼠标捕获和 raw mode 并显示光标。
+/// 正常退出由 graceful_exit 恢复终端，这里只在出错返回或 panic 时兜底
+pub struct TerminalGuard {
+    bracketed_paste: bool,
+    mouse_capture: bool,
+}
+
+impl TerminalGuard {
+    pub fn new(bracketed_paste: bool, mouse_capture: bool) -> Self {
+        TerminalGuard { bracketed_paste, mouse_capture }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.bracketed_paste {
+            let _ = execute!(io::stdout(), crossterm::event::DisableBracketedPaste);
+        }
+        if self.mouse_capture {
+            let _ = execute!(io::stdout(), crossterm::event::DisableMouseCapture);
+        }
+        let _ = crossterm::terminal::disable_raw_mode();
+        let _ = execute!(io::stdout(), cursor::Show);
+    }
+}
+
+/// 从第 top 行开始绘制可滚动列表的 visible 行，选中项前显示 “▶”，超出可见范围时向下滚动。
+/// item 返回第 n 项（0 起）的文字（超出列表时返回 None），超出终端宽度时截断。播放历史面板和启动时的播放列表选择共用
+pub fn draw_list_rows(
+    stdout: &mut io::Stdout,
+    top: u16,
+    visible: usize,
+    selected: usize,
+    item: impl Fn(usize) -> Option<String>,
+) -> io::Result<()> {
+    let terminal_width = crossterm::terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    let first = selected.saturating_sub(visible.saturating_sub(1));
+    for slot in 0..visible {
+        execute!(stdout, cursor::MoveTo(0, top + slot as u16), crossterm::terminal::Clear(crossterm::terminal::ClearType::CurrentLine))?;
+        let position = first + slot;
+        if let Some(text) = item(position) {
+            let marker = if position == selected { "▶" } else { " " };
+            print!("{}", truncate_string(&format!("{}{}", marker, text), terminal_width));
+        }
+    }
+    Ok(())
+}
+
+/// 截断或用空格补齐到指定显示宽度，用于覆盖状态栏上的旧内容
+pub fn pad_to_width(text: &str, width: usize) -> String {
+    let text = truncate_string(text, width);
+    let padding = " ".repeat(width.saturating_sub(text.as_str().width()));
+    format!("{}{}", text, padding)
+}
+
+/// 将状态栏文本输出到当前行
+pub fn flush_progress_line(stdout: &mut io::Stdout, line: &str) -> io::Result<()> {
+    execute!(stdout, cursor::MoveToColumn(0))?;
+    print!("{}", line);
+    stdout.flush()
+}
+
+/// 一条按键说明，帮助面板和纯净模式下的轮换提示都由同一张表生成
+pub struct KeyBinding {
+    pub key: &'static str,
+    pub action: &'static str,
+    /// 纯净模式下是否可用
+    pub simple_mode: bool,
+}
+
+/// 将当前模式下可用的按键说明依次排成若干行，每行不超过指定显示宽度
+pub fn layout_key_bindings(bindings: &[KeyBinding], simple_mode: bool, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for binding in bindings.iter().filter(|binding| binding.simple_mode || !simple_mode) {
+        let item = format!("[{}]{}", binding.key, binding.action);
+        if !line.is_empty() && line.as_str().width() + 2 + item.as_str().width() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push_str("  ");
+        }
+        line.push_str(&item);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// 说明区域的按键提示：列出纯净模式下也可用的基本按键（完整列表见帮助面板），最多 rows 行。
+/// 排不下时截掉后面的按键，最后一行以“[?]更多”结尾
+pub fn guide_key_lines(bindings: &[KeyBinding], width: usize, rows: usize) -> Vec<String> {
+    const MORE: &str = "[?]更多";
+    let mut lines = layout_key_bindings(bindings, true, width);
+    if lines.len() <= rows {
+        return lines;
+    }
+    lines.truncate(rows);
+    if let Some(last) = lines.last_mut() {
+        while !last.is_empty() && last.as_str().width() + 2 + MORE.width() > width {
+            match last.rfind("  ") {
+                Some(end) => last.truncate(end),
+                None => last.clear(),
+            }
+        }
+        if !last.is_empty() {
+            last.push_str("  ");
+        }
+        last.push_str(MORE);
+    }
+    lines
+}
+
+/// 任务栏进度状态（Windows Terminal / ConEmu 的 OSC 9;4 序列）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskbarProgress {
+    /// 清除进度
+    Clear,
+    /// 正常播放，参数为百分比
+    Normal(u8),
+    /// 暂停（黄色），参数为百分比
+    Paused(u8),
+    /// 时长未知，显示为不确定进度
+    Indeterminate,
+}
+
+impl TaskbarProgress {
+    /// 按播放位置生成进度：总时长未知时为不确定进度，超过总时长时按 100% 显示
+    pub fn at(position: Duration, total: Duration, paused: bool) -> TaskbarProgress {
+        if total.is_zero() {
+            return TaskbarProgress::Indeterminate;
+        }
+        let percent = (position.as_secs_f64() / total.as_secs_f64() * 100.0).min(100.0) as u8;
+        if paused { TaskbarProgress::Paused(percent) } else { TaskbarProgress::Normal(percent) }
+    }
+}
+
+/// 生成 `ESC ] 9 ; 4 ; state ; pct ST` 序列
+pub fn taskbar_progress_sequence(progress: TaskbarProgress) -> String {
+    let (state, percent) = match progress {
+        TaskbarProgress::Clear => (0, 0),
+        TaskbarProgress::Normal(percent) => (1, percent.min(100)),
+        TaskbarProgress::Indeterminate => (3, 0),
+        TaskbarProgress::Paused(percent) => (4, percent.min(100)),
+    };
+    format!("\x1b]9;4;{};{}\x1b\\", state, percent)
+}
+
+/// 输出任务栏进度序列
+pub fn emit_taskbar_progress(stdout: &mut io::Stdout, progress: TaskbarProgress) -> io::Result<()> {
+    write!(stdout, "{}", taskbar_progress_sequence(progress))?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINDINGS: &[KeyBinding] = &[
+        KeyBinding { key: "P", action: "静音/取消静音", simple_mode: true },
+        KeyBinding { key: "空格", action: "暂停/播放", simple_mode: true },
+        KeyBinding { key: "H", action: "播放历史", simple_mode: false },
+        KeyBinding { key: "R", action: "播放模式", simple_mode: true },
+        KeyBinding { key: "?", action: "帮助", simple_mode: true },
+        KeyBinding { key: "Q/Ctrl+C", action: "退出", simple_mode: true },
+    ];
+
+    // 单曲循环、随机播放并带收听进度的状态栏，标题和宽度由测试指定
+    fn display_state<'a>(title: &'a str, artist: &'a str, terminal_width: usize) -> DisplayState<'a> {
+        DisplayState {
+            current_index: 11,
+            total_tracks: 300,
+            is_random: true,
+            loop_mode: LoopMode::Track,
+            cycle: None,
+            repeat: Some((2, Some(3))),
+            title,
+            artist,
+            track_path: "music/a.mp3",
+            disc_number: Some(1),
+            track_number: Some(3),
+            current_time: Duration::from_millis(154321),
+            precise_time: false,
+            total_duration: Duration::from_secs(200),
+            volume: 0.5,
+            muted: false,
+            paused: false,
+            max_volume: None,
+            speed: 1.0,
+            terminal_width,
+            osd_message: None,
+            progress_message: None,
+            theme: None,
+            playlist_progress: Some(PlaylistProgress { percent: 37, remaining: Duration::from_secs(9660), estimated: false }),
+        }
+    }
+
+    #[test]
+    fn status_line_fills_the_terminal_with_wide_characters() {
+        let titles = [("晴天", "周杰伦"), ("▶️ Play ❤️ 👨‍👩‍👧", "Ärtist"), ("한국어노래제목입니다", "가수"), ("tab\there", "x\u{200b}y")];
+        for width in [8, 12, 20, 21, 30, 45, 80, 81] {
+            for (title, artist) in titles {
+                let line = render_progress_line(&display_state(title, artist, width));
+                assert_eq!(line.as_str().width(), width, "{:?} 宽度 {}", line, width);
+            }
+        }
+    }
+
+    #[test]
+    fn tier_follows_terminal_width() {
+        let tier_at = |width| {
+            let state = DisplayState { repeat: None, playlist_progress: None, ..display_state("晴天", "周杰伦", width) };
+            (select_status_tier(&state), render_progress_line(&state))
+        };
+        let (tier, line) = tier_at(20);
+        assert_eq!(tier, StatusTier::Minimal);
+        assert_eq!(line.trim_end(), "02:34 ▶");
+        let (tier, line) = tier_at(30);
+        assert_eq!(tier, StatusTier::NoTotal);
+        assert_eq!(line.trim_end(), "[12/300][晴天][02:34][50%]");
+        let (tier, line) = tier_at(45);
+        assert_eq!(tier, StatusTier::NoExtension);
+        assert_eq!(line.trim_end(), "[12/300][随|复][晴天][02:34/03:20][50%]");
+        let (tier, line) = tier_at(80);
+        assert_eq!(tier, StatusTier::Full);
+        assert_eq!(line.trim_end(), "[12/300][随|复][MP3][D1T03][晴天-周杰伦][02:34/03:20][50%]");
+    }
+
+    #[test]
+    fn repeat_and_playlist_progress_lower_the_tier() {
+        let state = display_state("晴天", "周杰伦", 45);
+        assert_eq!(select_status_tier(&state), StatusTier::NoMode);
+        assert_eq!(render_progress_line(&state).trim_end(), "[12/300][2/3遍][晴天][02:34/03:20][50%]");
+        let state = display_state("晴天", "周杰伦", 80);
+        assert_eq!(select_status_tier(&state), StatusTier::Full);
+        assert!(render_progress_line(&state).starts_with("[12/300][2/3遍][随|复][总37% 剩2h41m][MP3][D1T03][晴天]"));
+    }
+
+    #[test]
+    fn mute_and_pause_show_on_the_status_line() {
+        let line = |muted, paused, max_volume| {
+            let state = DisplayState { repeat: None, playlist_progress: None, volume: 0.68, muted, paused, max_volume, ..display_state("晴天", "周杰伦", 80) };
+            render_progress_line(&state).trim_end().to_string()
+        };
+        let prefix = "[12/300][随|复][MP3][D1T03][晴天-周杰伦]";
+        assert_eq!(line(false, false, None), format!("{}[02:34/03:20][68%]", prefix));
+        assert_eq!(line(true, false, None), format!("{}[02:34/03:20][静音 68%]", prefix));
+        assert_eq!(line(false, true, None), format!("{}[⏸ 02:34/03:20][68%]", prefix));
+        assert_eq!(line(true, true, None), format!("{}[⏸ 02:34/03:20][静音 68%]", prefix));
+        assert_eq!(line(true, true, Some(0.8)), format!("{}[⏸ 02:34/03:20][静音 68/80%]", prefix));
+    }
+
+    #[test]
+    fn padding_counts_wide_characters_as_two_columns() {
+        for width in 0..12 {
+            for text in ["晴天", "周杰伦-晴天", "🎵 歌", "abc"] {
+                assert_eq!(pad_to_width(text, width).as_str().width(), width, "{:?} 宽度 {}", text, width);
+            }
+        }
+        assert_eq!(pad_to_width("晴天", 6), "晴天  ");
+    }
+
+    #[test]
+    fn guide_lists_only_basic_keys_when_they_fit() {
+        let lines = guide_key_lines(BINDINGS, 80, 2);
+        assert_eq!(lines, vec!["[P]静音/取消静音  [空格]暂停/播放  [R]播放模式  [?]帮助  [Q/Ctrl+C]退出"]);
+    }
+
+    #[test]
+    fn guide_ends_with_more_when_truncated() {
+        let lines = guide_key_lines(BINDINGS, 34, 1);
+        assert_eq!(lines, vec!["[P]静音/取消静音  [?]更多"]);
+        assert!(lines.iter().all(|line| line.as_str().width() <= 34));
+    }
+
+    #[test]
+    fn guide_without_rows_is_empty() {
+        assert!(guide_key_lines(BINDINGS, 80, 0).is_empty());
+    }
+
+    #[test]
+    fn speed_has_at_most_two_decimals() {
+        // 按 0.05 累加得到的倍速带有浮点误差
+        let mut speed = 1.0f32;
+        for _ in 0..7 {
+            speed += 0.05;
+        }
+        assert_eq!(format_speed(speed), "1.35");
+        assert_eq!(format_speed(1.25), "1.25");
+        assert_eq!(format_speed(1.5), "1.5");
+        assert_eq!(format_speed(2.0), "2");
+        assert_eq!(format_speed(0.5), "0.5");
+    }
+
+    #[test]
+    fn taskbar_progress_sequences() {
+        let secs = Duration::from_secs;
+        let cases = [
+            (TaskbarProgress::Clear, "\x1b]9;4;0;0\x1b\\"),
+            (TaskbarProgress::at(secs(0), secs(200), false), "\x1b]9;4;1;0\x1b\\"),
+            (TaskbarProgress::at(secs(100), secs(200), false), "\x1b]9;4;1;50\x1b\\"),
+            (TaskbarProgress::at(secs(200), secs(200), false), "\x1b]9;4;1;100\x1b\\"),
+            (TaskbarProgress::at(secs(100), secs(200), true), "\x1b]9;4;4;50\x1b\\"),
+            (TaskbarProgress::at(secs(100), Duration::ZERO, false), "\x1b]9;4;3;0\x1b\\"),
+            // 位置超过总时长（时长标签偏短）时按 100% 显示
+            (TaskbarProgress::at(secs(700), secs(200), false), "\x1b]9;4;1;100\x1b\\"),
+            (TaskbarProgress::Normal(250), "\x1b]9;4;1;100\x1b\\"),
+            (TaskbarProgress::Paused(101), "\x1b]9;4;4;100\x1b\\"),
+        ];
+        for (progress, expected) in cases {
+            assert_eq!(taskbar_progress_sequence(progress), expected, "{:?}", progress);
+        }
+    }
+}
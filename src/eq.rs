@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::cli::EqPreset;
+
+/// 二阶 IIR（biquad）滤波器系数 (b0, b1, b2, a1, a2)，已按 a0 归一化
+pub type Coefficients = (f64, f64, f64, f64, f64);
+
+/// 预设系数的设计采样率，其他采样率的音源会重新换算
+const PRESET_SAMPLE_RATE: u32 = 44100;
+
+// 各预设在 44100 Hz 下的系数（按 RBJ Audio EQ Cookbook 公式计算）
+const FLAT: Coefficients = (1.0, 0.0, 0.0, 0.0, 0.0);
+// 低频搁架 100 Hz +6 dB：增强低音，适合耳机
+const BASS: Coefficients = (1.0035029502, -1.9829760254, 0.9797574007, -1.983046938, 0.9831894383);
+// 高频搁架 6000 Hz -6 dB：削减高音，适合刺耳的小音箱
+const TREBLE: Coefficients = (0.6109257611, -0.4195548164, 0.1565266932, -1.0102466184, 0.3581442564);
+// 峰值 2000 Hz +4 dB（Q=1）：突出人声
+const VOCAL: Coefficients = (1.0587431984, -1.7265822944, 0.74038868, -1.7265822944, 0.7991318784);
+// 高频搁架 8000 Hz +3 dB：提升空气感，适合古典乐
+const CLASSICAL: Coefficients = (1.2418250003, -0.7467116272, 0.2933537331, -0.4134937775, 0.2019608838);
+
+/// 预设在 44100 Hz 下的系数
+pub fn preset_coefficients(preset: EqPreset) -> Coefficients {
+    match preset {
+        EqPreset::Flat => FLAT,
+        EqPreset::Bass => BASS,
+        EqPreset::Treble => TREBLE,
+        EqPreset::Vocal => VOCAL,
+        EqPreset::Classical => CLASSICAL,
+    }
+}
+
+/// 把按 from_rate 设计的系数换算到 to_rate：先还原为模拟原型（双线性变换的逆变换），再按新的采样率离散化，
+/// 即把 z⁻¹ 替换为 (w + r) / (1 + r·w)，其中 r = (from - to) / (from + to)
+pub fn rescale(coefficients: Coefficients, from_rate: u32, to_rate: u32) -> Coefficients {
+    if from_rate == to_rate || to_rate == 0 {
+        return coefficients;
+    }
+    let (b0, b1, b2, a1, a2) = coefficients;
+    let r = (from_rate as f64 - to_rate as f64) / (from_rate as f64 + to_rate as f64);
+    let substitute = |c0: f64, c1: f64, c2: f64| {
+        (
+            c0 + c1 * r + c2 * r * r,
+            2.0 * r * c0 + (1.0 + r * r) * c1 + 2.0 * r * c2,
+            r * r * c0 + r * c1 + c2,
+        )
+    };
+    let (nb0, nb1, nb2) = substitute(b0, b1, b2);
+    let (na0, na1, na2) = substitute(1.0, a1, a2);
+    (nb0 / na0, nb1 / na0, nb2 / na0, na1 / na0, na2 / na0)
+}
+
+/// 对音源做二阶 IIR 滤波的 Source 适配器，每个声道单独保存滤波状态
+pub struct BiquadFilter<S: Source<Item = i16>> {
+    source: S,
+    coefficients: Coefficients,
+    // 每个声道的 (x[n-1], x[n-2], y[n-1], y[n-2])
+    history: Vec<(f64, f64, f64, f64)>,
+    // 下一个样本所属的声道（样本按声道交错排列）
+    channel: usize,
+}
+
+impl<S: Source<Item = i16>> BiquadFilter<S> {
+    /// coefficients 为 44100 Hz 下的系数，按音源的采样率换算后使用
+    pub fn new(source: S, coefficients: Coefficients) -> Self {
+        let coefficients = rescale(coefficients, PRESET_SAMPLE_RATE, source.sample_rate());
+        let channels = source.channels().max(1) as usize;
+        BiquadFilter { source, coefficients, history: vec![(0.0, 0.0, 0.0, 0.0); channels], channel: 0 }
+    }
+}
+
+impl<S: Source<Item = i16>> Iterator for BiquadFilter<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.source.next()? as f64;
+        let (b0, b1, b2, a1, a2) = self.coefficients;
+        let (x1, x2, y1, y2) = self.history[self.channel];
+        let output = b0 * sample + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        self.history[self.channel] = (sample, x1, output, y1);
+        self.channel = (self.channel + 1) % self.history.len();
+        // 增益提升可能超出范围，直接削顶
+        Some(output.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source<Item = i16>> Source for BiquadFilter<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
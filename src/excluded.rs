@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::utils::{path_from_bytes, path_to_bytes, stream_url};
+
+/// 排除列表的文件名，放在播放列表文件或目录旁边
+const EXCLUDED_FILE_NAME: &str = "excluded.txt";
+
+/// 输入对应的排除列表位置：播放列表文件所在目录或输入目录下的 excluded.txt；标准输入、通配符和网络流没有对应的位置
+pub fn excluded_file_for(path: &Path) -> Option<PathBuf> {
+    if path == Path::new("-") || path.to_str().is_some_and(|input| input.contains('*')) || stream_url(path).is_some() {
+        return None;
+    }
+    if path.is_dir() {
+        return Some(path.join(EXCLUDED_FILE_NAME));
+    }
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    Some(dir.join(EXCLUDED_FILE_NAME))
+}
+
+/// 读取排除列表（每行一个路径，相对路径相对于排除列表所在目录），返回规范化后的路径；文件不存在时为空
+pub fn load_excluded(file: &Path) -> HashSet<PathBuf> {
+    let base_dir = file.parent().unwrap_or(Path::new("."));
+    let Ok(content) = fs::read(file) else {
+        return HashSet::new();
+    };
+    content
+        .split(|&byte| byte == b'\n')
+        .map(<[u8]>::trim_ascii)
+        .filter(|line| !line.is_empty() && !line.starts_with(b"#"))
+        .map(|line| canonical(&base_dir.join(path_from_bytes(line))))
+        .collect()
+}
+
+/// 把移除的歌曲追加到排除列表（路径按 path_to_bytes 原样写入）
+pub fn append_excluded(file: &Path, track: &Path) -> io::Result<()> {
+    let mut out = OpenOptions::new().create(true).append(true).open(file)?;
+    out.write_all(&path_to_bytes(&canonical(track)))?;
+    out.write_all(b"\n")
+}
+
+/// 撤销移除时从排除列表中删掉这首歌（删除最后一条匹配的记录）
+pub fn remove_excluded(file: &Path, track: &Path) -> io::Result<()> {
+    let content = fs::read(file)?;
+    let base_dir = file.parent().unwrap_or(Path::new("."));
+    let track = canonical(track);
+    let mut lines: Vec<&[u8]> = content.split(|&byte| byte == b'\n').filter(|line| !line.is_empty()).collect();
+    if let Some(position) = lines.iter().rposition(|line| canonical(&base_dir.join(path_from_bytes(line.trim_ascii()))) == track) {
+        lines.remove(position);
+    }
+    let mut updated = lines.join(b"\n".as_slice());
+    if !updated.is_empty() {
+        updated.push(b'\n');
+    }
+    fs::write(file, updated)
+}
+
+/// 规范化路径，用于比较排除列表中的记录和播放列表中的歌曲
+pub fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
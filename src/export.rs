@@ -0,0 +1,131 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cli::EncodingFix;
+use crate::metadata::{get_all_metadata, probe_properties, TrackMetadata};
+use crate::utils::{json_escape, parallel_map};
+
+/// 导出的列，CSV 表头和 JSON 字段名相同
+const COLUMNS: &[&str] = &[
+    "path", "title", "artist", "album", "year", "genre", "track", "disc", "duration_secs", "bitrate", "sample_rate", "channels",
+];
+/// list --csv 报告的列
+const REPORT_COLUMNS: &[&str] = &["path", "title", "artist", "album", "duration_secs", "file_size", "codec", "bitrate"];
+
+// 一行中的各列，未知的值为 None（CSV 中为空，JSON 中为 null）
+type Row = Vec<Option<String>>;
+
+/// 导出文件的格式，按扩展名判断：.json 为 JSON，其他为 CSV
+fn is_json_path(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// 多线程读取播放列表中所有歌曲的元数据（进度条输出到标准错误），写入 CSV 或 JSON 文件，返回导出的歌曲数
+pub fn export_metadata(playlist: &[PathBuf], output: &Path, fix: EncodingFix) -> io::Result<usize> {
+    let rows = parallel_map(playlist, "读取元数据", |path| metadata_row(path, &get_all_metadata(path, fix)));
+    let content = if is_json_path(output) { to_json(COLUMNS, &rows) } else { to_csv(COLUMNS, &rows) };
+    fs::write(output, content)?;
+    Ok(rows.len())
+}
+
+/// list --csv：多线程读取每首歌的标签、文件大小和编码（进度条输出到标准错误），写入 CSV 报告。
+/// 读取失败的歌曲照样写一行（未知的列为空），返回失败的文件和原因
+pub fn write_list_report(playlist: &[PathBuf], output: &Path, fix: EncodingFix) -> io::Result<Vec<(PathBuf, String)>> {
+    let results = parallel_map(playlist, "读取元数据", |path| report_row(path, fix));
+    let mut rows = Vec::with_capacity(results.len());
+    let mut failures = Vec::new();
+    for (path, (row, error)) in playlist.iter().zip(results) {
+        rows.push(row);
+        if let Some(error) = error {
+            failures.push((path.clone(), error));
+        }
+    }
+    fs::write(output, to_csv(REPORT_COLUMNS, &rows))?;
+    Ok(failures)
+}
+
+// 报告中的一行，以及读取失败的原因（文件无法读取或解码器无法识别）
+fn report_row(path: &Path, fix: EncodingFix) -> (Row, Option<String>) {
+    let size = fs::metadata(path).map(|meta| meta.len());
+    let probe = probe_properties(path);
+    let error = match (&size, &probe) {
+        (Err(e), _) => Some(format!("无法读取文件: {}", e)),
+        (_, Err(e)) => Some(format!("无法识别音频: {}", e)),
+        _ => None,
+    };
+    // 文件不存在时不再读取标签
+    let data = size.is_ok().then(|| get_all_metadata(path, fix));
+    let row = vec![
+        Some(path.to_string_lossy().into_owned()),
+        data.as_ref().and_then(|d| d.title.clone()),
+        data.as_ref().and_then(|d| d.artist.clone()),
+        data.as_ref().and_then(|d| d.album.clone()),
+        data.as_ref().filter(|d| !d.duration.is_zero()).map(|d| format!("{:.3}", d.duration.as_secs_f64())),
+        size.ok().map(|size| size.to_string()),
+        probe.ok().map(|properties| properties.codec),
+        data.as_ref().and_then(|d| d.bitrate).map(|bitrate| bitrate.to_string()),
+    ];
+    (row, error)
+}
+
+// --export-metadata 中一首歌的各列
+fn metadata_row(path: &Path, data: &TrackMetadata) -> Row {
+    let number = |value: Option<u32>| value.map(|v| v.to_string());
+    vec![
+        Some(path.to_string_lossy().into_owned()),
+        data.title.clone(),
+        data.artist.clone(),
+        data.album.clone(),
+        number(data.year),
+        data.genre.clone(),
+        number(data.track),
+        number(data.disc),
+        (!data.duration.is_zero()).then(|| format!("{:.3}", data.duration.as_secs_f64())),
+        number(data.bitrate),
+        number(data.sample_rate),
+        data.channels.map(|c| c.to_string()),
+    ]
+}
+
+// 字段中有逗号、引号或换行时加引号，引号写成两个
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_csv(columns: &[&str], rows: &[Row]) -> String {
+    let mut content = columns.join(",") + "\n";
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|value| value.as_deref().map_or_else(String::new, csv_field)).collect();
+        content.push_str(&fields.join(","));
+        content.push('\n');
+    }
+    content
+}
+
+// 路径和文本字段为字符串，其余为数字
+fn to_json(columns: &[&str], rows: &[Row]) -> String {
+    let objects: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let fields: Vec<String> = columns
+                .iter()
+                .zip(row)
+                .map(|(key, value)| {
+                    let value = match (*key, value) {
+                        (_, None) => "null".to_string(),
+                        ("path" | "title" | "artist" | "album" | "genre", Some(text)) => format!("\"{}\"", json_escape(text)),
+                        (_, Some(number)) => number.clone(),
+                    };
+                    format!("\"{}\":{}", key, value)
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    format!("[{}]\n", objects.join(","))
+}
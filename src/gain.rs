@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::state::data_file_path;
+use crate::utils::{path_from_bytes, path_to_bytes};
+
+/// 记忆音量相对基准音量的最大偏移（±30%）
+pub const MAX_MEMORY_OFFSET: f32 = 0.3;
+
+/// 当前歌曲在基准音量之上叠加的各项增益，各项相乘
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackGain {
+    /// --randomize-volume 的随机浮动系数
+    pub variation: f32,
+    /// 这首歌记住的音量系数
+    pub memory: f32,
+    /// --loudness-normalize 的响度均衡系数
+    pub loudness: f32,
+}
+
+impl Default for TrackGain {
+    fn default() -> Self {
+        TrackGain { variation: 1.0, memory: 1.0, loudness: 1.0 }
+    }
+}
+
+impl TrackGain {
+    /// 各项增益的乘积
+    pub fn factor(&self) -> f32 {
+        self.variation * self.memory * self.loudness
+    }
+}
+
+/// 响度均衡时测量开头的秒数：太短（例如几十毫秒）时安静的前奏会被当成整首歌的响度
+pub const LOUDNESS_SECONDS: usize = 10;
+/// 响度均衡的目标 RMS（-18 dBFS）
+const LOUDNESS_TARGET_DBFS: f32 = -18.0;
+/// 低于该 RMS（-60 dBFS）视为静音，无法测量
+const SILENCE_DBFS: f32 = -60.0;
+/// 响度均衡系数的范围，避免测量不准时音量变化过大（最多放大 2 倍，约 +6 dB）
+const MIN_LOUDNESS_GAIN: f32 = 0.25;
+const MAX_LOUDNESS_GAIN: f32 = 2.0;
+
+/// 根据开头一段样本的 RMS 计算把响度调到 -18 dBFS 的音量系数；样本为空或接近静音时返回 None
+pub fn loudness_gain(samples: &[i16]) -> Option<f32> {
+    if samples.is_empty() {
+        return None;
+    }
+    let sum: f64 = samples.iter().map(|&sample| (sample as f64 / 32768.0).powi(2)).sum();
+    let rms_dbfs = 20.0 * (sum / samples.len() as f64).sqrt().log10() as f32;
+    if !rms_dbfs.is_finite() || rms_dbfs < SILENCE_DBFS {
+        return None;
+    }
+    Some(10f32.powf((LOUDNESS_TARGET_DBFS - rms_dbfs) / 20.0).clamp(MIN_LOUDNESS_GAIN, MAX_LOUDNESS_GAIN))
+}
+
+/// 计算实际输出音量：基准音量乘以歌曲增益，静音时为 0，结果不超过音量上限
+pub fn compose_volume(base_volume: f32, gain: &TrackGain, muted: bool, max_volume: f32) -> f32 {
+    if muted {
+        return 0.0;
+    }
+    (base_volume * gain.factor()).clamp(0.0, max_volume)
+}
+
+/// 用户把实际输出音量从 old 调到 new 后想要的音量：按同样的比例缩放基准音量乘以各项增益的结果（未经音量上限截断），
+/// 这样歌曲被放大到音量上限时，调整音量也不会让基准音量跟着变小。old 为 0 时无法按比例计算，取 new
+pub fn target_volume(base_volume: f32, gain: &TrackGain, old: f32, new: f32) -> f32 {
+    if old > 0.0 {
+        base_volume * gain.factor() * new / old
+    } else {
+        new
+    }
+}
+
+/// 用户把音量调到 volume（见 target_volume）后这首歌的记忆音量系数：只除去基准音量、随机浮动和响度均衡，
+/// 不把这两项增益计入记忆音量
+pub fn memory_factor(base_volume: f32, gain: &TrackGain, volume: f32) -> f32 {
+    volume / (base_volume * gain.variation * gain.loudness)
+}
+
+/// 每首歌记住的音量系数（相对基准音量），按文件路径保存在数据目录下的 mddplayer/volume_memory.tsv
+pub struct VolumeMemory {
+    factors: HashMap<PathBuf, f32>,
+    // 有未保存的修改；调整音量时只修改内存中的记录，切歌和退出时才写入文件
+    changed: bool,
+}
+
+impl VolumeMemory {
+    /// 读取保存的记忆音量，文件不存在或格式错误的行会被忽略
+    pub fn load() -> VolumeMemory {
+        let factors = data_file_path("volume_memory.tsv")
+            .and_then(|path| fs::read(path).ok())
+            .map(|content| {
+                content
+                    .split(|&byte| byte == b'\n')
+                    .filter_map(|line| {
+                        let tab = line.iter().position(|&byte| byte == b'\t')?;
+                        let factor = std::str::from_utf8(&line[..tab]).ok()?.parse().ok()?;
+                        Some((path_from_bytes(&line[tab + 1..]), factor))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        VolumeMemory { factors, changed: false }
+    }
+
+    /// 这首歌记住的音量系数，没有记录时为 1
+    pub fn get(&self, path: &Path) -> f32 {
+        self.factors.get(&memory_key(path)).copied().unwrap_or(1.0)
+    }
+
+    /// 记住这首歌的音量系数（限制在 ±30% 内，接近 1 时视为没有调整），返回实际记住的系数
+    pub fn remember(&mut self, path: &Path, factor: f32) -> f32 {
+        let factor = factor.clamp(1.0 - MAX_MEMORY_OFFSET, 1.0 + MAX_MEMORY_OFFSET);
+        self.changed = true;
+        if (factor - 1.0).abs() < 0.005 {
+            self.factors.remove(&memory_key(path));
+            return 1.0;
+        }
+        self.factors.insert(memory_key(path), factor);
+        factor
+    }
+
+    /// 忘记这首歌的音量
+    pub fn forget(&mut self, path: &Path) {
+        self.changed = true;
+        self.factors.remove(&memory_key(path));
+    }
+
+    /// 有修改时保存到数据目录（目录不存在时创建），每行为 “系数<Tab>路径”，路径按 path_to_bytes 原样写入
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let path = data_file_path("volume_memory.tsv").ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "无法确定数据目录"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut content = Vec::new();
+        for (track, factor) in &self.factors {
+            content.extend_from_slice(format!("{:.3}\t", factor).as_bytes());
+            content.extend_from_slice(&path_to_bytes(track));
+            content.push(b'\n');
+        }
+        fs::write(path, content)?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+// 退出时（包括出错返回）保存还没写入的修改
+impl Drop for VolumeMemory {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            warn!(error = %e, "无法保存记忆音量");
+        }
+    }
+}
+
+// 记忆音量按规范化后的路径保存，从不同目录启动时也能对应到同一首歌
+fn memory_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAIN: TrackGain = TrackGain { variation: 1.1, memory: 1.2, loudness: 2.0 };
+
+    #[test]
+    fn volume_is_base_times_every_gain() {
+        assert!((compose_volume(0.25, &GAIN, false, 1.0) - 0.25 * 1.1 * 1.2 * 2.0).abs() < 1e-6);
+        assert_eq!(compose_volume(0.5, &TrackGain::default(), false, 1.0), 0.5);
+    }
+
+    #[test]
+    fn muted_volume_is_zero() {
+        assert_eq!(compose_volume(0.5, &GAIN, true, 1.0), 0.0);
+    }
+
+    #[test]
+    fn volume_is_clamped_to_the_cap() {
+        assert_eq!(compose_volume(0.5, &GAIN, false, 0.8), 0.8);
+        assert_eq!(compose_volume(-0.1, &GAIN, false, 0.8), 0.0);
+    }
+
+    #[test]
+    fn adjusting_a_capped_track_keeps_the_base_volume() {
+        // 响度均衡把这首歌放大到 2 倍，实际音量被上限截断为 1.0
+        let base_volume = 0.6;
+        let gain = TrackGain { loudness: 2.0, ..TrackGain::default() };
+        assert_eq!(compose_volume(base_volume, &gain, false, 1.0), 1.0);
+        // 把实际音量从 1.0 调到 0.95，基准音量按同样的比例降低，而不是变为 0.95 / 2
+        let target = target_volume(base_volume, &gain, 1.0, 0.95);
+        assert!((target / gain.factor() - 0.57).abs() < 1e-6);
+        // 从 0 调高时按调到的音量计算
+        assert_eq!(target_volume(0.0, &gain, 0.0, 0.05), 0.05);
+    }
+
+    #[test]
+    fn loudness_gain_is_limited() {
+        // -40 dBFS 的安静音频最多放大 2 倍
+        let quiet = [328i16, -328].repeat(1000);
+        assert_eq!(loudness_gain(&quiet), Some(MAX_LOUDNESS_GAIN));
+        // 半幅方波（约 -6 dBFS）调到 -18 dBFS，满幅方波最多缩小到 1/4
+        let loud = [16384i16, -16384].repeat(1000);
+        assert!((loudness_gain(&loud).unwrap() - 10f32.powf((-18.0 + 6.0206) / 20.0)).abs() < 1e-3);
+        let full = [i16::MAX, -i16::MAX].repeat(1000);
+        assert_eq!(loudness_gain(&full), Some(MIN_LOUDNESS_GAIN));
+        assert_eq!(loudness_gain(&[0; 2000]), None);
+        assert_eq!(loudness_gain(&[]), None);
+    }
+
+    #[test]
+    fn memory_factor_leaves_out_variation_and_loudness() {
+        let base_volume = 0.25;
+        let gain = TrackGain { memory: 1.0, ..GAIN };
+        // 用户把这首歌的实际音量调高 10%，记住的只有这 10%
+        let volume = compose_volume(base_volume, &gain, false, 1.0) * 1.1;
+        assert!((memory_factor(base_volume, &gain, volume) - 1.1).abs() < 1e-6);
+        // 以记住的系数重新计算音量，与调整后的音量一致
+        let remembered = TrackGain { memory: memory_factor(base_volume, &gain, volume), ..gain };
+        assert!((compose_volume(base_volume, &remembered, false, 1.0) - volume).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// 一条播放历史记录
+pub struct HistoryEntry {
+    /// 歌曲在播放列表中的索引
+    pub index: usize,
+    pub title: String,
+    pub artist: String,
+    /// 记录写入的时间（歌曲播完或被切走的时刻）
+    pub played_at: Instant,
+}
+
+/// 播放历史，最多保留 max_size 条，超出后丢弃最旧的记录。
+pub struct PlayHistory {
+    pub entries: VecDeque<HistoryEntry>,
+    pub max_size: usize,
+}
+
+impl PlayHistory {
+    pub fn new(max_size: usize) -> Self {
+        PlayHistory {
+            entries: VecDeque::with_capacity(max_size),
+            max_size,
+        }
+    }
+
+    /// 追加一条记录，超过容量时移除最旧的一条
+    pub fn push(&mut self, index: usize, title: &str, artist: &str) {
+        if self.max_size == 0 {
+            return;
+        }
+        while self.entries.len() >= self.max_size {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry {
+            index,
+            title: title.to_string(),
+            artist: artist.to_string(),
+            played_at: Instant::now(),
+        });
+    }
+
+    /// 播放列表中移除了一首歌：删除这首歌的记录，后面歌曲的索引前移
+    pub fn on_removed(&mut self, index: usize) {
+        self.entries.retain(|entry| entry.index != index);
+        for entry in self.entries.iter_mut().filter(|entry| entry.index > index) {
+            entry.index -= 1;
+        }
+    }
+
+    /// 播放列表中插入了一首歌：该位置及之后歌曲的索引后移
+    pub fn on_inserted(&mut self, index: usize) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.index >= index) {
+            entry.index += 1;
+        }
+    }
+
+    /// 按“最新在前”的顺序获取第 n 条记录
+    pub fn newest(&self, n: usize) -> Option<&HistoryEntry> {
+        self.entries.iter().rev().nth(n)
+    }
+}
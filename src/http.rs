@@ -0,0 +1,285 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::mpsc::Sender;
+
+use crate::ipc::RemoteCommand;
+
+/// --http 启动的 HTTP 控制服务。drop 时停止监听并等待服务线程退出
+pub struct HttpServer {
+    #[cfg(feature = "http")]
+    server: std::sync::Arc<tiny_http::Server>,
+    #[cfg(feature = "http")]
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+/// 在 addr 上启动 HTTP 控制服务（单个后台线程依次处理请求）：
+/// GET /status、/playlist 返回 JSON，POST /pause、/play、/toggle、/next、/prev、/volume?level=70、/goto?index=12 转发给主线程。
+/// 设置了 token 时请求必须带 `Authorization: Bearer <token>`；监听非本机地址时必须设置 token。
+/// Host 不是监听地址或带有其他网站 Origin 的请求被拒绝，网页无法借浏览器控制播放器（跨站请求、DNS 重绑定）
+#[cfg(feature = "http")]
+pub fn start_http_server(addr: SocketAddr, token: Option<String>, tx: Sender<RemoteCommand>) -> io::Result<HttpServer> {
+    use std::sync::Arc;
+
+    if token.is_none() && !addr.ip().is_loopback() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "监听非本机地址时需要用 --http-token 设置访问令牌"));
+    }
+    let server = Arc::new(tiny_http::Server::http(addr).map_err(io::Error::other)?);
+    let thread = {
+        let server = Arc::clone(&server);
+        std::thread::spawn(move || {
+            // unblock 之后 incoming_requests 结束
+            for request in server.incoming_requests() {
+                let (status, body) = handle_request(&request, addr, token.as_deref(), &tx);
+                let response = tiny_http::Response::from_string(body)
+                    .with_status_code(status)
+                    .with_header(tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json; charset=utf-8"[..]).unwrap());
+                let _ = request.respond(response);
+            }
+        })
+    };
+    Ok(HttpServer { server, thread: Some(thread) })
+}
+
+/// 未启用 http 功能时不能启动 HTTP 控制服务
+#[cfg(not(feature = "http"))]
+pub fn start_http_server(_addr: SocketAddr, _token: Option<String>, _tx: Sender<RemoteCommand>) -> io::Result<HttpServer> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "编译时未启用 http 功能（cargo build --features http）"))
+}
+
+#[cfg(feature = "http")]
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// 请求的 Host 是否为监听的地址（本机地址也可以写成 localhost，端口为 80 时可以省略）。
+// 监听 0.0.0.0 等任意地址时无法确定客户端使用的主机名，不检查（此时必须设置令牌）
+#[cfg(feature = "http")]
+fn allowed_host(host: &str, addr: SocketAddr) -> bool {
+    if addr.ip().is_unspecified() {
+        return true;
+    }
+    let ip = match addr {
+        SocketAddr::V4(v4) => v4.ip().to_string(),
+        SocketAddr::V6(v6) => format!("[{}]", v6.ip()),
+    };
+    let names = if addr.ip().is_loopback() { vec![ip, "localhost".to_string()] } else { vec![ip] };
+    names.iter().any(|name| {
+        let (host_name, port) = host.rsplit_once(':').filter(|(_, port)| !port.contains(']')).unwrap_or((host, "80"));
+        host_name.eq_ignore_ascii_case(name) && port.parse::<u16>() == Ok(addr.port())
+    })
+}
+
+// 按固定的时间比较令牌，响应时间不会透露令牌前几个字符是否正确
+#[cfg(feature = "http")]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// 处理一个请求，返回状态码和 JSON 响应
+#[cfg(feature = "http")]
+fn handle_request(request: &tiny_http::Request, addr: SocketAddr, token: Option<&str>, tx: &Sender<RemoteCommand>) -> (u16, String) {
+    use tiny_http::Method;
+
+    use crate::ipc::request_reply;
+    use crate::utils::json_escape;
+
+    let error = |status: u16, message: &str| (status, format!("{{\"error\":\"{}\"}}", json_escape(message)));
+    let header = |name: &'static str| request.headers().iter().find(|header| header.field.equiv(name)).map(|header| header.value.as_str());
+
+    let Some(host) = header("Host").filter(|host| allowed_host(host, addr)) else {
+        return error(403, "Host 不是监听的地址");
+    };
+    // 浏览器发出的跨站请求带有 Origin，只接受同源的请求
+    if header("Origin").is_some_and(|origin| !origin.eq_ignore_ascii_case(&format!("http://{}", host))) {
+        return error(403, "不接受来自其他网站的请求");
+    }
+    if let Some(token) = token {
+        let authorized = header("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|value| constant_time_eq(value.as_bytes(), token.as_bytes()));
+        if !authorized {
+            return error(401, "缺少或错误的访问令牌");
+        }
+    }
+
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let param = |name: &str| query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='));
+    let command = match (request.method(), path) {
+        (Method::Get, "/status" | "/playlist") => {
+            let command = if path == "/status" { RemoteCommand::Status } else { RemoteCommand::Playlist };
+            return match request_reply(tx, command) {
+                Ok(json) => (200, json),
+                Err(e) => error(503, e),
+            };
+        }
+        (Method::Post, "/pause") => RemoteCommand::Pause,
+        (Method::Post, "/play") => RemoteCommand::Play,
+        (Method::Post, "/toggle") => RemoteCommand::Toggle,
+        (Method::Post, "/next") => RemoteCommand::Next,
+        (Method::Post, "/prev") => RemoteCommand::Prev,
+        (Method::Post, "/volume") => match param("level").and_then(|value| value.parse::<u8>().ok()) {
+            Some(level) if level <= 100 => RemoteCommand::Volume(level),
+            _ => return error(400, "level 应为 0-100"),
+        },
+        (Method::Post, "/goto") => match param("index").and_then(|value| value.parse::<usize>().ok()) {
+            Some(index) if index >= 1 => RemoteCommand::Goto(index - 1),
+            _ => return error(400, "index 应为从 1 开始的整数"),
+        },
+        (_, "/status" | "/playlist" | "/pause" | "/play" | "/toggle" | "/next" | "/prev" | "/volume" | "/goto") => {
+            return error(405, "请求方法不支持");
+        }
+        _ => return error(404, "未知路径"),
+    };
+    match tx.send(command) {
+        Ok(()) => (200, "{\"ok\":true}".to_string()),
+        Err(_) => error(503, "播放器已退出"),
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread::JoinHandle;
+
+    // 代替播放器主线程：记录收到的命令，回复 status/playlist，通道关闭（服务停止）后返回记录
+    fn dummy_player(rx: Receiver<RemoteCommand>) -> JoinHandle<Vec<String>> {
+        std::thread::spawn(move || {
+            let mut received = Vec::new();
+            for command in rx {
+                received.push(match command {
+                    RemoteCommand::Next => "next".to_string(),
+                    RemoteCommand::Prev => "prev".to_string(),
+                    RemoteCommand::Pause => "pause".to_string(),
+                    RemoteCommand::Play => "play".to_string(),
+                    RemoteCommand::Toggle => "toggle".to_string(),
+                    RemoteCommand::Volume(level) => format!("volume {}", level),
+                    RemoteCommand::Quit => "quit".to_string(),
+                    RemoteCommand::Goto(index) => format!("goto {}", index),
+                    RemoteCommand::Status(reply) => {
+                        let _ = reply.send("{\"index\":1,\"paused\":false}".to_string());
+                        "status".to_string()
+                    }
+                    RemoteCommand::Playlist(reply) => {
+                        let _ = reply.send("[]".to_string());
+                        "playlist".to_string()
+                    }
+                });
+            }
+            received
+        })
+    }
+
+    // 在本机的空闲端口上启动服务
+    fn start(token: Option<&str>) -> (HttpServer, SocketAddr, JoinHandle<Vec<String>>) {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let (tx, rx) = channel();
+        let server = start_http_server(addr, token.map(str::to_string), tx).unwrap();
+        (server, addr, dummy_player(rx))
+    }
+
+    // 发送一个请求，返回状态码和响应体；headers 为额外的请求头
+    fn send(addr: SocketAddr, method: &str, path: &str, host: &str, headers: &[&str]) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut request = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: 0\r\n", method, path, host);
+        for header in headers {
+            request.push_str(header);
+            request.push_str("\r\n");
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+        let body = response.split_once("\r\n\r\n").map_or("", |(_, body)| body).to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn commands_reach_the_player() {
+        let (server, addr, player) = start(None);
+        let host = addr.to_string();
+        assert_eq!(send(addr, "POST", "/next", &host, &[]), (200, "{\"ok\":true}".to_string()));
+        assert_eq!(send(addr, "POST", "/volume?level=70", &host, &[]).0, 200);
+        assert_eq!(send(addr, "POST", "/goto?index=3", &format!("localhost:{}", addr.port()), &[]).0, 200);
+        assert_eq!(send(addr, "GET", "/status", &host, &[]), (200, "{\"index\":1,\"paused\":false}".to_string()));
+        assert_eq!(send(addr, "GET", "/playlist", &host, &[]), (200, "[]".to_string()));
+        drop(server);
+        assert_eq!(player.join().unwrap(), ["next", "volume 70", "goto 2", "status", "playlist"]);
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let (server, addr, player) = start(None);
+        let host = addr.to_string();
+        assert_eq!(send(addr, "POST", "/volume?level=101", &host, &[]).0, 400);
+        assert_eq!(send(addr, "POST", "/goto?index=0", &host, &[]).0, 400);
+        assert_eq!(send(addr, "GET", "/next", &host, &[]).0, 405);
+        assert_eq!(send(addr, "POST", "/unknown", &host, &[]).0, 404);
+        drop(server);
+        assert!(player.join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn foreign_hosts_and_origins_are_rejected() {
+        let (server, addr, player) = start(None);
+        let host = addr.to_string();
+        // DNS 重绑定：域名解析到本机，Host 仍是攻击者的域名
+        assert_eq!(send(addr, "POST", "/quit", "evil.example", &[]).0, 403);
+        assert_eq!(send(addr, "POST", "/next", &format!("evil.example:{}", addr.port()), &[]).0, 403);
+        assert_eq!(send(addr, "POST", "/next", "127.0.0.1:1", &[]).0, 403);
+        // 其他网站的页面发出的跨站请求
+        assert_eq!(send(addr, "POST", "/next", &host, &["Origin: http://evil.example"]).0, 403);
+        assert_eq!(send(addr, "POST", "/next", &host, &["Origin: null"]).0, 403);
+        assert_eq!(send(addr, "POST", "/pause", &host, &[&format!("Origin: http://{}", host)]).0, 200);
+        drop(server);
+        assert_eq!(player.join().unwrap(), ["pause"]);
+    }
+
+    #[test]
+    fn token_is_required_when_set() {
+        let (server, addr, player) = start(Some("s3cret"));
+        let host = addr.to_string();
+        assert_eq!(send(addr, "POST", "/next", &host, &[]).0, 401);
+        assert_eq!(send(addr, "POST", "/next", &host, &["Authorization: Bearer s3cre"]).0, 401);
+        assert_eq!(send(addr, "POST", "/next", &host, &["Authorization: Bearer s3cret!"]).0, 401);
+        assert_eq!(send(addr, "POST", "/next", &host, &["Authorization: Bearer s3cret"]).0, 200);
+        drop(server);
+        assert_eq!(player.join().unwrap(), ["next"]);
+    }
+
+    #[test]
+    fn public_address_needs_a_token() {
+        let (tx, _rx) = channel();
+        assert!(start_http_server("0.0.0.0:0".parse().unwrap(), None, tx).is_err());
+    }
+
+    #[test]
+    fn host_matching() {
+        let loopback: SocketAddr = "127.0.0.1:8090".parse().unwrap();
+        assert!(allowed_host("127.0.0.1:8090", loopback));
+        assert!(allowed_host("LOCALHOST:8090", loopback));
+        assert!(!allowed_host("localhost", loopback));
+        assert!(!allowed_host("127.0.0.1:8091", loopback));
+        let v6: SocketAddr = "[::1]:80".parse().unwrap();
+        assert!(allowed_host("[::1]", v6));
+        assert!(allowed_host("[::1]:80", v6));
+        assert!(!allowed_host("::1", v6));
+        assert!(allowed_host("music.lan:8090", "0.0.0.0:8090".parse().unwrap()));
+    }
+
+    #[test]
+    fn token_comparison() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
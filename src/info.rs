@@ -0,0 +1,118 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::cli::EncodingFix;
+use crate::metadata::{get_title_artist_info, get_total_duration, probe_properties, read_tag_details};
+use crate::utils::{format_duration, format_file_size, has_audio_extension, is_playlist_file, json_escape};
+
+// 两种方式读到的时长相差超过该值时视为不一致
+const DURATION_TOLERANCE: Duration = Duration::from_secs(1);
+
+// 文件按扩展名归入的类别（与目录扫描的判断一致）
+fn extension_bucket(path: &Path) -> &'static str {
+    if is_playlist_file(path) {
+        "playlist"
+    } else if has_audio_extension(path) {
+        "audio"
+    } else {
+        "unlisted"
+    }
+}
+
+// JSON 字段值：有值时为带引号的字符串，否则为 null
+fn json_string(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_string(), |v| format!("\"{}\"", json_escape(v)))
+}
+
+// JSON 字段值：有值时为数字，否则为 null
+fn json_number<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "null".to_string(), |v| v.to_string())
+}
+
+/// info 子命令：输出播放器看到的元数据，不播放。
+/// 标签（lofty）和解码器（symphonia）都无法读取时返回 false，调用方以非零状态码退出。
+/// binary_sizes 为 true 时文件大小使用二进制前缀（KiB、MiB）
+pub fn print_track_info(path: &Path, json: bool, fix: EncodingFix, binary_sizes: bool) -> bool {
+    let (title, artist) = get_title_artist_info(path, fix);
+    let player_duration = get_total_duration(path);
+    let tags = read_tag_details(path, fix);
+    let stream = probe_properties(path);
+    if tags.is_err() && stream.is_err() {
+        let reason = match &stream {
+            Err(e) => e.to_string(),
+            Ok(_) => String::new(),
+        };
+        eprintln!("[错误]无法读取 {}: {}", path.display(), reason);
+        return false;
+    }
+    let tags = tags.ok();
+    let stream = stream.ok();
+
+    // 播放器使用 symphonia 探测的时长，与 lofty 读到的属性时长对比
+    let tag_duration = tags.as_ref().map(|t| t.duration).filter(|d| !d.is_zero());
+    let probe_duration = Some(player_duration).filter(|d| !d.is_zero());
+    let duration_mismatch = match (tag_duration, probe_duration) {
+        (Some(a), Some(b)) => a.abs_diff(b) > DURATION_TOLERANCE,
+        _ => false,
+    };
+    let sample_rate = stream.as_ref().and_then(|s| s.sample_rate).or(tags.as_ref().and_then(|t| t.sample_rate));
+    let channels = stream.as_ref().and_then(|s| s.channels).or(tags.as_ref().and_then(|t| t.channels.map(usize::from)));
+    let codec = stream.as_ref().map(|s| s.codec.as_str());
+    let bitrate = tags.as_ref().and_then(|t| t.bitrate);
+    let cover_size = tags.as_ref().and_then(|t| t.cover_size);
+    let album = tags.as_ref().and_then(|t| t.album.as_deref());
+    let bucket = extension_bucket(path);
+    let file_size = std::fs::metadata(path).ok().map(|meta| meta.len());
+
+    if json {
+        let fields = [
+            ("path", json_string(Some(&path.to_string_lossy()))),
+            ("title", json_string(Some(&title))),
+            ("artist", json_string(Some(&artist))),
+            ("album", json_string(album)),
+            ("file_size", json_number(file_size)),
+            ("file_type", json_string(tags.as_ref().map(|t| t.file_type.as_str()))),
+            ("duration_tag", json_number(tag_duration.map(|d| format!("{:.3}", d.as_secs_f64())))),
+            ("duration_probe", json_number(probe_duration.map(|d| format!("{:.3}", d.as_secs_f64())))),
+            ("duration_mismatch", duration_mismatch.to_string()),
+            ("sample_rate", json_number(sample_rate)),
+            ("channels", json_number(channels)),
+            ("bits_per_sample", json_number(stream.as_ref().and_then(|s| s.bits_per_sample))),
+            ("codec", json_string(codec)),
+            ("bitrate_kbps", json_number(bitrate)),
+            ("cover_art", cover_size.is_some().to_string()),
+            ("cover_art_bytes", json_number(cover_size)),
+            ("extension_bucket", json_string(Some(bucket))),
+        ];
+        let body: Vec<String> = fields.iter().map(|(key, value)| format!("\"{}\":{}", key, value)).collect();
+        println!("{{{}}}", body.join(","));
+        return true;
+    }
+
+    let unknown = || "未知".to_string();
+    println!("文件:     {}", path.display());
+    println!("标题:     {}", title);
+    println!("艺术家:   {}", artist);
+    println!("专辑:     {}", album.unwrap_or("未知"));
+    println!("大小:     {}", file_size.map_or_else(unknown, |size| format_file_size(size, binary_sizes)));
+    println!("格式:     {}", tags.as_ref().map_or_else(unknown, |t| t.file_type.clone()));
+    let mismatch_note = if duration_mismatch { "  ⚠ 不一致" } else { "" };
+    println!(
+        "时长:     {}（标签）/ {}（解码器探测，播放器使用）{}",
+        tag_duration.map_or_else(unknown, format_duration),
+        probe_duration.map_or_else(unknown, format_duration),
+        mismatch_note
+    );
+    println!("采样率:   {}", sample_rate.map_or_else(unknown, |rate| format!("{} Hz", rate)));
+    println!("声道:     {}", channels.map_or_else(unknown, |count| count.to_string()));
+    println!("编码:     {}", codec.unwrap_or("未知"));
+    println!("码率:     {}", bitrate.map_or_else(unknown, |kbps| format!("{} kbps", kbps)));
+    println!("封面:     {}", cover_size.map_or_else(|| "无".to_string(), |size| format!("有（{}）", format_file_size(size as u64, binary_sizes))));
+    let bucket_text = match bucket {
+        "playlist" => "播放列表",
+        "audio" => "音频（目录扫描时包含）",
+        _ => "不在音频扩展名列表中（目录扫描时跳过）",
+    };
+    println!("类别:     {}", bucket_text);
+    true
+}
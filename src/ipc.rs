@@ -0,0 +1,155 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use crate::utils::json_escape;
+
+// 等待主线程回复 status/playlist 的最长时间
+#[cfg(any(unix, feature = "http"))]
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 通过控制套接字或 HTTP 收到的命令，转发给主线程处理
+pub enum RemoteCommand {
+    Next,
+    Prev,
+    Pause,
+    Play,
+    Toggle,
+    /// 音量百分比（0-100）
+    Volume(u8),
+    Quit,
+    /// 跳转到播放列表中的第几首（从 0 开始，超出范围时忽略）
+    Goto(usize),
+    /// 查询当前状态，主线程通过该通道回复一行 JSON
+    Status(Sender<String>),
+    /// 查询播放列表，主线程通过该通道回复一行 JSON 数组
+    Playlist(Sender<String>),
+}
+
+/// status 命令返回的播放状态
+pub struct PlayerStatus<'a> {
+    pub index: usize,
+    pub total_tracks: usize,
+    pub title: &'a str,
+    pub artist: &'a str,
+    pub path: &'a str,
+    pub position: Duration,
+    pub duration: Duration,
+    pub volume: f32,
+    pub paused: bool,
+    pub muted: bool,
+}
+
+impl PlayerStatus<'_> {
+    /// 生成单行 JSON
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"index\":{},\"total\":{},\"title\":\"{}\",\"artist\":\"{}\",\"path\":\"{}\",\"position\":{:.1},\"duration\":{:.1},\"volume\":{},\"paused\":{},\"muted\":{}}}",
+            self.index + 1,
+            self.total_tracks,
+            json_escape(self.title),
+            json_escape(self.artist),
+            json_escape(self.path),
+            self.position.as_secs_f64(),
+            self.duration.as_secs_f64(),
+            (self.volume * 100.0).round() as u32,
+            self.paused,
+            self.muted,
+        )
+    }
+}
+
+/// 播放列表的 JSON：每首歌的序号（从 1 开始，与 status 一致）和路径
+pub fn playlist_json(playlist: &[PathBuf]) -> String {
+    let entries: Vec<String> = playlist
+        .iter()
+        .enumerate()
+        .map(|(index, path)| format!("{{\"index\":{},\"path\":\"{}\"}}", index + 1, json_escape(&path.to_string_lossy())))
+        .collect();
+    format!("[{}]", entries.join(","))
+}
+
+/// 发送需要回复的命令（status/playlist）并等待主线程回复，失败时返回错误说明
+#[cfg(any(unix, feature = "http"))]
+pub fn request_reply(tx: &Sender<RemoteCommand>, command: fn(Sender<String>) -> RemoteCommand) -> Result<String, &'static str> {
+    let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+    tx.send(command(reply_tx)).map_err(|_| "播放器已退出")?;
+    reply_rx.recv_timeout(STATUS_TIMEOUT).map_err(|_| "等待状态超时")
+}
+
+// 解析一行命令并转发给主线程，返回写回客户端的一行响应
+#[cfg(unix)]
+fn handle_line(line: &str, tx: &Sender<RemoteCommand>) -> String {
+    let mut parts = line.split_whitespace();
+    let command = match (parts.next(), parts.next(), parts.next()) {
+        (Some("next"), None, _) => RemoteCommand::Next,
+        (Some("prev"), None, _) => RemoteCommand::Prev,
+        (Some("pause"), None, _) => RemoteCommand::Pause,
+        (Some("play"), None, _) => RemoteCommand::Play,
+        (Some("toggle"), None, _) => RemoteCommand::Toggle,
+        (Some("quit"), None, _) => RemoteCommand::Quit,
+        (Some("volume"), Some(value), None) => match value.parse::<u8>() {
+            Ok(volume) if volume <= 100 => RemoteCommand::Volume(volume),
+            _ => return "error: 音量应为 0-100".to_string(),
+        },
+        (Some("goto"), Some(value), None) => match value.parse::<usize>() {
+            Ok(index) if index >= 1 => RemoteCommand::Goto(index - 1),
+            _ => return "error: 序号应为从 1 开始的整数".to_string(),
+        },
+        (Some(query @ ("status" | "playlist")), None, _) => {
+            let command = if query == "status" { RemoteCommand::Status } else { RemoteCommand::Playlist };
+            return request_reply(tx, command).unwrap_or_else(|e| format!("error: {}", e));
+        }
+        _ => return format!("error: 未知命令 '{}'", line),
+    };
+    match tx.send(command) {
+        Ok(()) => "ok".to_string(),
+        Err(_) => "error: 播放器已退出".to_string(),
+    }
+}
+
+/// 在 path 创建 Unix 域套接字并在后台线程监听，每个连接按行读取命令。
+/// 已存在但无人监听的套接字文件（上次异常退出遗留）会被替换。
+#[cfg(unix)]
+pub fn start_ipc_server(path: &Path, tx: Sender<RemoteCommand>) -> io::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::thread;
+
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, "已有播放器在使用该套接字"));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let Ok(reader) = stream.try_clone() else { return };
+                let mut writer = stream;
+                for line in BufReader::new(reader).lines() {
+                    let Ok(line) = line else { break };
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    if writeln!(writer, "{}", handle_line(line, &tx)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// 非 Unix 平台不支持 Unix 域套接字
+#[cfg(not(unix))]
+pub fn start_ipc_server(_path: &Path, _tx: Sender<RemoteCommand>) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "当前平台不支持 Unix 域套接字"))
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cli::EncodingFix;
+use crate::export::write_list_report;
+use crate::utils::{has_audio_extension, json_escape, stream_url};
+
+// 条目存在的问题，没有问题时为 None（网络流不检查）
+fn entry_problem(path: &Path) -> Option<&'static str> {
+    if stream_url(path).is_some() {
+        None
+    } else if !path.exists() {
+        Some("missing")
+    } else if !has_audio_extension(path) {
+        Some("unsupported_extension")
+    } else {
+        None
+    }
+}
+
+/// list 子命令：输出整理后的播放列表，标记不存在或扩展名不受支持的条目，最后输出统计。
+/// sources 不为 None 时（多个输入）同时输出每首歌来自哪个输入
+pub fn print_playlist(playlist: &[PathBuf], sources: Option<&HashMap<PathBuf, PathBuf>>, json: bool) {
+    for line in format_playlist(playlist, sources, json) {
+        println!("{}", line);
+    }
+}
+
+/// 生成 print_playlist 输出的各行（JSON 模式下只有一行）
+pub fn format_playlist(playlist: &[PathBuf], sources: Option<&HashMap<PathBuf, PathBuf>>, json: bool) -> Vec<String> {
+    if json {
+        let entries: Vec<String> = playlist
+            .iter()
+            .enumerate()
+            .map(|(index, path)| {
+                let problem = entry_problem(path).map_or_else(|| "null".to_string(), |p| format!("\"{}\"", p));
+                let source = match sources.and_then(|sources| sources.get(path)) {
+                    Some(source) => format!(",\"source\":\"{}\"", json_escape(&source.to_string_lossy())),
+                    None => String::new(),
+                };
+                format!(
+                    "{{\"index\":{},\"path\":\"{}\",\"exists\":{},\"problem\":{}{}}}",
+                    index + 1,
+                    json_escape(&path.to_string_lossy()),
+                    path.exists(),
+                    problem,
+                    source
+                )
+            })
+            .collect();
+        return vec![format!("[{}]", entries.join(","))];
+    }
+
+    let width = playlist.len().to_string().len();
+    let mut lines = Vec::new();
+    let mut missing = 0;
+    let mut unsupported = 0;
+    for (index, path) in playlist.iter().enumerate() {
+        let note = match entry_problem(path) {
+            Some("missing") => {
+                missing += 1;
+                "  [不存在]"
+            }
+            Some(_) => {
+                unsupported += 1;
+                "  [扩展名不受支持]"
+            }
+            None => "",
+        };
+        let source = match sources.and_then(|sources| sources.get(path)) {
+            Some(source) => format!("  <- {}", source.display()),
+            None => String::new(),
+        };
+        lines.push(format!("{:>width$}. {}{}{}", index + 1, path.display(), note, source, width = width));
+    }
+    lines.push(format!("共 {} 首，不存在 {} 首，扩展名不受支持 {} 首。", playlist.len(), missing, unsupported));
+    lines
+}
+
+/// list --csv：把播放列表的报告写入 CSV 文件，在标准错误输出统计和读取失败的文件；写入失败时返回 false（以非零状态码退出）
+pub fn write_report(playlist: &[PathBuf], output: &Path, fix: EncodingFix) -> bool {
+    let failures = match write_list_report(playlist, output, fix) {
+        Ok(failures) => failures,
+        Err(e) => {
+            eprintln!("[错误]无法写入 {}: {}", output.display(), e);
+            return false;
+        }
+    };
+    eprintln!("已写入 {} 首到 {}。", playlist.len(), output.display());
+    if !failures.is_empty() {
+        eprintln!("其中 {} 首读取失败（对应的列留空）：", failures.len());
+        for (path, reason) in &failures {
+            eprintln!("  {}: {}", path.display(), reason);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{fixture_dir, get_playlist_from_input, write_fixture, ScanOptions};
+
+    // 展开 dir 后列出，路径去掉 dir 前缀，便于比较
+    fn list_dir(dir: &Path, json: bool) -> Vec<String> {
+        let playlist: Vec<PathBuf> = get_playlist_from_input(dir, ScanOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        let prefix = format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR);
+        format_playlist(&playlist, None, json)
+            .into_iter()
+            .map(|line| line.replace(&prefix, "").replace(&json_escape(&prefix), ""))
+            .collect()
+    }
+
+    #[test]
+    fn lists_a_fixture_tree_with_indices_and_summary() {
+        let dir = fixture_dir("list-tree");
+        for file in ["b.mp3", "a.flac", "disc1/01.ogg", "cover.jpg", "notes.txt"] {
+            write_fixture(&dir, file, "");
+        }
+        // 只列出目录下的音频文件（不进入子目录），按文件名排序
+        assert_eq!(list_dir(&dir, false), ["1. a.flac", "2. b.mp3", "共 2 首，不存在 0 首，扩展名不受支持 0 首。"]);
+    }
+
+    #[test]
+    fn flags_missing_and_unsupported_entries() {
+        let dir = fixture_dir("list-problems");
+        let song = write_fixture(&dir, "song.mp3", "");
+        let text = write_fixture(&dir, "readme.txt", "");
+        let playlist = vec![song, dir.join("gone.mp3"), text];
+        let lines = format_playlist(&playlist, None, false);
+        assert!(!lines[0].contains('['), "{}", lines[0]);
+        assert!(lines[1].ends_with("gone.mp3  [不存在]"), "{}", lines[1]);
+        assert!(lines[2].ends_with("readme.txt  [扩展名不受支持]"), "{}", lines[2]);
+        assert_eq!(lines[3], "共 3 首，不存在 1 首，扩展名不受支持 1 首。");
+    }
+
+    #[test]
+    fn json_output_marks_problems_and_sources() {
+        let dir = fixture_dir("list-json");
+        let song = write_fixture(&dir, "song.mp3", "");
+        let missing = dir.join("gone.mp3");
+        let sources = HashMap::from([(song.clone(), PathBuf::from("input"))]);
+        let lines = format_playlist(&[song.clone(), missing], Some(&sources), true);
+        assert_eq!(lines.len(), 1);
+        let json = &lines[0];
+        assert!(json.starts_with("[{\"index\":1,"), "{}", json);
+        assert!(json.contains("\"exists\":true,\"problem\":null,\"source\":\"input\"}"), "{}", json);
+        assert!(json.contains("\"index\":2,"), "{}", json);
+        assert!(json.contains("\"exists\":false,\"problem\":\"missing\"}"), "{}", json);
+    }
+
+    #[test]
+    fn empty_directory_lists_nothing() {
+        let dir = fixture_dir("list-empty");
+        write_fixture(&dir, "cover.jpg", "");
+        assert_eq!(list_dir(&dir, false), ["共 0 首，不存在 0 首，扩展名不受支持 0 首。"]);
+    }
+}
@@ -0,0 +1,91 @@
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// 日志库：事件经后台线程写入文件，不阻塞界面刷新
+use tracing::Level;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{filter::LevelFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::cli::LogLevel;
+
+// --verbose 时暂存的警告，退出时统一输出到 stderr，避免覆盖状态栏
+static WARNING_BUFFER: OnceLock<Arc<Mutex<Vec<u8>>>> = OnceLock::new();
+
+/// 写入内存缓冲区的日志输出
+struct BufferWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for BufferWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Ok(mut buffer) = self.0.lock() {
+            buffer.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl From<LogLevel> for Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Level::ERROR,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+        }
+    }
+}
+
+/// 初始化日志：指定 log_path 时按 level 写入文件（追加），verbose 时额外收集警告。
+/// 返回的 guard 需要保留到程序结束，丢弃时会把尚未写出的日志刷到文件。
+pub fn init_logging(log_path: Option<&Path>, level: LogLevel, verbose: bool) -> Result<Option<WorkerGuard>, Box<dyn Error>> {
+    if log_path.is_none() && !verbose {
+        return Ok(None);
+    }
+
+    let mut guard = None;
+    let file_layer = match log_path {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            let (writer, worker_guard) = tracing_appender::non_blocking(file);
+            guard = Some(worker_guard);
+            let layer = fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false)
+                .with_filter(LevelFilter::from_level(level.into()));
+            Some(layer)
+        }
+        None => None,
+    };
+
+    let warning_layer = if verbose {
+        let buffer = WARNING_BUFFER.get_or_init(|| Arc::new(Mutex::new(Vec::new()))).clone();
+        let layer = fmt::layer()
+            .with_writer(move || BufferWriter(buffer.clone()))
+            .with_ansi(false)
+            .with_target(false)
+            .with_filter(LevelFilter::WARN);
+        Some(layer)
+    } else {
+        None
+    };
+
+    tracing_subscriber::registry().with(file_layer).with(warning_layer).try_init()?;
+    Ok(guard)
+}
+
+/// 输出 --verbose 期间收集的警告（在终端恢复之后调用）
+pub fn flush_warnings() {
+    if let Some(buffer) = WARNING_BUFFER.get()
+        && let Ok(mut buffer) = buffer.lock()
+        && !buffer.is_empty()
+    {
+        eprint!("{}", String::from_utf8_lossy(&buffer));
+        buffer.clear();
+    }
+}
@@ -4,29 +4,85 @@
 mod cli;
 mod utils;
 mod metadata;
+mod history;
+mod transcode;
+mod display;
+mod logging;
+mod ipc;
+mod info;
+mod list;
+mod state;
+mod eq;
+mod gain;
+mod dedup;
+mod excluded;
+mod announce;
+mod export;
+mod mix;
+mod stream;
+mod validate;
+mod http;
+mod shuffle;
+mod theme;
+mod clock;
+mod picker;
+mod tmux;
+mod watch;
+mod skip;
+mod seek;
 
 // 从各个模块引入所需的项
 use clap::Parser;
-use rodio::{Decoder, OutputStream, Sink};
+use rodio::{Decoder, OutputStream, Sink, Source};
+use rodio::decoder::DecoderError;
+use rodio::source::Buffered;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use eq::BiquadFilter;
+use mix::{balance_message, ChannelMix, ChannelMixer};
 use std::time::{Instant, Duration};
-use std::{fs::File, io::{self, BufReader, Write}};
-use std::sync::mpsc::{channel, Sender, Receiver};
-use std::path::PathBuf;
+use std::{fs::File, io::{self, BufReader, Cursor, IsTerminal, Write}};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::thread;
 
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use unicode_width::UnicodeWidthStr;
+use tracing::{debug, error, info, warn};
 
 // 从 cli 模块引入常量和参数结构体
-use cli::{Args, NAME, VERSION, URL};
+use cli::{Args, Cli, Command, Announce, AutoAdvance, BufferMode, DedupMode, EncodingFix, EqPreset, RemoveMode, ShuffleMode, SortMode, NAME, VERSION, URL};
 // 从 utils 模块引入所有公共函数
-use utils::{get_playlist_from_input, truncate_string, format_duration};
+use utils::{get_playlist_from_input, truncate_string, format_duration, format_file_size, save_playlist, reveal_in_file_manager, stream_url, Debouncer, PlaylistEntry, ScanOptions, SourceType};
 // 从 metadata 模块引入元数据获取函数
-use metadata::{get_title_artist_info, get_total_duration};
+use metadata::{get_album, get_disc_track_info, group_playlist, read_group_tags, GroupTags, get_title_artist_info, resolve_title_artist, resolve_total_duration, sort_by_album, sort_by_track_number, duration_in_range, filter_by_duration};
+// 从 history 模块引入播放历史
+use history::PlayHistory;
+use shuffle::spread_playlist;
+use theme::{resolve_theme, Theme};
+use clock::PlaybackClock;
+use seek::SeekableDecoder;
+use tmux::TmuxTitle;
+use skip::{SkipAccelerator, SkipAction};
+// 从 transcode 模块引入转码入口
+use transcode::{render_playlist, transcode_playlist};
+// 从 logging 模块引入日志初始化
+use logging::{init_logging, flush_warnings};
+use state::SessionState;
+use gain::{compose_volume, loudness_gain, memory_factor, target_volume, TrackGain, VolumeMemory, LOUDNESS_SECONDS};
+// 从 ipc 模块引入控制套接字
+use ipc::{playlist_json, PlayerStatus, RemoteCommand, start_ipc_server};
+use http::start_http_server;
+// 从 display 模块引入状态栏渲染
+use display::{draw_list_rows, DisplayState, KeyBinding, LoopMode, Osd, PlaybackState, PlaylistProgress, StatusMessage, TaskbarProgress, TerminalGuard, TitleInfo, DEFAULT_WINDOW_TITLE, window_title, layout_key_bindings, guide_key_lines, render_progress_line, flush_progress_line, emit_taskbar_progress, pad_to_width, format_speed};
 
 // 终端交互库：用于控制终端（raw mode, 键入事件, 光标/清屏）
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     execute,
     terminal::{self, disable_raw_mode, enable_raw_mode, ClearType, SetTitle, SetSize},
     cursor,
@@ -34,513 +90,3163 @@ use crossterm::{
 
 // --- 常量定义 ---
 const MIN_SKIP_INTERVAL: Duration = Duration::from_millis(250); // 最小切歌间隔
+const SKIP_JUMP: i32 = 10; // Ctrl+←/→ 一次跳过的歌曲数
+const SPEED_STEP: f32 = 0.25; // 每次按 +/- 调整的倍速
+const BALANCE_STEP: f32 = 0.1; // 每次按 </> 调整的左右平衡
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED: f32 = 3.0;
+const MIN_TOGGLE_INTERVAL: Duration = Duration::from_millis(200); // 暂停/静音等切换键的最小间隔
 const VOLUME_STEP: f32 = 0.01; // 音量调节步长
+const COARSE_VOLUME_STEP: f32 = 0.1; // 按住 Shift 时的音量调节步长
 const UPDATE_INTERVAL: Duration = Duration::from_millis(1000); // 进度更新频率
+const OSD_DURATION: Duration = Duration::from_millis(1500); // 状态栏临时提示的显示时长
+const SEEK_STEP: Duration = Duration::from_secs(10); // 每次按 [/] 快退/快进的时长
+const SEEK_ERROR_DURATION: Duration = Duration::from_millis(500); // 定位失败提示的显示时长
+const PRECISE_TIME_DURATION: Duration = Duration::from_secs(2); // 定位后以毫秒精度显示播放时间的时长
 const ERROR_WAIT_DURATION: Duration = Duration::from_secs(1);
+const PRELOAD_WAIT_TIMEOUT: Duration = Duration::from_secs(5); // 等待当前歌曲加载完成的最长时间
+const HEADER_LINES: u16 = 7; // 非纯净模式下状态栏上方的说明区域行数
+const HISTORY_VISIBLE: usize = 5; // 历史面板一次最多显示的条数
+const DEFAULT_SAVE_PLAYLIST: &str = "mddplayer_playlist.m3u"; // 未指定 --save-playlist 时 W 键保存的文件
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(2); // 检查默认输出设备是否变化的间隔
+const NETWORK_RETRY_DELAY: Duration = Duration::from_millis(100); // 网络流第一次重试前的等待时间，之后每次加倍
+const NETWORK_RETRY_MAX_DELAY: Duration = Duration::from_secs(5); // 网络流重试间隔的上限
+const NETWORK_RETRY_POLL: Duration = Duration::from_millis(20); // 等待重试期间检查播放列表是否改变的间隔
+const DEVICE_MAX_FAILURES: u32 = 5; // 输出设备连续恢复失败的次数上限
+const MAX_RETRY_ATTEMPTS: u32 = 2; // 播放列表结束时每首加载失败的歌曲最多重试的次数
+const WATCH_OSD_DURATION: Duration = Duration::from_secs(2); // --watch-playlist 新增歌曲提示的显示时长
+
+// 播放中可用的全部按键，帮助面板由这张表生成；新增按键时在这里登记
+const KEY_BINDINGS: &[KeyBinding] = &[
+    KeyBinding { key: "P", action: "静音/取消静音", simple_mode: true },
+    KeyBinding { key: "空格", action: "暂停/播放", simple_mode: true },
+    KeyBinding { key: "←", action: "上一首", simple_mode: true },
+    KeyBinding { key: "→", action: "下一首", simple_mode: true },
+    KeyBinding { key: "↑", action: "音量增", simple_mode: true },
+    KeyBinding { key: "↓", action: "音量减", simple_mode: true },
+    KeyBinding { key: "Ctrl+←/→", action: "跳过 10 首", simple_mode: false },
+    KeyBinding { key: "Shift+↑/↓", action: "音量 ±10%", simple_mode: false },
+    KeyBinding { key: "+/-", action: "倍速", simple_mode: true },
+    KeyBinding { key: "</>", action: "左右平衡", simple_mode: false },
+    KeyBinding { key: "[/]", action: "快退/快进", simple_mode: true },
+    KeyBinding { key: "R", action: "播放模式", simple_mode: true },
+    KeyBinding { key: "0", action: "恢复基准音量", simple_mode: false },
+    KeyBinding { key: "H", action: "播放历史", simple_mode: false },
+    KeyBinding { key: "W", action: "保存播放列表", simple_mode: true },
+    KeyBinding { key: "O", action: "打开所在文件夹", simple_mode: false },
+    KeyBinding { key: "D", action: "移除当前歌曲", simple_mode: false },
+    KeyBinding { key: "U", action: "撤销移除", simple_mode: false },
+    KeyBinding { key: "?", action: "帮助", simple_mode: true },
+    KeyBinding { key: "Q/Ctrl+C", action: "退出", simple_mode: true },
+];
 
 // ===============================================
 // 异步预加载数据结构
 // ===============================================
 
+// 预加载的解码器：边播边从文件读取，或者（--buffer full）整首读入内存后解码，网络流边下载边解码
+enum TrackDecoder {
+    Streamed(Decoder<BufReader<File>>),
+    Buffered(Decoder<Cursor<Vec<u8>>>),
+    Network(Decoder<stream::NetworkReader>),
+    // 经过均衡器滤波的解码器（流式或内存中的）
+    Equalized(BiquadFilter<Box<dyn Source<Item = i16> + Send>>),
+    // 测量过响度的解码器：开头的样本已解码并缓存，播放时从头读取
+    Measured(Buffered<Box<dyn Source<Item = i16> + Send>>),
+    // 从歌曲中间开始播放时，用 symphonia 直接定位的解码器（--resume、--preview）
+    Seeked(SeekableDecoder),
+}
+
+impl TrackDecoder {
+    fn boxed(self) -> Box<dyn Source<Item = i16> + Send> {
+        match self {
+            TrackDecoder::Streamed(decoder) => Box::new(decoder),
+            TrackDecoder::Buffered(decoder) => Box::new(decoder),
+            TrackDecoder::Network(decoder) => Box::new(decoder),
+            TrackDecoder::Equalized(filter) => Box::new(filter),
+            TrackDecoder::Measured(buffered) => Box::new(buffered),
+            TrackDecoder::Seeked(decoder) => Box::new(decoder),
+        }
+    }
+
+    // --loudness-normalize：从缓冲的副本读取开头 LOUDNESS_SECONDS 秒计算响度均衡系数，不消耗播放用的样本
+    fn measure_loudness(self) -> (TrackDecoder, Option<f32>) {
+        let buffered = self.boxed().buffered();
+        let window = LOUDNESS_SECONDS * buffered.sample_rate() as usize * buffered.channels() as usize;
+        let samples: Vec<i16> = buffered.clone().take(window).collect();
+        (TrackDecoder::Measured(buffered), loudness_gain(&samples))
+    }
+
+    // 指定了均衡器预设时，用滤波器包装解码器（系数按音源的采样率换算）
+    fn with_equalizer(self, preset: Option<EqPreset>) -> TrackDecoder {
+        let Some(preset) = preset else { return self };
+        TrackDecoder::Equalized(BiquadFilter::new(self.boxed(), eq::preset_coefficients(preset)))
+    }
+
+    // 经过声道处理（单声道混音、左右平衡）后添加到 sink
+    fn append_to(self, sink: &Sink, channel_mix: &ChannelMix) {
+        sink.append(ChannelMixer::new(self.boxed(), channel_mix.clone()));
+    }
+}
+
+// 是否把本地文件整首读入内存（--buffer full 且不超过上限）
+fn reads_into_memory(path: &Path, buffer_limit: Option<u64>) -> bool {
+    let Some(limit) = buffer_limit else { return false };
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.len() > limit => {
+            info!(path = %path.display(), size = meta.len(), limit, "文件超过内存缓冲上限，改为流式读取");
+            false
+        }
+        _ => true,
+    }
+}
+
+// 按预加载线程相同的设置（--buffer full、均衡器）重新打开本地文件，并直接定位到 position。
+// 网络流不能定位，返回错误
+fn open_track_at(path: &Path, position: Duration, options: &LoadOptions) -> Result<TrackDecoder, String> {
+    if stream_url(path).is_some() {
+        return Err("网络流不支持定位".to_string());
+    }
+    let decoder = SeekableDecoder::open(path, reads_into_memory(path, options.buffer_limit), position)?;
+    Ok(TrackDecoder::Seeked(decoder).with_equalizer(options.equalizer))
+}
+
 // 定义用于线程间发送成功加载结果的数据结构
 struct PreloadedData {
-    decoder: rodio::Decoder<std::io::BufReader<std::fs::File>>,
+    decoder: TrackDecoder,
     title: String,
     artist: String,
+    album: Option<String>,
+    // 碟号和音轨号标签，状态栏显示为 D1T03
+    disc_number: Option<u32>,
+    track_number: Option<u32>,
     total_duration: Duration,
+    // --loudness-normalize 测得的音量系数，未开启或无法测量时为 None
+    loudness_gain: Option<f32>,
+    // --announce tts 朗读歌名的音频（在预加载线程中合成），未开启或合成失败时为 None
+    speech: Option<Box<dyn Source<Item = i16> + Send>>,
 }
 
 // 定义用于线程间发送预加载结果的消息
 enum PreloadResult {
     Success(PreloadedData, usize), // (数据, 预加载的歌曲在播放列表中的索引)
-    Failure(usize, String, String), // (索引, 错误信息类型, 文件名)
+    Failure(usize, String, String, String), // (索引, 错误信息类型, 文件名, 完整错误信息)
+    OutOfRange(usize, Duration), // (索引, 时长)：时长不在 --min-duration/--max-duration 范围内，没有解码
+}
+
+// 线程间实际发送的消息：(缓存代数, 结果)，代数不一致的结果已过期
+type PreloadMessage = (u64, PreloadResult);
+
+impl PreloadResult {
+    // 该结果对应的播放列表索引
+    fn index(&self) -> usize {
+        match self {
+            PreloadResult::Success(_, index) => *index,
+            PreloadResult::Failure(index, _, _, _) => *index,
+            PreloadResult::OutOfRange(index, _) => *index,
+        }
+    }
+}
+
+// 预加载线程的设置，启动后不再改变
+#[derive(Clone, Copy)]
+struct LoadOptions {
+    // 读取标签时的编码修复方式
+    encoding_fix: EncodingFix,
+    // 整首读入内存的文件大小上限（字节），None 表示流式读取
+    buffer_limit: Option<u64>,
+    // 均衡器预设，None 表示不处理
+    equalizer: Option<EqPreset>,
+    // --min-duration/--max-duration：探测到时长后，不在范围内的歌曲不解码
+    min_duration: Option<Duration>,
+    max_duration: Option<Duration>,
+    // --loudness-normalize：解码后测量开头的响度
+    loudness_normalize: bool,
+    // --network-retry：网络流打开失败时的重试次数
+    network_retry: u32,
+    // --announce tts：加载后合成朗读歌名的音频
+    announce_speech: bool,
+}
+
+// 预加载缓存：按索引保存已完成但尚未播放的结果，并记录正在加载中的索引，
+// 这样提前预加载的多首歌曲不会因为“不是当前需要的索引”而被丢弃。
+struct PreloadCache {
+    results: BTreeMap<usize, PreloadResult>,
+    pending: HashSet<usize>,
+    // 播放列表重新排列后递增，用于识别过期的预加载结果
+    generation: u64,
+    // 与预加载线程共享的当前 generation，重试中的线程据此判断结果是否已经作废
+    live_generation: Arc<AtomicU64>,
+    options: LoadOptions,
+    // 播放列表文件为条目提供的显示名和时长（只记录有附加信息的条目和网络流），标签缺失时使用
+    entry_hints: HashMap<PathBuf, PlaylistEntry>,
+    // 详细模式：记录每个预加载请求的发起时间，收到结果时输出耗时
+    verbose: bool,
+    requested_at: HashMap<usize, Instant>,
+    // 等待当前歌曲加载完成的最长时间
+    wait_timeout: Duration,
+}
+
+impl PreloadCache {
+    fn new(options: LoadOptions, entry_hints: HashMap<PathBuf, PlaylistEntry>, verbose: bool) -> Self {
+        PreloadCache {
+            results: BTreeMap::new(),
+            pending: HashSet::new(),
+            generation: 0,
+            live_generation: Arc::new(AtomicU64::new(0)),
+            options,
+            entry_hints,
+            verbose,
+            requested_at: HashMap::new(),
+            wait_timeout: PRELOAD_WAIT_TIMEOUT,
+        }
+    }
+
+    // 启动指定索引的预加载（索引无效、已缓存或正在加载时跳过）
+    fn request(&mut self, playlist: &[PathBuf], index: usize, tx: &Sender<PreloadMessage>) {
+        if index >= playlist.len() || self.results.contains_key(&index) || self.pending.contains(&index) {
+            return;
+        }
+        self.pending.insert(index);
+        if self.verbose {
+            self.requested_at.insert(index, Instant::now());
+        }
+        let entry = self.entry_hints.get(&playlist[index]).cloned().unwrap_or_else(|| PlaylistEntry::new(playlist[index].clone()));
+        start_preloader_thread(entry, index, self.generation, Arc::clone(&self.live_generation), self.options, tx.clone());
+    }
+
+    // 播放列表追加了新条目（--watch-playlist）：记下其中的显示名、时长和网络流信息
+    fn add_entries(&mut self, entries: &[PlaylistEntry]) {
+        for entry in entries {
+            if entry.display_name.is_some() || entry.duration_hint.is_some() || entry.source_type != SourceType::LocalFile {
+                self.entry_hints.insert(entry.path.clone(), entry.clone());
+            }
+        }
+    }
+
+    // 播放列表顺序改变后，丢弃所有缓存，并让仍在加载中的结果作废
+    fn invalidate(&mut self) {
+        self.results.clear();
+        self.pending.clear();
+        self.requested_at.clear();
+        self.generation += 1;
+        self.live_generation.store(self.generation, Ordering::SeqCst);
+    }
+
+    // 详细模式下输出从发起请求到收到成功结果的耗时和文件大小（输出到 stderr，不影响状态栏）
+    fn log_load_time(&mut self, result: &PreloadResult, playlist: &[PathBuf], binary_sizes: bool) {
+        if let Some(requested_at) = self.requested_at.remove(&result.index())
+            && let PreloadResult::Success(..) = result
+        {
+            let size = playlist
+                .get(result.index())
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|meta| format!(" ({})", format_file_size(meta.len(), binary_sizes)))
+                .unwrap_or_default();
+            eprint!("\r[DEBUG] Track {} loaded in {}ms{}\r\n", result.index() + 1, requested_at.elapsed().as_millis(), size);
+        }
+    }
+
+    // 保存一个收到的预加载结果
+    fn store(&mut self, result: PreloadResult) {
+        let index = result.index();
+        self.pending.remove(&index);
+        self.results.insert(index, result);
+    }
+
+    // 取出指定索引的结果
+    fn take(&mut self, index: usize) -> Option<PreloadResult> {
+        self.results.remove(&index)
+    }
+
+    // 丢弃播放位置之后 depth 首以外（包括已经播过）的结果，循环模式下窗口可绕回开头
+    fn evict_outside(&mut self, current: usize, depth: usize, total: usize) {
+        self.results.retain(|&index, _| (index + total - current) % total <= depth);
+    }
+}
+
+// 本次运行的播放统计
+#[derive(Default)]
+struct SessionStats {
+    errors: usize,     // 出错的歌曲数（加载失败、超时、提前结束），重试成功的歌曲不计入
+    early_ends: usize, // 其中播放提前结束（文件可能损坏）的歌曲数
+    removed_tracks: Vec<(usize, PathBuf)>, // 按 D 键移除的歌曲 (原位置, 路径)，U 键从末尾撤销
+    listened: Duration, // 已播完或切走的歌曲实际收听的歌曲内时长（不含暂停时间，倍速播放时按歌曲内的时长计）
+    failed_tracks: Vec<FailedTrack>, // 加载失败、之后还没有加载成功过的歌曲
+}
+
+// 加载失败的歌曲：最近一次失败的原因和失败的次数
+struct FailedTrack {
+    path: PathBuf,
+    reason: String,
+    failures: u32,
+}
+
+impl SessionStats {
+    // 记入一次加载失败，同一首歌再次失败（重试或下一轮循环）时只更新原因和次数
+    fn record_failure(&mut self, path: &Path, reason: String) {
+        match self.failed_tracks.iter_mut().find(|track| track.path == path) {
+            Some(track) => {
+                track.reason = reason;
+                track.failures += 1;
+            }
+            None => {
+                self.errors += 1;
+                self.failed_tracks.push(FailedTrack { path: path.to_path_buf(), reason, failures: 1 });
+            }
+        }
+    }
+
+    // 歌曲加载成功：之前失败过的歌曲不再计为出错
+    fn record_loaded(&mut self, path: &Path) {
+        if let Some(position) = self.failed_tracks.iter().position(|track| track.path == path) {
+            self.failed_tracks.remove(position);
+            self.errors -= 1;
+        }
+    }
+
+    // 播放列表结束时可以重试的歌曲：还在播放列表中（没有按 D 键移除），且重试次数没有用完
+    fn retry_candidates(&self, playlist: &[PathBuf]) -> Vec<PathBuf> {
+        self.failed_tracks
+            .iter()
+            .filter(|track| track.failures <= MAX_RETRY_ATTEMPTS && playlist.contains(&track.path))
+            .map(|track| track.path.clone())
+            .collect()
+    }
+
+    // 记入一首歌的收听时长：played 为播放过的歌曲内时长（PlaybackClock::listened），切走的歌曲只计已播放的部分，
+    // 不超过歌曲的时长（时长未知时不限制）
+    fn record_listened(&mut self, played: Duration, total: Duration) {
+        self.listened += if total.is_zero() { played } else { played.min(total) };
+    }
+}
+
+// 整个播放列表的收听进度（--playlist-progress）：listened 为已收听的时长，
+// 列表总时长中时长未知的歌曲按 assumed 估算，剩余时长从当前歌曲的 current_time 算起。
+// 已知时长的歌曲不到一半时不显示
+fn playlist_progress(
+    playlist: &[PathBuf],
+    current_index: usize,
+    current_time: Duration,
+    listened: Duration,
+    durations: &HashMap<PathBuf, Duration>,
+    assumed: Duration,
+) -> Option<PlaylistProgress> {
+    let mut known = 0;
+    let mut total = Duration::ZERO;
+    let mut remaining = Duration::ZERO;
+    for (index, path) in playlist.iter().enumerate() {
+        let duration = match durations.get(path) {
+            Some(&duration) => {
+                known += 1;
+                duration
+            }
+            None => assumed,
+        };
+        total += duration;
+        match index.cmp(&current_index) {
+            std::cmp::Ordering::Greater => remaining += duration,
+            std::cmp::Ordering::Equal => remaining += duration.saturating_sub(current_time),
+            std::cmp::Ordering::Less => {}
+        }
+    }
+    if known * 2 < playlist.len() || total.is_zero() {
+        return None;
+    }
+    let percent = (listened.as_secs_f64() / total.as_secs_f64() * 100.0).min(100.0) as u8;
+    Some(PlaylistProgress { percent, remaining, estimated: known < playlist.len() })
 }
 
-// 统一的退出清理逻辑，确保终端恢复正常
-fn graceful_exit(stdout: &mut io::Stdout) -> Result<(), Box<dyn std::error::Error>> {
+// 运行期间创建、退出时需要删除的文件（PID 文件、控制套接字）。
+// 离开 run() 时由 Drop 删除，包括用 ? 提前返回的错误路径
+#[derive(Default)]
+struct CleanupFiles(Vec<PathBuf>);
+
+impl CleanupFiles {
+    fn push(&mut self, path: PathBuf) {
+        self.0.push(path);
+    }
+}
+
+impl Drop for CleanupFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// 退出时需要恢复的终端状态和退出设置，启动时确定
+struct ExitContext {
+    // 无终端模式：没有需要恢复的终端状态
+    headless: bool,
+    // 启动时开启的任务栏进度、括号粘贴模式和鼠标捕获
+    taskbar_progress: bool,
+    bracketed_paste: bool,
+    mouse_capture: bool,
+    // --tmux-title：退出时恢复原来的窗口名
+    tmux_title: Option<TmuxTitle>,
+    // --resume：退出时保存播放位置
+    save_session: bool,
+}
+
+// 统一的退出清理逻辑，确保终端恢复正常（无终端模式下没有需要恢复的状态）。
+// PID 文件和控制套接字由 CleanupFiles 在 run() 返回时删除。
+// 返回传入的退出码，供 main 作为进程的退出状态
+fn graceful_exit(
+    stdout: &mut io::Stdout,
+    context: &ExitContext,
+    stats: &SessionStats,
+    session: &SessionState,
+    exit_code: ExitCode,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let ExitContext { headless, taskbar_progress, bracketed_paste, mouse_capture, ref tmux_title, save_session } = *context;
+    // 使用 --resume 时保存播放位置，下次从这里继续
+    if save_session
+        && let Err(e) = session.save()
+    {
+        warn!(error = %e, "无法保存播放状态");
+    }
+    if headless {
+        if stats.errors > 0 {
+            println!("本次播放出错 {} 首（其中提前结束 {} 首）。", stats.errors, stats.early_ends);
+        }
+        print_track_lists(stdout, stats, headless)?;
+        println!("播放器退出。");
+        flush_warnings();
+        return Ok(exit_code);
+    }
+    if taskbar_progress {
+        emit_taskbar_progress(stdout, TaskbarProgress::Clear)?;
+    }
+    if let Some(tmux_title) = tmux_title {
+        tmux_title.restore(stdout)?;
+    }
     // 尝试清空可视区域并移到 (0, 0) (类似 cls 的效果)
     // execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::All))?;
     execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
+    if stats.errors > 0 {
+        print!("本次播放出错 {} 首（其中提前结束 {} 首）。", stats.errors, stats.early_ends);
+        execute!(stdout, cursor::MoveToNextLine(1))?;
+    }
+    print_track_lists(stdout, stats, headless)?;
     println!("👋 播放器退出。");
+    if bracketed_paste {
+        execute!(stdout, DisableBracketedPaste)?;
+    }
+    if mouse_capture {
+        execute!(stdout, DisableMouseCapture)?;
+    }
     disable_raw_mode()?;
     execute!(stdout, cursor::Show)?;
+    flush_warnings();
+    Ok(exit_code)
+}
+
+// --use-excluded 时歌曲所属输入对应的排除列表
+fn excluded_file_of(args: &Args, sources: &HashMap<PathBuf, PathBuf>, track: &Path) -> Option<PathBuf> {
+    if !args.use_excluded {
+        return None;
+    }
+    sources.get(track).and_then(|input| excluded::excluded_file_for(input))
+}
+
+// 退出时列出最终仍加载失败的歌曲及原因、本次移除的歌曲（raw mode 下换行需要移动光标）
+fn print_track_lists(stdout: &mut io::Stdout, stats: &SessionStats, headless: bool) -> io::Result<()> {
+    let mut lines = Vec::new();
+    if !stats.failed_tracks.is_empty() {
+        lines.push(format!("加载失败的 {} 首：", stats.failed_tracks.len()));
+        lines.extend(stats.failed_tracks.iter().map(|track| format!("  {}（{}）", track.path.display(), track.reason)));
+    }
+    if !stats.removed_tracks.is_empty() {
+        lines.push(format!("本次从播放列表中移除了 {} 首：", stats.removed_tracks.len()));
+        lines.extend(stats.removed_tracks.iter().map(|(_, path)| format!("  {}", path.display())));
+    }
+    for line in lines {
+        if headless {
+            println!("{}", line);
+        } else {
+            print!("{}", line);
+            execute!(stdout, cursor::MoveToNextLine(1))?;
+        }
+    }
     Ok(())
 }
 
+// 当前歌曲的加载结果（wait_for_track 的返回值）
+enum TrackLoad {
+    Loaded(Box<PreloadedData>),
+    Failed(String, String, String), // (错误信息类型, 文件名, 完整错误信息)
+    OutOfRange(Duration),           // 时长不在 --min-duration/--max-duration 范围内
+    TimedOut,                       // 等待预加载线程超时
+    Disconnected,                   // 预加载通道关闭
+}
+
+// 取得 index 处歌曲的加载结果：优先使用缓存，否则等待预加载线程，等待时间较长以确保有时间加载。
+// 等待期间收到的其他歌曲的结果存入缓存供之后使用，重新洗牌之前发出的请求的结果直接丢弃
+fn wait_for_track(preload_cache: &mut PreloadCache, rx: &Receiver<PreloadMessage>, playlist: &[PathBuf], index: usize, binary_sizes: bool) -> TrackLoad {
+    loop {
+        let received = match preload_cache.take(index) {
+            Some(result) => Ok(result),
+            None => match rx.recv_timeout(preload_cache.wait_timeout) {
+                // 重新洗牌之前发出的请求，索引已对应别的歌曲，直接丢弃
+                Ok((generation, _)) if generation != preload_cache.generation => continue,
+                other => other.map(|(_, result)| {
+                    preload_cache.log_load_time(&result, playlist, binary_sizes);
+                    result
+                }),
+            },
+        };
+        let result = match received {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                // 放弃这次加载，之后再轮到这首歌时可以重新尝试
+                preload_cache.pending.remove(&index);
+                return TrackLoad::TimedOut;
+            }
+            Err(RecvTimeoutError::Disconnected) => return TrackLoad::Disconnected,
+        };
+        if result.index() != index {
+            // 不是当前需要的歌曲，缓存起来供之后使用
+            preload_cache.store(result);
+            continue;
+        }
+        preload_cache.pending.remove(&index);
+        return match result {
+            PreloadResult::Success(data, _) => TrackLoad::Loaded(Box::new(data)),
+            PreloadResult::Failure(_, err_type, filename, detail) => TrackLoad::Failed(err_type, filename, detail),
+            PreloadResult::OutOfRange(_, duration) => TrackLoad::OutOfRange(duration),
+        };
+    }
+}
+
 // 在后台线程启动下一首歌曲的预加载。
 fn start_preloader_thread(
-    path: PathBuf,
+    entry: PlaylistEntry,
     index: usize,
-    tx: Sender<PreloadResult>,
+    generation: u64,
+    live_generation: Arc<AtomicU64>,
+    options: LoadOptions,
+    tx: Sender<PreloadMessage>,
 ) {
+    let LoadOptions { encoding_fix, buffer_limit, equalizer, min_duration, max_duration, loudness_normalize, network_retry, announce_speech } = options;
+    let PlaylistEntry { path, display_name, duration_hint, source_type } = entry;
     let filename_display = path.file_name().map_or_else(
         || path.as_os_str().to_string_lossy().into_owned(),
         |os_str| os_str.to_string_lossy().into_owned(),
     );
 
     thread::spawn(move || {
-        let (title, artist) = get_title_artist_info(path.as_path());
-        let total_duration = get_total_duration(path.as_path());
+        let (title, artist) = resolve_title_artist(path.as_path(), encoding_fix, display_name.as_deref());
+        let total_duration = resolve_total_duration(path.as_path(), duration_hint);
+        if !duration_in_range(total_duration, min_duration, max_duration) {
+            let _ = tx.send((generation, PreloadResult::OutOfRange(index, total_duration)));
+            return;
+        }
+        let album = get_album(path.as_path(), encoding_fix);
+        let (disc_number, track_number) = get_disc_track_info(path.as_path());
 
-        let file = match File::open(&path) {
-            Ok(f) => BufReader::new(f),
-            Err(_e) => {
-                if tx.send(PreloadResult::Failure(index, "无法打开或读取".to_string(), filename_display)).is_err() {}
+        // 整首读入内存（不超过上限时），之后播放不会再因为网络或磁盘 I/O 卡顿
+        let in_memory = source_type == SourceType::LocalFile && reads_into_memory(&path, buffer_limit);
+        let opened = match &source_type {
+            SourceType::NetworkStream(url) => {
+                open_network_stream(url, network_retry, || live_generation.load(Ordering::SeqCst) != generation)
+            }
+            SourceType::LocalFile if in_memory => std::fs::read(&path).map(|bytes| Decoder::new(Cursor::new(bytes)).map(TrackDecoder::Buffered)),
+            SourceType::LocalFile => File::open(&path).map(|file| Decoder::new(BufReader::new(file)).map(TrackDecoder::Streamed)),
+        };
+        let decoded = match opened {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "无法打开或读取");
+                let _ = tx.send((generation, PreloadResult::Failure(index, "无法打开或读取".to_string(), filename_display, e.to_string())));
                 return;
             }
         };
-        let decoder = match Decoder::new(file) {
-            Ok(d) => d,
-            Err(_e) => {
-                if tx.send(PreloadResult::Failure(index, "解码失败".to_string(), filename_display)).is_err() {}
+        let decoder = match decoded {
+            Ok(d) => d.with_equalizer(equalizer),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "解码失败");
+                let _ = tx.send((generation, PreloadResult::Failure(index, "解码失败".to_string(), filename_display, e.to_string())));
                 return;
             }
         };
 
-        let data = PreloadedData{decoder, title, artist, total_duration};
-        if tx.send(PreloadResult::Success(data, index)).is_err() {
+        let (decoder, loudness_gain) = if loudness_normalize { decoder.measure_loudness() } else { (decoder, None) };
+        let speech = if announce_speech { announce::speech(&title, &artist) } else { None };
+        let data = PreloadedData{decoder, title, artist, album, disc_number, track_number, total_duration, loudness_gain, speech};
+        if tx.send((generation, PreloadResult::Success(data, index))).is_err() {
             // 主线程已退出，忽略发送失败
         }
-    });
-}
-
-// 启动指定索引的预加载（如果索引有效）
-fn start_preload_if_valid(
-    playlist: &[PathBuf],
-    index: usize,
-    tx: &Sender<PreloadResult>,
-) {
-    if index < playlist.len() {
-        let path = playlist[index].clone();
-        start_preloader_thread(path, index, tx.clone());
+    });
+}
+
+// 打开网络流并识别格式
+fn open_network_stream(url: &str, retries: u32, cancelled: impl Fn() -> bool) -> io::Result<Result<TrackDecoder, DecoderError>> {
+    retry_network_open(url, retries, cancelled, || stream::open(url).map(|reader| Decoder::new(reader).map(TrackDecoder::Network)))
+}
+
+// 连接失败、读取中断等 I/O 错误时最多重试 retries 次，间隔从 NETWORK_RETRY_DELAY 起每次加倍；
+// 无法识别格式等解码错误重试也不会成功，直接返回。播放列表已经改变（cancelled 返回 true，结果会被丢弃）时不再重试
+fn retry_network_open<T>(
+    url: &str,
+    retries: u32,
+    cancelled: impl Fn() -> bool,
+    mut open: impl FnMut() -> io::Result<Result<T, DecoderError>>,
+) -> io::Result<Result<T, DecoderError>> {
+    let mut attempt = 0;
+    loop {
+        let result = open();
+        let error = match &result {
+            Err(e) => e.to_string(),
+            Ok(Err(DecoderError::IoError(e))) => e.clone(),
+            Ok(_) => return result,
+        };
+        if attempt >= retries || cancelled() {
+            return result;
+        }
+        let delay = NETWORK_RETRY_DELAY.saturating_mul(2u32.saturating_pow(attempt)).min(NETWORK_RETRY_MAX_DELAY);
+        warn!(url, attempt = attempt + 1, error = %error, "网络流打开失败，{}ms 后重试", delay.as_millis());
+        if !sleep_unless_cancelled(delay, &cancelled) {
+            return result;
+        }
+        attempt += 1;
+    }
+}
+
+// 分段等待 delay，每段之间检查 cancelled，播放列表改变时不必等完整个退避间隔。等满时返回 true
+fn sleep_unless_cancelled(delay: Duration, cancelled: &impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        if cancelled() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep((deadline - now).min(NETWORK_RETRY_POLL));
+    }
+}
+
+// 定位到当前歌曲的 position：按预加载线程相同的设置（--buffer full、均衡器）重新打开文件，
+// 用 symphonia 直接定位（见 open_track_at），不在音频回调中解码并丢弃 position 之前的部分。
+// 文件无法重新打开或定位时（以及网络流）返回错误，sink 中的歌曲保持原样继续播放
+fn seek_to(sink: &Sink, path: &Path, position: Duration, options: &LoadOptions, channel_mix: &ChannelMix) -> Result<(), String> {
+    let decoder = open_track_at(path, position, options)?;
+    let paused = sink.is_paused();
+    // clear 会同时暂停 sink
+    sink.clear();
+    decoder.append_to(sink, channel_mix);
+    if !paused {
+        sink.play();
+    }
+    Ok(())
+}
+
+// 从 current 移动 offset 首后的位置：循环播放时首尾相接，否则停在第一首或最后一首
+fn skip_target(current: usize, offset: i32, total: usize, wrap: bool) -> usize {
+    if total == 0 {
+        return 0;
+    }
+    let target = current as i64 + offset as i64;
+    if wrap {
+        target.rem_euclid(total as i64) as usize
+    } else {
+        target.clamp(0, total as i64 - 1) as usize
+    }
+}
+
+// 鼠标操作对应的按键：滚轮向上为上一首（←），向下为下一首（→），左键单击为暂停/播放（空格）
+fn mouse_key(mouse_event: MouseEvent) -> Option<KeyEvent> {
+    let code = match mouse_event.kind {
+        MouseEventKind::ScrollUp => KeyCode::Left,
+        MouseEventKind::ScrollDown => KeyCode::Right,
+        MouseEventKind::Down(MouseButton::Left) => KeyCode::Char(' '),
+        _ => return None,
+    };
+    Some(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+// 暂停或继续播放，并在同一时刻记下暂停的起止时间（而不是等下一次刷新时才发现状态变化），
+// 继续播放后的播放时间不会因此跳动
+fn set_paused(sink: &Sink, paused: bool, clock: &mut PlaybackClock) {
+    if paused {
+        sink.pause();
+    } else {
+        sink.play();
+    }
+    clock.set_paused(paused, Instant::now());
+}
+
+// 在 sink 中播放歌曲之前的提示（--announce），播完、按任意键或收到停止信号时结束，结束后 sink 为空。
+// 按 Q/Ctrl+C 时返回 true，由调用方退出播放器
+fn play_announcement(sink: &Sink, announcement: Box<dyn Source<Item = i16> + Send>, headless: bool, stop_requested: &AtomicBool) -> io::Result<bool> {
+    sink.clear();
+    sink.append(announcement);
+    sink.play();
+    let mut quit = false;
+    while !sink.empty() && !stop_requested.load(Ordering::SeqCst) {
+        if headless {
+            thread::sleep(Duration::from_millis(50));
+        } else if event::poll(Duration::from_millis(50))?
+            && let Event::Key(key_event) = event::read()?
+        {
+            quit = matches!(key_event.code, KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('c'));
+            break;
+        }
+    }
+    sink.clear();
+    Ok(quit)
+}
+
+// 是否能读取按键。标准输入被管道占用时（例如 fd -e flac | mddplayer -），crossterm 会改从控制终端读取
+// （Unix 上为 /dev/tty，Windows 上为 CONIN$）；控制终端也无法打开时只能使用无终端模式
+fn terminal_input_available() -> bool {
+    if io::stdin().is_terminal() {
+        return true;
+    }
+    let console = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+    std::fs::OpenOptions::new().read(true).write(true).open(console).is_ok()
+}
+
+// 输出终端响铃字符
+fn ring_bell(stdout: &mut io::Stdout) -> io::Result<()> {
+    write!(stdout, "\x07")?;
+    stdout.flush()
+}
+
+// 显示警告信息并等待
+fn display_warning_and_wait(
+    stdout: &mut io::Stdout,
+    message: &str,
+    headless: bool,
+    bell: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if bell {
+        ring_bell(stdout)?;
+    }
+    if headless {
+        eprintln!("{}", message);
+        return Ok(());
+    }
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    flush_progress_line(stdout, &pad_to_width(message, terminal_width))?;
+    thread::sleep(ERROR_WAIT_DURATION);
+    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
+    Ok(())
+}
+
+// 在状态栏询问是否覆盖已存在的文件，按 Y 确认，其他键取消
+fn confirm_overwrite(stdout: &mut io::Stdout, path: &std::path::Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    let prompt = format!("{} 已存在，按 Y 覆盖，其他键取消", path.display());
+    flush_progress_line(stdout, &pad_to_width(&prompt, terminal_width))?;
+    loop {
+        if let Event::Key(key_event) = event::read()? {
+            return Ok(matches!(key_event.code, KeyCode::Char('y') | KeyCode::Char('Y')));
+        }
+    }
+}
+
+// 显示错误信息并等待；detail 为完整错误信息（仅 --verbose 时传入），有值时不截断文件名
+#[allow(clippy::too_many_arguments)]
+fn display_error_and_wait(
+    stdout: &mut io::Stdout,
+    current_index: usize,
+    total_tracks: usize,
+    err_type: &str,
+    filename: &str,
+    detail: Option<&str>,
+    headless: bool,
+    bell: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // 提示音是声音输出，无终端模式下同样生效
+    if bell {
+        ring_bell(stdout)?;
+    }
+    if headless {
+        match detail {
+            Some(detail) => eprintln!("[{}/{}] [错误:{}]: {} ({}) -> 跳过", current_index + 1, total_tracks, err_type, filename, detail),
+            None => eprintln!("[{}/{}] [错误:{}]: {} -> 跳过", current_index + 1, total_tracks, err_type, filename),
+        }
+        return Ok(());
+    }
+    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
+    let track_info = format!("[{}/{}]", current_index + 1, total_tracks);
+    let error_msg = match detail {
+        Some(detail) => format!("{} ({})", filename, detail),
+        None => truncate_string(filename, 30),
+    };
+    eprint!("{} [错误:{}]: {} -> 跳过...", track_info, err_type, error_msg);
+    thread::sleep(ERROR_WAIT_DURATION);
+    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
+    Ok(())
+}
+
+// 播放列表结束时询问是否重试加载失败的歌曲，按 Y/Enter 重试，其他键跳过；收到停止信号时不重试
+fn confirm_retry(stdout: &mut io::Stdout, count: usize, stop_requested: &AtomicBool) -> io::Result<bool> {
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    let prompt = format!("有 {} 首歌曲加载失败，是否重试？[Y/Enter]重试 [其他键]跳过", count);
+    flush_progress_line(stdout, &pad_to_width(&prompt, terminal_width))?;
+    let retry = loop {
+        if stop_requested.load(Ordering::SeqCst) {
+            break false;
+        }
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        if let Event::Key(key_event) = event::read()? {
+            break matches!(key_event.code, KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter);
+        }
+    };
+    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
+    Ok(retry)
+}
+
+// 绘制程序说明区域（非纯净模式），逐行定位绘制，便于历史面板关闭后重绘。
+// 按键提示由 KEY_BINDINGS 生成，与帮助面板一致
+fn draw_control_guide(stdout: &mut io::Stdout) -> Result<(), Box<dyn std::error::Error>> {
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    let mut lines = vec![
+        format!("=====================【 {} 】======================", NAME),
+        format!(" 版本:v{}          主页:{}", VERSION, URL),
+        "====================【 控 制 说 明 】======================".to_string(),
+    ];
+    let key_rows = HEADER_LINES as usize - lines.len() - 1;
+    let mut key_lines: Vec<String> = guide_key_lines(KEY_BINDINGS, terminal_width.saturating_sub(2), key_rows)
+        .into_iter()
+        .map(|line| format!(" {}", line))
+        .collect();
+    key_lines.resize(key_rows, String::new());
+    lines.extend(key_lines);
+    lines.push("============================================================".to_string());
+    for (row, line) in lines.iter().enumerate() {
+        execute!(stdout, cursor::MoveTo(0, row as u16), terminal::Clear(ClearType::CurrentLine))?;
+        print!("{}", line);
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+// 在说明区域绘制播放历史面板（最新的在最上面），绘制完成后光标回到状态栏
+fn draw_history_overlay(
+    stdout: &mut io::Stdout,
+    history: &PlayHistory,
+    selected: usize,
+    status_line: u16,
+) -> Result<(), Box<dyn std::error::Error>> {
+    execute!(stdout, cursor::MoveTo(0, 0), terminal::Clear(ClearType::CurrentLine))?;
+    print!("====================【 播 放 历 史 】======================");
+    draw_list_rows(stdout, 1, HISTORY_VISIBLE, selected, |position| {
+        let entry = history.newest(position)?;
+        let minutes_ago = entry.played_at.elapsed().as_secs() / 60;
+        let ago = if minutes_ago == 0 { "刚刚".to_string() } else { format!("{}分钟前", minutes_ago) };
+        Some(format!("[{}] {}-{} ({})", entry.index + 1, entry.title, entry.artist, ago))
+    })?;
+    if history.entries.is_empty() {
+        execute!(stdout, cursor::MoveTo(0, 1))?;
+        print!(" 暂无播放记录");
+    }
+    execute!(stdout, cursor::MoveTo(0, HEADER_LINES - 1), terminal::Clear(ClearType::CurrentLine))?;
+    print!(" [↑↓]选择    [Enter]跳转播放    [H]关闭历史");
+    execute!(stdout, cursor::MoveTo(0, status_line))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+// 在说明区域绘制按键帮助面板，绘制完成后光标回到状态栏
+fn draw_help_overlay(stdout: &mut io::Stdout, status_line: u16) -> Result<(), Box<dyn std::error::Error>> {
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    let lines = layout_key_bindings(KEY_BINDINGS, false, terminal_width.saturating_sub(1));
+    execute!(stdout, cursor::MoveTo(0, 0), terminal::Clear(ClearType::CurrentLine))?;
+    print!("====================【 按 键 帮 助 】======================");
+    for row in 1..HEADER_LINES - 1 {
+        execute!(stdout, cursor::MoveTo(0, row), terminal::Clear(ClearType::CurrentLine))?;
+        if let Some(line) = lines.get(row as usize - 1) {
+            print!(" {}", line);
+        }
+    }
+    execute!(stdout, cursor::MoveTo(0, HEADER_LINES - 1), terminal::Clear(ClearType::CurrentLine))?;
+    print!(" [?/Esc]关闭帮助");
+    execute!(stdout, cursor::MoveTo(0, status_line))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+// 查找第一首文件名（不含扩展名）或标题包含指定文本的歌曲（不区分大小写）
+fn find_track_by_title(playlist: &[PathBuf], needle: &str, encoding_fix: EncodingFix) -> Option<usize> {
+    let needle = needle.to_lowercase();
+    playlist.iter().position(|path| {
+        let stem_matches = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase().contains(&needle))
+            .unwrap_or(false);
+        // 文件名不匹配时才读取标签，避免不必要的 IO
+        stem_matches || get_title_artist_info(path, encoding_fix).0.to_lowercase().contains(&needle)
+    })
+}
+
+// 切歌后重新应用静音状态：静音时保持音量为 0，否则沿用 sink 当前的音量。
+// sink.clear() / sink.append() 本身不会重置音量，这里显式设置是为了不依赖 rodio 的实现细节。
+fn reapply_mute_state(sink: &Sink, muted_volume: Option<f32>) {
+    if muted_volume.is_some() {
+        sink.set_volume(0.0);
+    }
+}
+
+// 切歌：清空 sink 中上一首的音源，换上新的解码器，再重新应用静音状态（自动切歌和手动切歌都会经过这里）
+fn replace_track_source(sink: &Sink, decoder: TrackDecoder, channel_mix: &ChannelMix, muted_volume: Option<f32>) {
+    sink.clear();
+    decoder.append_to(sink, channel_mix);
+    reapply_mute_state(sink, muted_volume);
+}
+
+// 窗口标题中显示的播放状态（暂停优先于静音）
+fn playback_state(sink: &Sink, muted_volume: Option<f32>) -> PlaybackState {
+    if sink.is_paused() {
+        PlaybackState::Paused
+    } else if muted_volume.is_some() {
+        PlaybackState::Muted
+    } else {
+        PlaybackState::Playing
+    }
+}
+
+// 窗口标题与上次设置的不同时才更新，避免每次循环都向终端写入
+fn update_window_title(stdout: &mut io::Stdout, shown: &mut String, title: String) -> io::Result<()> {
+    if *shown != title {
+        execute!(stdout, SetTitle(&title))?;
+        *shown = title;
+    }
+    Ok(())
+}
+
+// 音量变化时的临时提示文本
+fn volume_message(volume: f32) -> String {
+    format!("音量 {:.0}%", volume * 100.0)
+}
+
+// 用户在播放中把实际音量从 old 调到 new：变化先记为这首歌的记忆音量（超出 ±30% 的部分计入基准音量），
+// 未开启记忆音量时全部计入基准音量。记忆音量只修改内存中的记录，切歌和退出时才保存
+fn apply_volume_change(memory: &mut Option<VolumeMemory>, path: &Path, track_gain: &mut TrackGain, base_volume: &mut f32, old: f32, new: f32) {
+    let target = target_volume(*base_volume, track_gain, old, new);
+    if let Some(memory) = memory
+        && *base_volume > 0.0
+    {
+        track_gain.memory = memory.remember(path, memory_factor(*base_volume, track_gain, target));
+    }
+    rescale_base_volume(base_volume, track_gain, target);
+}
+
+// 按想要的音量（见 target_volume）反推基准音量；各项增益为 0 时保持不变
+fn rescale_base_volume(base_volume: &mut f32, track_gain: &TrackGain, target: f32) {
+    if track_gain.factor() > 0.0 {
+        *base_volume = target / track_gain.factor();
+    }
+}
+
+// ↑/↓ 每次调整的音量：按住 Shift 时为粗调（仅非纯净模式）
+fn volume_step(modifiers: KeyModifiers, is_simple_mode: bool) -> f32 {
+    if !is_simple_mode && modifiers.contains(KeyModifiers::SHIFT) { COARSE_VOLUME_STEP } else { VOLUME_STEP }
+}
+
+// 需要用户看到的提示：在状态栏的 OSD 中显示，无终端模式下没有状态栏，输出到标准错误
+fn show_notice(osd: &mut Osd, headless: bool, message: &str) {
+    if headless {
+        eprintln!("[警告]{}", message);
+    } else {
+        osd.show(message);
+    }
+}
+
+// 调整音量，结果不超过音量上限
+fn adjust_volume(sink: &Sink, delta: f32, max_volume: f32) {
+    let current_volume = sink.volume();
+    let new_volume = (current_volume + delta).clamp(0.0, max_volume);
+    sink.set_volume(new_volume);
+}
+
+// 音频输出流，播放期间保持存活即可
+enum AudioStream {
+    Rodio { _stream: OutputStream },
+    // --audio-buffer-size：直接用 cpal 打开的输出流，回调从 rodio 的混音器取采样
+    Fixed { _stream: rodio::cpal::Stream },
+}
+
+// 打开输出设备并创建 Sink（启动和设备切换后重建时共用）。
+// 指定了设备名时只使用该设备；否则使用默认设备，默认设备不可用时依次尝试其他输出设备。
+// 指定了 buffer_size 时先尝试以固定缓冲区大小打开，失败时给出警告并按默认设置打开
+fn open_audio_output(volume: f32, output_device: Option<&str>, buffer_size: Option<u32>) -> Result<(AudioStream, Sink), Box<dyn std::error::Error>> {
+    if let Some(frames) = buffer_size {
+        match open_fixed_buffer_output(output_device, frames) {
+            Ok((stream, sink)) => {
+                sink.set_volume(volume);
+                info!(device = ?output_device, frames, "已以固定缓冲区大小打开音频输出设备");
+                return Ok((AudioStream::Fixed { _stream: stream }, sink));
+            }
+            Err(e) => warn!(error = %e, frames, "无法以指定的缓冲区大小打开输出设备，改用默认设置"),
+        }
+    }
+    let (stream, stream_handle, device_name) = match output_device {
+        Some(name) => {
+            let device = find_output_device(name).ok_or_else(|| format!("未找到输出设备 '{}'", name))?;
+            let (stream, stream_handle) = OutputStream::try_from_device(&device)?;
+            (stream, stream_handle, Some(name.to_string()))
+        }
+        None => match OutputStream::try_default() {
+            Ok((stream, stream_handle)) => (stream, stream_handle, default_output_device_name()),
+            Err(e) => {
+                warn!(error = %e, "默认输出设备不可用，尝试其他设备");
+                open_fallback_output()?
+            }
+        },
+    };
+    let sink = Sink::try_new(&stream_handle)?;
+    sink.set_volume(volume);
+    info!(device = ?device_name, "已打开音频输出设备");
+    Ok((AudioStream::Rodio { _stream: stream }, sink))
+}
+
+// 以固定缓冲区大小（帧数）打开输出设备。rodio 的 OutputStream 不能设置缓冲区大小，
+// 这里自己用 cpal 建立输出流，由 rodio 的混音器提供采样，Sink 的输出接入混音器。
+// 设备报告的缓冲区范围不包含 frames，或者以固定大小建立失败时，改用设备默认的缓冲区大小并给出警告
+fn open_fixed_buffer_output(output_device: Option<&str>, frames: u32) -> Result<(rodio::cpal::Stream, Sink), Box<dyn std::error::Error>> {
+    use rodio::cpal::traits::StreamTrait;
+    use rodio::cpal::{BufferSize, SampleFormat, SupportedBufferSize};
+
+    let device = match output_device {
+        Some(name) => find_output_device(name).ok_or_else(|| format!("未找到输出设备 '{}'", name))?,
+        None => rodio::cpal::default_host().default_output_device().ok_or("没有默认输出设备")?,
+    };
+    let supported = device.default_output_config()?;
+    let mut config = supported.config();
+    let fixed_supported = match supported.buffer_size() {
+        SupportedBufferSize::Range { min, max } => (*min..=*max).contains(&frames),
+        SupportedBufferSize::Unknown => true,
+    };
+    if !fixed_supported {
+        warn!(frames, range = ?supported.buffer_size(), "输出设备不支持指定的缓冲区大小，使用默认缓冲区大小");
+    }
+
+    let build = |config: &rodio::cpal::StreamConfig| {
+        let (controller, mixer) = rodio::dynamic_mixer::mixer::<f32>(config.channels, config.sample_rate.0);
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_mixer_stream::<f32>(&device, config, mixer),
+            SampleFormat::I16 => build_mixer_stream::<i16>(&device, config, mixer),
+            SampleFormat::U16 => build_mixer_stream::<u16>(&device, config, mixer),
+            format => return Err(format!("不支持的采样格式 {:?}", format).into()),
+        }?;
+        Ok::<_, Box<dyn std::error::Error>>((stream, controller))
+    };
+    let (stream, controller) = if fixed_supported {
+        config.buffer_size = BufferSize::Fixed(frames);
+        match build(&config) {
+            Ok(output) => output,
+            Err(e) => {
+                warn!(error = %e, frames, "输出设备不支持固定缓冲区大小，使用默认缓冲区大小");
+                config.buffer_size = BufferSize::Default;
+                build(&config)?
+            }
+        }
+    } else {
+        build(&config)?
+    };
+    stream.play()?;
+
+    let (sink, queue) = Sink::new_idle();
+    controller.add(queue);
+    Ok((stream, sink))
+}
+
+// 建立采样格式为 T 的输出流，回调中从混音器取采样（混音器没有数据时输出静音）
+fn build_mixer_stream<T>(
+    device: &rodio::cpal::Device,
+    config: &rodio::cpal::StreamConfig,
+    mut mixer: rodio::dynamic_mixer::DynamicMixer<f32>,
+) -> Result<rodio::cpal::Stream, rodio::cpal::BuildStreamError>
+where
+    T: rodio::cpal::SizedSample + rodio::cpal::FromSample<f32>,
+{
+    device.build_output_stream::<T, _, _>(
+        config,
+        move |data: &mut [T], _| {
+            for sample in data.iter_mut() {
+                *sample = T::from_sample(mixer.next().unwrap_or(0.0));
+            }
+        },
+        |e| {
+            warn!(error = %e, "音频输出流出错");
+            STREAM_ERROR.store(true, Ordering::Relaxed);
+        },
+        None,
+    )
+}
+
+// 在所有音频后端中按名称查找输出设备
+fn find_output_device(name: &str) -> Option<rodio::cpal::Device> {
+    rodio::cpal::available_hosts()
+        .into_iter()
+        .filter_map(|host_id| rodio::cpal::host_from_id(host_id).ok())
+        .filter_map(|host| host.output_devices().ok())
+        .flatten()
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+}
+
+// 依次尝试所有音频后端的所有输出设备，返回第一个能打开的；全部失败时列出尝试过的设备
+fn open_fallback_output() -> Result<(OutputStream, rodio::OutputStreamHandle, Option<String>), Box<dyn std::error::Error>> {
+    let mut attempted = Vec::new();
+    for host_id in rodio::cpal::available_hosts() {
+        let Ok(host) = rodio::cpal::host_from_id(host_id) else { continue };
+        let Ok(devices) = host.output_devices() else { continue };
+        for device in devices {
+            let name = device.name().unwrap_or_else(|_| "未知设备".to_string());
+            match OutputStream::try_from_device(&device) {
+                Ok((stream, stream_handle)) => {
+                    warn!(device = %name, host = host_id.name(), "已改用其他输出设备");
+                    return Ok((stream, stream_handle, Some(name)));
+                }
+                Err(e) => attempted.push(format!("{}（{}）: {}", name, host_id.name(), e)),
+            }
+        }
+    }
+    eprintln!("[错误]没有可用的音频输出设备，已尝试：");
+    for device in &attempted {
+        eprintln!("  - {}", device);
+    }
+    if attempted.is_empty() {
+        eprintln!("  （未找到任何输出设备）");
+    }
+    Err("没有可用的音频输出设备".into())
+}
+
+// 输出流的错误回调是否触发过（设备被拔出、音频后端出错等），重建输出设备成功后清除
+static STREAM_ERROR: AtomicBool = AtomicBool::new(false);
+
+// 当前默认输出设备的名称，用于检测设备切换（插拔耳机、切换蓝牙设备等）
+fn default_output_device_name() -> Option<String> {
+    rodio::cpal::default_host().default_output_device().and_then(|device| device.name().ok())
+}
+
+// 输出设备恢复状态：连续失败时逐次延长重试间隔，超过次数后放弃
+struct DeviceRecovery {
+    device_name: Option<String>,
+    last_check: Instant,
+    failures: u32,
+    retry_at: Instant,
+    gave_up: bool,
+}
+
+impl DeviceRecovery {
+    fn new() -> Self {
+        DeviceRecovery {
+            device_name: default_output_device_name(),
+            last_check: Instant::now(),
+            failures: 0,
+            retry_at: Instant::now(),
+            gave_up: false,
+        }
+    }
+
+    // 定期检查默认输出设备是否变化
+    fn device_changed(&mut self) -> bool {
+        if self.last_check.elapsed() < DEVICE_CHECK_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let device_name = default_output_device_name();
+        device_name.is_some() && device_name != self.device_name
+    }
+
+    // 歌曲提前变空时判断是否是输出设备失效造成的：输出流报告过错误，或默认输出设备已经变化
+    // （指定了 --output-device 时不比较默认设备）
+    fn output_failed(&mut self, follow_default: bool) -> bool {
+        if STREAM_ERROR.swap(false, Ordering::Relaxed) {
+            return true;
+        }
+        if !follow_default {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let device_name = default_output_device_name();
+        device_name.is_some() && device_name != self.device_name
+    }
+}
+
+// 重建输出设备时的设置：输出设备和缓冲区大小，以及重新打开歌曲时使用的加载选项（均衡器等）和声道设置
+struct OutputOptions<'a> {
+    device: Option<&'a str>,
+    buffer_size: Option<u32>,
+    load_options: &'a LoadOptions,
+    channel_mix: &'a ChannelMix,
+}
+
+// 重建输出设备，并重新打开当前歌曲从 position（播放时钟的位置）处继续播放（保持音量、倍速、均衡器、声道设置和暂停状态）。
+// 网络流不能定位，记录警告后重新连接，从直播的当前位置播放。
+// 失败时按 2^n 秒退避，连续失败 DEVICE_MAX_FAILURES 次后暂停播放并提示错误。
+fn recover_audio_output(
+    stdout: &mut io::Stdout,
+    recovery: &mut DeviceRecovery,
+    sink: &Sink,
+    path: &std::path::Path,
+    position: Duration,
+    output: &OutputOptions,
+    headless: bool,
+) -> Result<Option<(AudioStream, Sink)>, Box<dyn std::error::Error>> {
+    if recovery.gave_up || Instant::now() < recovery.retry_at {
+        return Ok(None);
+    }
+    let rebuilt = open_audio_output(sink.volume(), output.device, output.buffer_size).and_then(|(stream, new_sink)| {
+        new_sink.set_speed(sink.speed());
+        if let Err(e) = seek_to(&new_sink, path, position, output.load_options, output.channel_mix) {
+            let Some(url) = stream_url(path) else { return Err(e.into()) };
+            warn!(url, position = ?position, error = %e, "无法定位到原来的位置，重新连接网络流");
+            let decoder = open_network_stream(url, 0, || false)??;
+            decoder.with_equalizer(output.load_options.equalizer).append_to(&new_sink, output.channel_mix);
+        }
+        if sink.is_paused() {
+            new_sink.pause();
+        }
+        Ok((stream, new_sink))
+    });
+    match rebuilt {
+        Ok(output) => {
+            recovery.failures = 0;
+            recovery.device_name = default_output_device_name();
+            STREAM_ERROR.store(false, Ordering::Relaxed);
+            // 新设备已经开始播放，提示期间的时间照常计入进度
+            display_warning_and_wait(stdout, "输出设备已切换，正在恢复…", headless, false)?;
+            Ok(Some(output))
+        }
+        Err(e) => {
+            recovery.failures += 1;
+            warn!(error = %e, failures = recovery.failures, "恢复输出设备失败");
+            if recovery.failures >= DEVICE_MAX_FAILURES {
+                recovery.gave_up = true;
+                error!("输出设备连续恢复失败，已暂停播放");
+                sink.pause();
+                display_warning_and_wait(stdout, &format!("[错误]无法恢复输出设备，已暂停: {}", e), headless, true)?;
+            } else {
+                recovery.retry_at = Instant::now() + Duration::from_secs(1 << recovery.failures);
+            }
+            Ok(None)
+        }
+    }
+}
+
+
+// prepare_playlist 整理好的播放列表
+struct PreparedPlaylist {
+    tracks: Vec<PathBuf>,
+    // 按路径记录的带有显示名或时长的条目（来自 m3u 的 #EXTINF）
+    entry_hints: HashMap<PathBuf, PlaylistEntry>,
+    // 每首歌来自哪个输入参数（同一首歌出现在多个输入中时记录第一个）
+    sources: HashMap<PathBuf, PathBuf>,
+    // --group-by：分组用的标签，每轮重新分组时复用（未分组时为空）
+    group_tags: HashMap<PathBuf, GroupTags>,
+}
+
+// 解析所有输入并整理播放列表：去重、时长过滤、排序、洗牌、截断。播放和 list 子命令共用同一流程。
+// 随机模式下用 shuffle_seed 洗牌，相同的种子得到相同的顺序（--resume 依赖这一点）。
+fn prepare_playlist(args: &Args, inputs: &[PathBuf], shuffle_seed: Option<u64>) -> Result<PreparedPlaylist, String> {
+    // 按参数顺序展开每个输入并拼接
+    let scan_options = ScanOptions { include_hidden: args.include_hidden, ignore_nomedia: args.ignore_nomedia, verbose: args.verbose };
+    let mut entries = Vec::new();
+    let mut sources = HashMap::new();
+    for input in inputs {
+        let mut input_entries = get_playlist_from_input(input, scan_options)
+            .map_err(|e| format!("[错误]处理输入路径 '{}' 时失败: {}", input.display(), e))?;
+        // --use-excluded：去掉上次按 D 键移除、记录在输入旁边 excluded.txt 中的歌曲
+        if args.use_excluded
+            && let Some(file) = excluded::excluded_file_for(input)
+        {
+            let excluded_tracks = excluded::load_excluded(&file);
+            if !excluded_tracks.is_empty() {
+                let before = input_entries.len();
+                input_entries.retain(|entry| !excluded_tracks.contains(&excluded::canonical(&entry.path)));
+                eprintln!("已按 {} 排除 {} 首。", file.display(), before - input_entries.len());
+            }
+        }
+        for entry in &input_entries {
+            sources.entry(entry.path.clone()).or_insert_with(|| input.clone());
+        }
+        entries.extend(input_entries);
+    }
+
+    // --dedup：同一个文件（按规范化路径判断）只保留第一次出现的位置；
+    // tag 模式再按标签找出不同路径下的同一首歌，保留音质最好的文件（audio 模式在播放时于后台检查）
+    if let Some(mode) = args.dedup {
+        let removed = dedup::dedup_by_path(&mut entries);
+        if removed > 0 {
+            eprintln!("已移除 {} 个重复的路径。", removed);
+        }
+        if mode == DedupMode::Tag {
+            let duplicates = dedup::dedup_by_tag(&mut entries, args.fix_encoding);
+            if !duplicates.is_empty() {
+                eprintln!("已移除 {} 首标签重复的歌曲：", duplicates.len());
+                for duplicate in &duplicates {
+                    eprintln!("  保留 {}，移除 {}", duplicate.kept.display(), duplicate.dropped.display());
+                }
+            }
+        }
+    }
+
+    // --skip-duplicates-by-title：标题和艺术家相同（不区分大小写）的歌曲只保留第一次出现的文件
+    if args.skip_duplicates_by_title {
+        let removed = dedup::dedup_by_title(&mut entries, args.fix_encoding);
+        if removed > 0 {
+            eprintln!("已移除 {} 首标题和艺术家重复的歌曲。", removed);
+        }
+    }
+
+    // 按时长过滤：播放列表文件（#EXTINF）已经给出时长的条目在这里去掉，使曲目数准确；
+    // 其他歌曲不在启动时逐个探测，而是在预加载探测到时长后跳过
+    if args.min_duration.is_some() || args.max_duration.is_some() {
+        entries.retain(|entry| entry.duration_hint.is_none_or(|duration| duration_in_range(duration, args.min_duration, args.max_duration)));
+    }
+
+    let entry_hints: HashMap<PathBuf, PlaylistEntry> = entries
+        .iter()
+        .filter(|entry| entry.display_name.is_some() || entry.duration_hint.is_some() || entry.source_type != SourceType::LocalFile)
+        .map(|entry| (entry.path.clone(), entry.clone()))
+        .collect();
+    let mut playlist: Vec<PathBuf> = entries.into_iter().map(|entry| entry.path).collect();
+
+    let mut group_tags = HashMap::new();
+    if let Some(group_by) = args.group_by {
+        // 分组模式：组内保持音轨顺序，随机模式下只打乱各组的顺序
+        group_tags = playlist.iter().map(|path| (path.clone(), read_group_tags(path, args.fix_encoding))).collect();
+        let mut rng = shuffle_seed.map(StdRng::seed_from_u64);
+        playlist = group_playlist(playlist, group_by, &group_tags, rng.as_mut());
+    } else {
+        if !args.random {
+            match args.sort {
+                SortMode::Name => {}
+                SortMode::Track => sort_by_track_number(&mut playlist),
+                SortMode::Album => sort_by_album(&mut playlist, args.fix_encoding),
+            }
+        }
+
+        if let Some(seed) = shuffle_seed {
+            let mut rng = StdRng::seed_from_u64(seed);
+            playlist.shuffle(&mut rng);
+            // 启动时还没有读取过标签，按目录打散（保证相同的种子得到相同的顺序）
+            if args.shuffle == ShuffleMode::Spread {
+                playlist = spread_playlist(playlist, None, |_| None, &mut rng);
+            }
+        }
+    }
+
+    // 只保留前 N 首（在排序、过滤、洗牌之后截断，随机模式下相当于随机抽取 N 首）
+    if let Some(max_tracks) = args.max_tracks {
+        playlist.truncate(max_tracks);
+    }
+    Ok(PreparedPlaylist { tracks: playlist, entry_hints, sources, group_tags })
+}
+
+// ===============================================
+// MAIN 函数
+// ===============================================
+
+// 正常结束（播完、按 Q 退出、收到停止信号）时退出码为 0；
+// 输入无效、音频设备无法打开、预加载通道关闭等异常退出时为 1，便于脚本判断
+// play_track 的结果：这首歌怎样结束，决定接下来播放哪首
+enum TrackAdvance {
+    Finished,    // 播完（包括 --preview 的试听时间到）
+    Skip(i32),   // 用户切歌，向后（正数）或向前（负数）移动的首数
+    Jump(usize), // 跳转到指定的歌曲（历史面板、控制命令）
+    Quit,        // 按 Q、收到停止信号或控制命令要求退出
+}
+
+// 播放期间不变的设置和通道（在 run 中创建一次，每首歌借给 play_track）
+struct PlaybackContext<'a> {
+    args: &'a Args,
+    sources: &'a HashMap<PathBuf, PathBuf>,
+    headless: bool,
+    is_simple_mode: bool,
+    is_random_enabled: bool,
+    taskbar_progress: bool,
+    loop_count: Option<u32>,
+    max_volume: Option<f32>,
+    volume_cap: f32,
+    status_line: u16,
+    early_end_threshold: f64,
+    title_template: &'a str,
+    theme: Option<Theme>,
+    save_playlist_path: &'a Path,
+    load_options: &'a LoadOptions,
+    output_options: &'a OutputOptions<'a>,
+    channel_mix: &'a ChannelMix,
+    stop_requested: &'a AtomicBool,
+    ipc_rx: &'a Receiver<RemoteCommand>,
+    tx: &'a Sender<PreloadMessage>,
+    duration_probe: Option<&'a Receiver<(PathBuf, Duration)>>,
+    playlist_watch: Option<&'a Receiver<Vec<PlaylistEntry>>>,
+}
+
+// 在歌曲之间保持、播放中可以改变的状态（由 run 持有，播放每首歌时借给 play_track）
+struct PlayerState<'a> {
+    stdout: &'a mut io::Stdout,
+    output_stream: &'a mut AudioStream,
+    sink: &'a mut Sink,
+    device_recovery: &'a mut DeviceRecovery,
+    playlist: &'a mut Vec<PathBuf>,
+    total_tracks: &'a mut usize,
+    current_track_index: &'a mut usize,
+    group_tags: &'a mut HashMap<PathBuf, GroupTags>,
+    preload_cache: &'a mut PreloadCache,
+    session_state: &'a mut SessionState,
+    session_stats: &'a mut SessionStats,
+    history: &'a mut PlayHistory,
+    retry_start: &'a mut Option<usize>,
+    known_track_info: &'a HashMap<PathBuf, (String, String, Duration)>,
+    playlist_durations: &'a mut Option<HashMap<PathBuf, Duration>>,
+    loop_mode: &'a mut LoopMode,
+    repeat_cycle: u32,
+    repeat_pass: &'a mut u32,
+    playback_speed: &'a mut f32,
+    muted_volume: &'a mut Option<f32>,
+    base_volume: &'a mut f32,
+    track_gain: &'a mut TrackGain,
+    volume_memory: &'a mut Option<VolumeMemory>,
+    skip_accelerator: &'a mut SkipAccelerator,
+    osd: &'a mut Osd,
+    show_history: &'a mut bool,
+    show_help: &'a mut bool,
+    help_page: &'a mut usize,
+    history_selected: &'a mut usize,
+    remove_current: &'a mut bool,
+    window_title_shown: &'a mut String,
+}
+
+// 正在播放的歌曲
+struct CurrentTrack<'a> {
+    title: &'a str,
+    artist: &'a str,
+    album: &'a str,
+    path_str: &'a str,
+    disc_number: Option<u32>,
+    track_number: Option<u32>,
+    total_duration: Duration,
+    preview_end: Option<Duration>, // --preview 的试听在这个位置结束
+}
+
+// 播放一首歌：刷新状态栏和窗口标题，处理按键、控制命令、播放列表文件的变化和输出设备的失效，
+// 直到歌曲播完、被切走或要求退出。clock 从歌曲的起始位置开始计时，返回后由调用方统计收听时长
+fn play_track(
+    context: &PlaybackContext,
+    player: PlayerState,
+    track: &CurrentTrack,
+    clock: &mut PlaybackClock,
+    mut awaiting_start: bool,
+) -> Result<TrackAdvance, Box<dyn std::error::Error>> {
+    let PlaybackContext {
+        args, sources, headless, is_simple_mode, is_random_enabled, taskbar_progress, loop_count, max_volume, volume_cap, status_line,
+        early_end_threshold, title_template, theme, save_playlist_path, load_options, output_options, channel_mix, stop_requested,
+        ipc_rx, tx, duration_probe, playlist_watch,
+    } = *context;
+    let PlayerState {
+        stdout, output_stream, sink, device_recovery, playlist, total_tracks, current_track_index, group_tags, preload_cache,
+        session_state, session_stats, history, retry_start, known_track_info, playlist_durations, loop_mode, repeat_cycle,
+        repeat_pass, playback_speed, muted_volume, base_volume, track_gain, volume_memory, skip_accelerator, osd, show_history,
+        show_help, help_page, history_selected, remove_current, window_title_shown,
+    } = player;
+    let CurrentTrack { title, artist, album, path_str: track_path_str, disc_number, track_number, total_duration, preview_end } = *track;
+    let mut last_progress_update = Instant::now();
+    let mut last_status_line = String::new(); // 上次输出的状态栏，内容相同时不重写
+    let mut toggle_debouncer = Debouncer::new(MIN_TOGGLE_INTERVAL); // 按键防抖
+    let mut output_recovered = false; // 本首歌是否已因输出设备失效尝试过重建输出设备
+    let mut seek_error: Option<StatusMessage> = None; // 定位失败的提示，短暂代替播放时间显示
+    let mut last_seek_time: Option<Instant> = None; // 最近一次定位成功的时刻，之后短暂显示精确的播放时间
+
+    loop {
+        if sink.empty() {
+            // 远早于预期变空，并且输出流报告过错误或默认设备已经变化：输出设备失效，
+            // 每首歌尝试一次重建，从中断处继续播放。其他情况（例如文件被截断）按提前结束处理
+            let played = clock.position(Instant::now());
+            let ended_early = !total_duration.is_zero() && played.as_secs_f64() < total_duration.as_secs_f64() * early_end_threshold;
+            if ended_early && !output_recovered && device_recovery.output_failed(args.output_device.is_none()) {
+                output_recovered = true;
+                if let Some((stream, new_sink)) = recover_audio_output(stdout, device_recovery, sink, &playlist[*current_track_index], played, output_options, headless)? {
+                    *output_stream = stream;
+                    *sink = new_sink;
+                    last_status_line.clear();
+                    continue;
+                }
+            }
+            return Ok(TrackAdvance::Finished);
+        }
+        // ... (时间计算)
+
+        // 暂停和继续由 set_paused 在按键处理时记录；其他途径改变了暂停状态（例如重建输出设备）时在这里补记
+        let now = Instant::now();
+        clock.set_paused(sink.is_paused(), now);
+        if let (Some(durations), Some(probe)) = (playlist_durations.as_mut(), duration_probe) {
+            durations.extend(probe.try_iter());
+        }
+        // --watch-playlist：播放列表文件新增的歌曲追加到末尾，已有的歌曲不受影响
+        if let Some(watch) = playlist_watch {
+            let added: Vec<PlaylistEntry> = watch.try_iter().flatten().collect();
+            if !added.is_empty() {
+                osd.show_for(format!("[+{} 首新歌]", added.len()), WATCH_OSD_DURATION);
+                last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                preload_cache.add_entries(&added);
+                // 新增的歌曲也要在下一轮参与分组
+                if args.group_by.is_some() {
+                    group_tags.extend(added.iter().map(|entry| (entry.path.clone(), read_group_tags(&entry.path, args.fix_encoding))));
+                }
+                playlist.extend(added.into_iter().map(|entry| entry.path));
+                *total_tracks = playlist.len();
+                session_state.playlist_hash = state::playlist_hash(playlist);
+                // 当前是最后一首时，之前没有可以预加载的下一首
+                preload_cache.request(playlist, *current_track_index + 1, tx);
+            }
+        }
+        // 状态栏显示歌曲内的位置（媒体时间），倍速播放时与实际经过的时间不同；暂停期间停在暂停的那一刻
+        let current_time = clock.position(now);
+        session_state.seek_position_secs = current_time.as_secs();
+        // 试听时间到：停止后 sink 变空，下一轮按播放完毕处理
+        if preview_end.is_some_and(|end| current_time >= end) {
+            sink.stop();
+        }
+
+        // 窗口标题：按模板生成，暂停、静音和音量变化后自动更新
+        if !headless {
+            let title_info = TitleInfo {
+                title,
+                artist,
+                album,
+                index: *current_track_index,
+                total: *total_tracks,
+                volume: if muted_volume.is_some() { 0.0 } else { sink.volume() },
+                state: playback_state(sink, *muted_volume),
+                position: current_time,
+                duration: total_duration,
+            };
+            update_window_title(stdout, window_title_shown, window_title(title_template, &title_info))?;
+        }
+
+        // 默认输出设备变化时，切换到新设备并从当前位置继续播放
+        // （指定了 --output-device 时固定使用该设备，不跟随默认设备）
+        if args.output_device.is_none() && device_recovery.device_changed() {
+            if let Some((stream, new_sink)) = recover_audio_output(stdout, device_recovery, sink, &playlist[*current_track_index], current_time, output_options, headless)? {
+                *output_stream = stream;
+                *sink = new_sink;
+            }
+            last_status_line.clear();
+        }
+
+        // 处理控制套接字转发来的命令（无终端模式下同样有效）
+        while let Ok(command) = ipc_rx.try_recv() {
+            match command {
+                RemoteCommand::Next => {
+                    if *current_track_index < total_tracks.saturating_sub(1) || *loop_mode != LoopMode::Off {
+                        sink.stop(); return Ok(TrackAdvance::Skip(1));
+                    }
+                }
+                RemoteCommand::Prev => {
+                    if *current_track_index > 0 || *loop_mode != LoopMode::Off {
+                        sink.stop(); return Ok(TrackAdvance::Skip(-1));
+                    }
+                }
+                RemoteCommand::Pause | RemoteCommand::Play | RemoteCommand::Toggle => {
+                    let pause = match command {
+                        RemoteCommand::Pause => true,
+                        RemoteCommand::Play => false,
+                        _ => !sink.is_paused(),
+                    };
+                    set_paused(sink, pause, clock);
+                    if pause {
+                        osd.show("[暂停]");
+                    } else {
+                        awaiting_start = false;
+                        osd.show("[播放]");
+                    }
+                }
+                RemoteCommand::Volume(volume) => {
+                    // 设置音量会取消静音，且不超过音量上限
+                    *muted_volume = None;
+                    sink.set_volume((volume as f32 / 100.0).min(volume_cap));
+                    rescale_base_volume(base_volume, track_gain, sink.volume());
+                    osd.show(volume_message(sink.volume()));
+                }
+                RemoteCommand::Quit => {
+                    return Ok(TrackAdvance::Quit);
+                }
+                RemoteCommand::Status(reply) => {
+                    let status = PlayerStatus {
+                        index: *current_track_index,
+                        total_tracks: *total_tracks,
+                        title,
+                        artist,
+                        path: track_path_str,
+                        position: current_time,
+                        duration: total_duration,
+                        volume: muted_volume.unwrap_or_else(|| sink.volume()),
+                        paused: sink.is_paused(),
+                        muted: muted_volume.is_some(),
+                    };
+                    let _ = reply.send(status.to_json());
+                }
+                RemoteCommand::Playlist(reply) => {
+                    let _ = reply.send(playlist_json(playlist));
+                }
+                RemoteCommand::Goto(index) => {
+                    if index < *total_tracks {
+                        sink.stop();
+                        return Ok(TrackAdvance::Jump(index));
+                    }
+                }
+            }
+            last_progress_update = Instant::now() - UPDATE_INTERVAL;
+        }
+
+        // 收到停止信号：与按 Q 退出相同，恢复终端并保存播放状态
+        if stop_requested.load(Ordering::SeqCst) {
+            return Ok(TrackAdvance::Quit);
+        }
+
+        // 无终端模式：不刷新状态栏也不读取按键，只响应停止信号
+        if headless {
+            thread::sleep(Duration::from_millis(100));
+            continue;
+        }
+
+        // 纯净模式下的帮助：状态栏轮换显示按键说明
+        if *show_help && is_simple_mode && last_progress_update.elapsed() >= UPDATE_INTERVAL {
+            let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+            let pages = layout_key_bindings(KEY_BINDINGS, true, terminal_width.saturating_sub(1));
+            let hint = pages.get(*help_page % pages.len().max(1)).map_or("", String::as_str);
+            flush_progress_line(stdout, &pad_to_width(&format!(" {}", hint), terminal_width))?;
+            // 状态栏被帮助覆盖，关闭帮助后需要重写
+            last_status_line.clear();
+            *help_page += 1;
+            last_progress_update = Instant::now();
+        }
+
+        // 临时提示过期后立即恢复显示歌名，不等到下一次定时刷新
+        if osd.expire() {
+            last_progress_update = Instant::now() - UPDATE_INTERVAL;
+        }
+        if seek_error.as_ref().is_some_and(|message| !message.is_active()) {
+            seek_error = None;
+            last_progress_update = Instant::now() - UPDATE_INTERVAL;
+        }
+        if last_seek_time.is_some_and(|time| time.elapsed() >= PRECISE_TIME_DURATION) {
+            last_seek_time = None;
+            last_progress_update = Instant::now() - UPDATE_INTERVAL;
+        }
+
+        // 刷新显示 (与原代码一致)，等待开始时保留提示
+        if !(awaiting_start || *show_help && is_simple_mode) && last_progress_update.elapsed() >= UPDATE_INTERVAL {
+            let display_state = DisplayState {
+                current_index: *current_track_index,
+                total_tracks: *total_tracks,
+                is_random: is_random_enabled,
+                loop_mode: *loop_mode,
+                cycle: loop_count.filter(|&count| count > 1).map(|count| (repeat_cycle, count)),
+                repeat: match *loop_mode {
+                    LoopMode::Track => Some((*repeat_pass, None)),
+                    _ => (args.repeat_each > 1).then_some((*repeat_pass, Some(args.repeat_each))),
+                },
+                title,
+                artist,
+                track_path: track_path_str,
+                disc_number,
+                track_number,
+                current_time,
+                precise_time: last_seek_time.is_some(),
+                total_duration,
+                volume: muted_volume.unwrap_or_else(|| sink.volume()),
+                muted: muted_volume.is_some(),
+                paused: sink.is_paused(),
+                max_volume,
+                speed: *playback_speed,
+                terminal_width: terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize,
+                osd_message: osd.message(),
+                progress_message: seek_error.as_ref().filter(|message| message.is_active()).map(|message| message.text.as_str()),
+                theme,
+                playlist_progress: playlist_durations.as_ref().and_then(|durations| {
+                    let listened = session_stats.listened + clock.listened(now);
+                    playlist_progress(playlist, *current_track_index, current_time, listened, durations, Duration::from_secs(args.assumed_duration))
+                }),
+            };
+            // 内容没有变化（例如暂停中）时不重写同一行，避免光标闪烁
+            let status_line_text = render_progress_line(&display_state);
+            if status_line_text != last_status_line {
+                flush_progress_line(stdout, &status_line_text)?;
+                last_status_line = status_line_text;
+            }
+            if taskbar_progress {
+                emit_taskbar_progress(stdout, TaskbarProgress::at(current_time, total_duration, sink.is_paused()))?;
+            }
+            last_progress_update = Instant::now();
+        }
+
+        // 快速浏览中松开切歌键：切到浏览到的歌曲
+        if let Some(offset) = skip_accelerator.release(Instant::now())
+            && offset != 0
+        {
+            sink.stop(); return Ok(TrackAdvance::Skip(offset));
+        }
+
+        // --- 用户输入处理 (非阻塞) ---
+        if event::poll(Duration::from_millis(100))? {
+            let input_event = event::read()?;
+            // 误粘贴的内容整体丢弃
+            if let Event::Paste(_) = input_event {
+                debug!("丢弃粘贴的内容");
+                continue;
+            }
+            // 鼠标操作按对应的按键处理，其他鼠标事件（移动、拖动等）忽略
+            let input_event = match input_event {
+                Event::Mouse(mouse_event) => match mouse_key(mouse_event) {
+                    Some(key_event) => Event::Key(key_event),
+                    None => continue,
+                },
+                other => other,
+            };
+            if let Event::Key(key_event) = input_event {
+                debug!(key = ?key_event.code, "按键");
+                // 帮助面板打开时按其他按键：先关闭面板恢复说明区域，按键照常处理
+                if *show_help && !is_simple_mode && !matches!(key_event.code, KeyCode::Char('?') | KeyCode::Esc) {
+                    *show_help = false;
+                    draw_control_guide(stdout)?;
+                    execute!(stdout, cursor::MoveTo(0, status_line))?;
+                }
+                match key_event.code {
+                    // P键：静音/取消静音
+                    KeyCode::Char('p') | KeyCode::Char('P') => {
+                        if !toggle_debouncer.trigger() { continue; }
+                        if let Some(vol) = *muted_volume {
+                            // 取消静音（静音前的音量高于上限时只恢复到上限）
+                            sink.set_volume(vol.min(volume_cap));
+                            *muted_volume = None;
+                            osd.show(volume_message(sink.volume()));
+                        } else {
+                            // 静音
+                            *muted_volume = Some(sink.volume());
+                            sink.set_volume(0.0);
+                            osd.show("[静音]");
+                        }
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // 空格键：暂停/播放
+                    KeyCode::Char(' ') => {
+                        if !toggle_debouncer.trigger() { continue; }
+                        if sink.is_paused() {
+                            set_paused(sink, false, clock);
+                            awaiting_start = false;
+                            osd.show("[播放]");
+                        } else {
+                            set_paused(sink, true, clock);
+                            osd.show("[暂停]");
+                        }
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // R键：切换播放模式（顺序 → 列表循环 → 单曲循环），从当前这一遍重新计数
+                    KeyCode::Char('r') | KeyCode::Char('R') => {
+                        if !toggle_debouncer.trigger() { continue; }
+                        *loop_mode = loop_mode.next();
+                        *repeat_pass = 1;
+                        osd.show(loop_mode.message());
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // W键：保存当前播放列表
+                    KeyCode::Char('w') | KeyCode::Char('W') => {
+                        let confirmed = !save_playlist_path.exists() || confirm_overwrite(stdout, save_playlist_path)?;
+                        let message = if !confirmed {
+                            "已取消保存播放列表".to_string()
+                        } else {
+                            match save_playlist(save_playlist_path, playlist, known_track_info) {
+                                Ok(()) => format!("播放列表已保存到 {}", save_playlist_path.display()),
+                                Err(e) => format!("保存播放列表失败: {}", e),
+                            }
+                        };
+                        display_warning_and_wait(stdout, &message, headless, false)?;
+                        last_status_line.clear();
+                    }
+                    // D键：从播放列表中移除当前歌曲（默认立即跳过，--remove-mode finish 时播完再移除；仅非纯净模式）
+                    KeyCode::Char('d') | KeyCode::Char('D') if !*remove_current && !is_simple_mode => {
+                        if !toggle_debouncer.trigger() { continue; }
+                        *remove_current = true;
+                        if args.remove_mode == RemoveMode::Skip {
+                            sink.stop(); return Ok(TrackAdvance::Skip(1));
+                        }
+                        osd.show("播完后从播放列表中移除");
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // U键：撤销最近一次移除（仅非纯净模式）
+                    KeyCode::Char('u') | KeyCode::Char('U') if !is_simple_mode => {
+                        if !toggle_debouncer.trigger() { continue; }
+                        if *remove_current {
+                            *remove_current = false;
+                            osd.show("已取消移除");
+                        } else if let Some((index, path)) = session_stats.removed_tracks.pop() {
+                            let index = index.min(playlist.len());
+                            playlist.insert(index, path.clone());
+                            history.on_inserted(index);
+                            if let Some(start) = retry_start.as_mut()
+                                && *start >= index
+                            {
+                                *start += 1;
+                            }
+                            if index <= *current_track_index {
+                                *current_track_index += 1;
+                                session_state.track_index = *current_track_index;
+                            }
+                            *total_tracks = playlist.len();
+                            session_state.playlist_hash = state::playlist_hash(playlist);
+                            // 索引已变化，之前的预加载结果作废
+                            preload_cache.invalidate();
+                            preload_cache.request(playlist, (*current_track_index + 1) % *total_tracks, tx);
+                            if let Some(file) = excluded_file_of(args, sources, &path)
+                                && let Err(e) = excluded::remove_excluded(&file, &path)
+                            {
+                                warn!(file = %file.display(), error = %e, "无法更新排除列表");
+                            }
+                            let filename = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                            osd.show(format!("已恢复: {}", filename));
+                        } else {
+                            osd.show("没有可以撤销的移除");
+                        }
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // O键：在文件管理器中显示当前歌曲（失败时在状态栏提示，不输出到标准错误以免打乱界面；仅非纯净模式）
+                    KeyCode::Char('o') | KeyCode::Char('O') if !is_simple_mode => {
+                        if !toggle_debouncer.trigger() { continue; }
+                        match reveal_in_file_manager(&playlist[*current_track_index]) {
+                            Ok(()) => osd.show("已打开所在文件夹"),
+                            Err(e) => {
+                                warn!(path = %track_path_str, error = %e, "无法打开文件管理器");
+                                osd.show(format!("无法打开文件管理器: {}", e));
+                            }
+                        }
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // H键：显示/隐藏播放历史（仅非纯净模式）
+                    KeyCode::Char('h') | KeyCode::Char('H') if !is_simple_mode => {
+                        if !toggle_debouncer.trigger() { continue; }
+                        *show_history = !*show_history;
+                        *show_help = false;
+                        if *show_history {
+                            *history_selected = 0;
+                            draw_history_overlay(stdout, history, *history_selected, status_line)?;
+                        } else {
+                            draw_control_guide(stdout)?;
+                            execute!(stdout, cursor::MoveTo(0, status_line))?;
+                        }
+                    }
+                    // ?键：显示/隐藏按键帮助，Esc 也可关闭
+                    KeyCode::Char('?') | KeyCode::Esc if *show_help || key_event.code == KeyCode::Char('?') => {
+                        *show_help = !*show_help;
+                        *show_history = false;
+                        if is_simple_mode {
+                            *help_page = 0;
+                            last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                        } else if *show_help {
+                            draw_help_overlay(stdout, status_line)?;
+                        } else {
+                            draw_control_guide(stdout)?;
+                            execute!(stdout, cursor::MoveTo(0, status_line))?;
+                        }
+                    }
+                    // 历史面板打开时，上下键用于选择条目
+                    KeyCode::Up if *show_history => {
+                        *history_selected = history_selected.saturating_sub(1);
+                        draw_history_overlay(stdout, history, *history_selected, status_line)?;
+                    }
+                    KeyCode::Down if *show_history => {
+                        if *history_selected + 1 < history.entries.len() {
+                            *history_selected += 1;
+                        }
+                        draw_history_overlay(stdout, history, *history_selected, status_line)?;
+                    }
+                    // 回车：跳转到历史面板中高亮的歌曲
+                    KeyCode::Enter if *show_history => {
+                        if let Some(entry) = history.newest(*history_selected) {
+                            sink.stop();
+                            return Ok(TrackAdvance::Jump(entry.index));
+                        }
+                    }
+                    // 音量控制
+                    KeyCode::Up => {
+                        // 调整音量时，如果处于静音状态，应先取消静音，恢复音量并增加
+                        if let Some(vol) = muted_volume.take() {
+                            // 先恢复到静音前的音量
+                            sink.set_volume(vol.min(volume_cap));
+                        }
+                        let old_volume = sink.volume();
+                        adjust_volume(sink, volume_step(key_event.modifiers, is_simple_mode), volume_cap);
+                        apply_volume_change(volume_memory, &playlist[*current_track_index], track_gain, base_volume, old_volume, sink.volume());
+                        osd.show(volume_message(sink.volume()));
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    },
+                    KeyCode::Down => {
+                        // 调整音量时，如果处于静音状态，应先取消静音，恢复音量并减小
+                        if let Some(vol) = muted_volume.take() {
+                            // 先恢复到静音前的音量
+                            sink.set_volume(vol.min(volume_cap));
+                        }
+                        let old_volume = sink.volume();
+                        adjust_volume(sink, -volume_step(key_event.modifiers, is_simple_mode), volume_cap);
+                        apply_volume_change(volume_memory, &playlist[*current_track_index], track_gain, base_volume, old_volume, sink.volume());
+                        osd.show(volume_message(sink.volume()));
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    },
+                    // 0键：恢复基准音量并忘记这首歌的记忆音量（仅非纯净模式）
+                    KeyCode::Char('0') if !is_simple_mode => {
+                        if let Some(vol) = muted_volume.take() {
+                            sink.set_volume(vol.min(volume_cap));
+                        }
+                        track_gain.memory = 1.0;
+                        sink.set_volume(compose_volume(*base_volume, track_gain, false, volume_cap));
+                        if let Some(memory) = volume_memory.as_mut() {
+                            memory.forget(&playlist[*current_track_index]);
+                        }
+                        osd.show(format!("已恢复基准{}", volume_message(sink.volume())));
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // 等待开始时，→ 开始播放当前歌曲
+                    KeyCode::Right if awaiting_start => {
+                        awaiting_start = false;
+                        set_paused(sink, false, clock);
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // 倍速：+（或 =，不用按 Shift）加快，- 减慢，切歌后保持
+                    KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char('-') => {
+                        let step = if key_event.code == KeyCode::Char('-') { -SPEED_STEP } else { SPEED_STEP };
+                        let new_speed = (*playback_speed + step).clamp(MIN_SPEED, MAX_SPEED);
+                        if new_speed != *playback_speed {
+                            clock.set_speed(new_speed, Instant::now());
+                            *playback_speed = new_speed;
+                            sink.set_speed(*playback_speed);
+                            osd.show(format!("倍速 {}x", format_speed(*playback_speed)));
+                            last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                        }
+                    }
+                    // 快退/快进：[ 后退、] 前进 10 秒，不越过结尾
+                    KeyCode::Char('[') | KeyCode::Char(']') => {
+                        let target = if key_event.code == KeyCode::Char('[') {
+                            current_time.saturating_sub(SEEK_STEP)
+                        } else if total_duration.is_zero() {
+                            current_time + SEEK_STEP
+                        } else {
+                            (current_time + SEEK_STEP).min(total_duration.saturating_sub(Duration::from_secs(1)))
+                        };
+                        match seek_to(sink, &playlist[*current_track_index], target, load_options, channel_mix) {
+                            Ok(()) => {
+                                clock.seek(target, Instant::now());
+                                last_seek_time = Some(Instant::now());
+                            }
+                            Err(e) => {
+                                warn!(path = %track_path_str, position = ?target, error = %e, "定位失败");
+                                seek_error = Some(StatusMessage::new("Seek N/A", SEEK_ERROR_DURATION));
+                            }
+                        }
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // 左右平衡：<（或 ,，不用按 Shift）偏左，> （或 .）偏右，切歌后保持（仅非纯净模式）
+                    KeyCode::Char('<') | KeyCode::Char(',') | KeyCode::Char('>') | KeyCode::Char('.') if !is_simple_mode => {
+                        let step = if matches!(key_event.code, KeyCode::Char('<') | KeyCode::Char(',')) { -BALANCE_STEP } else { BALANCE_STEP };
+                        let balance = channel_mix.set_balance(channel_mix.balance() + step);
+                        osd.show(balance_message(balance));
+                        last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                    }
+                    // 切歌：→ 下一首，← 上一首，按住 Ctrl 时一次跳过 SKIP_JUMP 首（仅非纯净模式）。
+                    // 按住不放时进入快速浏览，状态栏显示目标歌曲，松开后才切歌（见 SkipAccelerator）
+                    KeyCode::Right | KeyCode::Left => {
+                        let step = if key_event.code == KeyCode::Right { 1 } else { -1 };
+                        let step = if !is_simple_mode && key_event.modifiers.contains(KeyModifiers::CONTROL) { step * SKIP_JUMP } else { step };
+                        match skip_accelerator.press(step, Instant::now()) {
+                            SkipAction::Ignore => {}
+                            SkipAction::Skip(offset) => {
+                                let movable = if offset > 0 { *current_track_index < total_tracks.saturating_sub(1) } else { *current_track_index > 0 };
+                                if movable || *loop_mode != LoopMode::Off {
+                                    sink.stop(); return Ok(TrackAdvance::Skip(offset)); }
+                            }
+                            SkipAction::Scrub(offset) => {
+                                let target = skip_target(*current_track_index, offset, *total_tracks, *loop_mode != LoopMode::Off);
+                                let filename = playlist[target].file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                                osd.show(format!("跳到 {}/{} {}", target + 1, *total_tracks, filename));
+                                last_progress_update = Instant::now() - UPDATE_INTERVAL;
+                            }
+                        }
+                    }
+                    // 退出 (Q/q 或 Ctrl+C)
+                    KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('c') => {
+                        return Ok(TrackAdvance::Quit);
+                    }
+                    _ => {}
+                }
+            }
+        }
     }
 }
 
-// 显示错误信息并等待
-fn display_error_and_wait(
-    stdout: &mut io::Stdout,
-    current_index: usize,
-    total_tracks: usize,
-    err_type: &str,
-    filename: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
-    let track_info = format!("[{}/{}]", current_index + 1, total_tracks);
-    let error_msg_truncated = truncate_string(filename, 30);
-    eprint!("{} [错误:{}]: {} -> 跳过...", track_info, err_type, error_msg_truncated);
-    thread::sleep(ERROR_WAIT_DURATION);
-    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
-    Ok(())
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("[错误]{}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-// 更新进度显示
-fn update_progress_display(
-    stdout: &mut io::Stdout,
-    current_index: usize,
-    total_tracks: usize,
-    is_random: bool,
-    is_loop: bool,
-    title: &str,
-    artist: &str,
-    track_path: &str,
-    current_time: Duration,
-    total_duration: Duration,
-    volume: f32,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let current_time_str = format_duration(current_time);
-    let total_duration_str = format_duration(total_duration);
-    let track_count_str = format!("[{}/{}]", current_index + 1, total_tracks);
-    let ext = track_path.split('.').last().unwrap_or("未知").to_uppercase();
-    let random_str = if is_random { "随" } else { "顺" };
-    let loop_str = if is_loop { "循" } else { "单" };
-    let play_mode_str = format!("{}|{}", random_str, loop_str);
-
-    let mut display_text_unpadded = format!(
-        "{}[{}][{}][][{}/{}][{:.0}%]",
-        track_count_str, play_mode_str, ext, current_time_str, total_duration_str, volume * 100.0
-    );
-
-    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
-    let current_unpadded_width = display_text_unpadded.as_str().width();
-    let music_info_width = terminal_width.saturating_sub(current_unpadded_width);
-    let music_info_content = format!("{}-{}", title, artist);
-    let music_info = if music_info_width < 15 {
-        truncate_string(title, music_info_width)
-    } else {
-        truncate_string(&music_info_content, music_info_width)
+fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let args = match cli.command {
+        Some(Command::Play(args)) => args,
+        Some(Command::Info { file, json, fix_encoding, binary_sizes }) => {
+            if !info::print_track_info(&file, json, fix_encoding, binary_sizes) {
+                return Ok(ExitCode::FAILURE);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::List { args, json, csv }) => {
+            // 没有指定输入时列出当前目录
+            let inputs = if args.files.is_empty() { vec![PathBuf::from(".")] } else { args.files.clone() };
+            match prepare_playlist(&args, &inputs, args.random.then(rand::random)) {
+                Ok(mut prepared) => {
+                    // 播放时在预加载时才按时长跳过，list 预先探测全部时长，列出的就是实际会播放的歌曲
+                    if args.min_duration.is_some() || args.max_duration.is_some() {
+                        prepared.tracks = filter_by_duration(prepared.tracks, args.min_duration, args.max_duration);
+                    }
+                    match csv {
+                        Some(csv_path) => {
+                            if !list::write_report(&prepared.tracks, &csv_path, args.fix_encoding) {
+                                return Ok(ExitCode::FAILURE);
+                            }
+                        }
+                        // 多个输入时标出每首歌来自哪个输入
+                        None => list::print_playlist(&prepared.tracks, (inputs.len() > 1).then_some(&prepared.sources), json),
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Render { args, output, rate, gap }) => {
+            let inputs = if args.files.is_empty() { vec![PathBuf::from(".")] } else { args.files.clone() };
+            let mut prepared = match prepare_playlist(&args, &inputs, args.random.then(rand::random)) {
+                Ok(prepared) => prepared,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            // 与 list 相同，预先探测时长按 --min-duration/--max-duration 过滤
+            if args.min_duration.is_some() || args.max_duration.is_some() {
+                prepared.tracks = filter_by_duration(prepared.tracks, args.min_duration, args.max_duration);
+            }
+            // 网络流没有尽头，不能渲染
+            prepared.tracks.retain(|path| stream_url(path).is_none());
+            if prepared.tracks.is_empty() {
+                eprintln!("[错误]在指定的路径中未找到支持的音频文件。");
+                return Ok(ExitCode::FAILURE);
+            }
+            return match render_playlist(&prepared.tracks, &output, rate, gap) {
+                Ok(()) => Ok(ExitCode::SUCCESS),
+                Err(e) => {
+                    eprintln!("[错误]渲染失败: {}", e);
+                    Ok(ExitCode::FAILURE)
+                }
+            };
+        }
+        Some(Command::Completions { shell }) => {
+            cli::print_completions(shell, &mut io::stdout());
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => cli.play,
     };
 
-    display_text_unpadded = format!(
-        "{}[{}][{}][{}][{}/{}][{:.0}%]",
-        track_count_str, play_mode_str, ext, music_info, current_time_str, total_duration_str, volume * 100.0
-    );
-
-    let new_len = display_text_unpadded.as_str().width();
-    let padding_needed = terminal_width.saturating_sub(new_len);
-    let padding = " ".repeat(padding_needed);
-    let display_text = format!("{}{}", display_text_unpadded, padding);
-
-    execute!(stdout, cursor::MoveToColumn(0))?;
-    print!("{}", display_text);
-    stdout.flush()?;
-    Ok(())
-}
-
-// 调整音量
-fn adjust_volume(sink: &Sink, delta: f32) {
-    let current_volume = sink.volume();
-    let new_volume = (current_volume + delta).clamp(0.0, 1.0);
-    sink.set_volume(new_volume);
-}
-
-
-// ===============================================
-// MAIN 函数
-// ===============================================
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    // 如果没有提供文件参数，显示帮助信息（只导出元数据时使用当前目录）
+    let export_only = args.files.is_empty() && args.export_metadata.is_some();
+    if args.files.is_empty() && !export_only {
+        Cli::parse_from(["mddplayer", "--help"]);
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    // 如果没有提供文件参数，显示帮助信息
-    let input_path_str = match &args.file {
-        Some(path) => path,
-        None => {
-            Args::parse_from(&["mddplayer", "--help"]);
-            return Ok(());
+    // 日志：guard 需保留到 main 结束，退出时把后台线程中尚未写出的日志刷到文件
+    let _log_guard = match init_logging(args.log.as_deref(), args.log_level, args.verbose) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("[警告]无法初始化日志: {}", e);
+            None
         }
     };
 
+    // 退出时需要删除的文件。PID 文件在扫描播放列表之前写入，脚本在启动扫描期间也能检测到播放器
+    let mut cleanup_files = CleanupFiles::default();
+    if let Some(pid_path) = &args.pid_file {
+        match std::fs::write(pid_path, std::process::id().to_string()) {
+            Ok(()) => cleanup_files.push(pid_path.clone()),
+            Err(e) => eprintln!("[警告]无法写入 PID 文件 {}: {}", pid_path.display(), e),
+        }
+    }
+
     let is_simple_mode = args.clean;
+    let manual_advance = args.manual_advance;
     let is_random_enabled = args.random;
-    let is_loop_enabled = args.is_loop;
-    let initial_volume = args.volume as f32 / 100.0;
+    // 指定循环次数时视为开启循环，循环 1 次等同于不循环
+    let loop_count = args.loop_count;
+    let is_loop_enabled = match loop_count {
+        Some(count) => count > 1,
+        None => args.is_loop,
+    };
+    // 播放模式（顺序、列表循环、单曲循环），播放中按 R 切换
+    let mut loop_mode = if is_loop_enabled { LoopMode::List } else { LoopMode::Off };
+    // 音量上限：同时限制初始音量、音量增加和取消静音后恢复的音量
+    let max_volume = args.max_volume.map(|cap| cap as f32 / 100.0);
+    let volume_cap = max_volume.unwrap_or(1.0);
+    let mut initial_volume = args.volume as f32 / 100.0;
+    if initial_volume > volume_cap {
+        eprintln!("[警告]初始音量 {}% 超过音量上限 {:.0}%，已调整为上限。", args.volume, volume_cap * 100.0);
+        initial_volume = volume_cap;
+    }
 
-    let mut playlist = match get_playlist_from_input(input_path_str) {
-        Ok(p) => p,
-        Err(_e) => {
-            eprintln!("[错误]处理输入路径 '{}' 时失败", input_path_str);
-            return Ok(());
+    if args.announce == Announce::Tts && !cfg!(feature = "tts") {
+        eprintln!("[警告]编译时未启用 tts 功能（cargo build --features tts），--announce tts 改为在两首歌之间播放提示音。");
+    }
+
+    // --resume：读取上次退出时保存的状态，随机模式下沿用上次的洗牌种子以得到相同的顺序
+    let resume_state = if args.resume {
+        match SessionState::load() {
+            Ok(state) => Some(state),
+            Err(e) => {
+                eprintln!("[警告]无法读取上次的播放状态: {}，从头开始播放。", e);
+                None
+            }
         }
+    } else {
+        None
     };
+    let shuffle_seed = args.random.then(|| resume_state.as_ref().and_then(|state| state.shuffle_seed).unwrap_or_else(rand::random));
 
+    let inputs = if export_only { vec![PathBuf::from(".")] } else { args.files.clone() };
+    // 目录中有播放列表文件时选择要播放的（--pick，或目录中只有播放列表时）
+    let scan_options = ScanOptions { include_hidden: args.include_hidden, ignore_nomedia: args.ignore_nomedia, verbose: args.verbose };
+    let interactive = !args.headless && io::stdout().is_terminal() && terminal_input_available();
+    let inputs = match picker::pick_playlists(&inputs, args.pick, interactive, scan_options) {
+        Ok(Some(inputs)) => inputs,
+        Ok(None) => {
+            eprintln!("已取消播放。");
+            return Ok(ExitCode::SUCCESS);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let PreparedPlaylist { tracks: mut playlist, entry_hints, sources, mut group_tags } = match prepare_playlist(&args, &inputs, shuffle_seed) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    // 播放前去掉不存在的文件，--strict 时检查全部条目，有问题则退出
+    if args.strict {
+        if !validate::check_playlist(&playlist) {
+            return Ok(ExitCode::FAILURE);
+        }
+    } else {
+        let missing = validate::drop_missing(&mut playlist);
+        if missing > 0 {
+            eprintln!("[警告]已跳过 {} 个不存在的条目（加 --strict 在播放前检查全部条目）。", missing);
+        }
+    }
     if playlist.is_empty() {
         eprintln!("[错误]在指定的路径中未找到支持的音频文件。");
-        return Ok(());
+        return Ok(ExitCode::FAILURE);
+    }
+
+    // 转码模式：不初始化终端和音频设备，直接输出到标准输出
+    if let Some(format) = args.output_format {
+        if io::stdout().is_terminal() {
+            eprintln!("[错误]转码输出是二进制数据，请重定向到文件或管道，例如: mddplayer song.mp3 --output-format flac > song.flac");
+            return Ok(ExitCode::FAILURE);
+        }
+        return transcode_playlist(&playlist, format).map(|()| ExitCode::SUCCESS);
+    }
+
+    // 指定了 --save-playlist 时，先把整理好的播放列表保存下来（非交互，直接覆盖）
+    if let Some(save_path) = &args.save_playlist {
+        match save_playlist(save_path, &playlist, &HashMap::new()) {
+            Ok(()) => eprintln!("播放列表已保存到 {}", save_path.display()),
+            Err(e) => eprintln!("[错误]保存播放列表失败: {}", e),
+        }
     }
 
-    if is_random_enabled {
-        let mut rng = rand::thread_rng();
-        playlist.shuffle(&mut rng);
+    // --export-metadata：播放前导出元数据，没有指定要播放的文件时导出后退出
+    if let Some(export_path) = &args.export_metadata {
+        let exported = match export::export_metadata(&playlist, export_path, args.fix_encoding) {
+            Ok(count) => {
+                eprintln!("已导出 {} 首的元数据到 {}", count, export_path.display());
+                true
+            }
+            Err(e) => {
+                eprintln!("[错误]导出元数据失败: {}", e);
+                false
+            }
+        };
+        // 只导出时以导出结果作为退出状态；同时播放时导出失败不影响播放
+        if export_only {
+            return Ok(if exported { ExitCode::SUCCESS } else { ExitCode::FAILURE });
+        }
     }
 
+    // 播放列表（文件集合）和播放模式都与上次相同时，才能从上次的位置继续
+    let playlist_hash = state::playlist_hash(&playlist);
+    let resume_target = resume_state.and_then(|state| {
+        let same_order = state.shuffle_seed.is_some() == is_random_enabled;
+        if state.playlist_hash == playlist_hash && same_order && state.track_index < playlist.len() {
+            Some((state.track_index, Duration::from_secs(state.seek_position_secs)))
+        } else {
+            eprintln!("[警告]播放列表与上次不同，从头开始播放。");
+            None
+        }
+    });
+
+    // 指定了 --from-title 时，从第一首匹配的歌曲开始播放（--resume 成功时以恢复的位置为准）
+    let start_index = match (&resume_target, &args.from_title) {
+        (Some((index, _)), _) => *index,
+        (None, Some(needle)) => find_track_by_title(&playlist, needle, args.fix_encoding).unwrap_or_else(|| {
+            eprintln!("[警告]未找到标题包含 '{}' 的歌曲，从第一首开始播放。", needle);
+            0
+        }),
+        (None, None) => 0,
+    };
+    // 恢复的播放位置，第一首歌加载后跳转过去
+    let mut resume_position = resume_target.map(|(_, position)| position);
+
     // ----------------------------------------------------
     // --- 核心播放逻辑：初始化 ---
     // ----------------------------------------------------
 
     let mut stdout = io::stdout();
+    // 无终端模式：输出不是终端（例如后台运行或重定向）、无法读取按键或指定了 --headless
+    let headless = args.headless || !stdout.is_terminal() || !terminal_input_available();
+    // 任务栏进度：手动开启，或检测到 Windows Terminal（WT_SESSION）时自动开启
+    let taskbar_progress = !headless && (args.taskbar_progress || std::env::var_os("WT_SESSION").is_some());
+    // 括号粘贴模式：粘贴的文本作为一个整体事件送达并被丢弃，不会被逐字当作按键命令（例如路径中的 q 导致退出）
+    let bracketed_paste = !headless && !args.no_bracket_paste;
+    let mouse_capture = !headless && !args.no_mouse;
+    // 收到外部信号（SIGINT/SIGTERM/SIGHUP，Windows 下为控制台关闭事件）时设置标记，由播放循环走正常的退出流程恢复终端。
+    // 无终端模式下只能通过信号停止播放；终端模式下 raw mode 中的 Ctrl+C 作为按键处理
+    let stop_requested = Arc::new(AtomicBool::new(false));
+    {
+        let flag = Arc::clone(&stop_requested);
+        ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))?;
+    }
+
+    // 控制套接字：其他终端可以通过它发送 next/pause/volume 等命令
+    let (ipc_tx, ipc_rx) = channel::<RemoteCommand>();
+    if let Some(path) = &args.socket {
+        match start_ipc_server(path, ipc_tx.clone()) {
+            Ok(()) => cleanup_files.push(path.clone()),
+            Err(e) => eprintln!("[警告]无法创建控制套接字 {}: {}", path.display(), e),
+        }
+    }
+    // HTTP 控制服务：与控制套接字共用命令通道，退出时随 _http_server 一起关闭
+    let _http_server = match args.http {
+        Some(addr) => match start_http_server(addr, args.http_token.clone(), ipc_tx.clone()) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                eprintln!("[警告]无法启动 HTTP 控制服务 {}: {}", addr, e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // 初始化音频输出和 Sink（在终端初始化之前，找不到可用设备时错误信息能正常输出）
+    let (mut _stream, mut sink) = open_audio_output(initial_volume.clamp(0.0, volume_cap), args.output_device.as_deref(), args.audio_buffer_size)?;
+    let mut device_recovery = DeviceRecovery::new();
+    // 播放倍速：保存在 sink 的控制状态里，切歌后保持
+    let mut playback_speed = args.speed;
+    sink.set_speed(playback_speed);
 
     // 终端初始化
-    execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-    if !is_simple_mode {
-        execute!(stdout, SetSize(60, 8))?;
-    } else {
-        execute!(stdout, SetSize(60, 1))?;
+    // 窗口标题模板，以及当前已设置的标题（只在变化时更新）
+    let title_template = args.window_title_template.as_deref().unwrap_or(DEFAULT_WINDOW_TITLE);
+    // 状态栏配色
+    let theme = resolve_theme(args.color);
+    let mut window_title_shown = format!("{} - v{}", cli::NAME, cli::VERSION);
+    // --tmux-title：不在 tmux/screen 中时为 None
+    let tmux_title = if args.tmux_title && !headless { TmuxTitle::detect() } else { None };
+    let mut exit_context = ExitContext { headless, taskbar_progress, bracketed_paste, mouse_capture, tmux_title, save_session: args.resume };
+    if !headless {
+        execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        if !is_simple_mode {
+            execute!(stdout, SetSize(60, 8))?;
+        } else {
+            execute!(stdout, SetSize(60, 1))?;
+        }
+        execute!(stdout, SetTitle(&window_title_shown))?;
+        enable_raw_mode()?;
+        execute!(stdout, cursor::Hide)?;
+        if bracketed_paste {
+            execute!(stdout, EnableBracketedPaste)?;
+        }
+        if mouse_capture {
+            execute!(stdout, EnableMouseCapture)?;
+        }
     }
-    let mut initial_title = format!("{} - v{}", cli::NAME, cli::VERSION);
-    execute!(stdout, SetTitle(initial_title.clone()))?;
-    enable_raw_mode()?;
-    execute!(stdout, cursor::Hide)?;
+    // 出错返回或 panic 时兜底恢复终端（正常退出时 graceful_exit 已经恢复过）
+    let _terminal_guard = (!headless).then(|| TerminalGuard::new(bracketed_paste, mouse_capture));
 
-    // 初始化音频输出和 Sink
-    let (_stream, stream_handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&stream_handle)?;
-    // 初始设置音量
-    sink.set_volume(initial_volume.min(1.0).max(0.0));
+    // 状态栏所在行：纯净模式下为第一行，否则位于说明区域下方
+    let status_line: u16 = if is_simple_mode { 0 } else { HEADER_LINES };
 
     // 显示界面信息（非纯净模式下）
-    if !is_simple_mode {
-        println!("=====================【 {} 】======================", NAME);
-        println!(" 版本:v{}          主页:{}", VERSION, URL);
-        println!("===========================================================");
-        println!("====================【 控 制 说 明 】======================");
-        println!(" [P]静音/取消静音   [空格]暂停/播放    [Q/Ctrl+C]退出播放");
-        println!(" [←]上一首      [→]下一首    [↑]音量增    [↓]音量减");
-        println!("============================================================");
+    if !headless {
+        if !is_simple_mode {
+            draw_control_guide(&mut stdout)?;
+        }
+        execute!(stdout, cursor::MoveTo(0, status_line))?;
     }
 
     // --- 异步初始化和预加载设置 ---
-    let (tx, rx): (Sender<PreloadResult>, Receiver<PreloadResult>) = channel();
-    let total_tracks = playlist.len();
-    let mut current_track_index: usize = 0;
+    let (tx, rx): (Sender<PreloadMessage>, Receiver<PreloadMessage>) = channel();
+    let mut total_tracks = playlist.len(); // 按 D/U 键移除或恢复歌曲后更新
+    let mut current_track_index: usize = start_index;
+    let preload_depth = args.preload as usize;
+    let load_options = LoadOptions {
+        encoding_fix: args.fix_encoding,
+        buffer_limit: (args.buffer == BufferMode::Full).then_some(args.buffer_cap * 1024 * 1024),
+        equalizer: args.equalizer,
+        min_duration: args.min_duration,
+        max_duration: args.max_duration,
+        loudness_normalize: args.loudness_normalize,
+        network_retry: args.network_retry,
+        announce_speech: args.announce == Announce::Tts,
+    };
+    let mut preload_cache = PreloadCache::new(load_options, entry_hints, args.verbose);
 
     // 🌟 启动第一首歌的预加载
-    start_preload_if_valid(&playlist, 0, &tx);
+    preload_cache.request(&playlist, current_track_index, &tx);
 
     let mut index_offset: i32 = 0;
-    let mut last_skip_time = Instant::now() - MIN_SKIP_INTERVAL;
+    let mut repeat_cycle: u32 = 1; // 当前是第几轮播放
+    let mut repeat_pass: u32 = 1; // 当前歌曲是第几遍播放（--repeat-each、单曲循环），切到其他歌曲时重置
+    let mut skip_accelerator = SkipAccelerator::new(MIN_SKIP_INTERVAL); // 切歌的防抖和按住时的快速浏览
+    let mut skip_carry: usize = 0; // 循环播放时向后跳过多首越过末尾，在新一轮中还要前进的歌曲数
     let mut muted_volume: Option<f32> = None; // 静音状态（移到外层循环，避免切歌时丢失）
+    let mut osd = Osd::new(OSD_DURATION); // 状态栏临时提示（切歌后继续显示到过期）
+    let channel_mix = ChannelMix::new(args.mono, args.balance); // 单声道和左右平衡，切歌后保持
+    let output_options = OutputOptions {
+        device: args.output_device.as_deref(),
+        buffer_size: args.audio_buffer_size,
+        load_options: &load_options,
+        channel_mix: &channel_mix,
+    };
+    let mut history = PlayHistory::new(args.history_size);
+    let mut show_history = false; // 是否显示历史面板
+    let mut show_help = false; // 是否显示按键帮助（纯净模式下为状态栏轮换提示）
+    let mut help_page: usize = 0; // 纯净模式下当前显示的提示页
+    let mut history_selected: usize = 0; // 历史面板中高亮的条目（0 为最新）
+    let mut jump_target: Option<usize> = None; // 从历史面板选择的跳转目标
+    let mut session_stats = SessionStats::default();
+    let mut exit_code = ExitCode::SUCCESS; // 主循环异常结束时改为 FAILURE
+    let mut base_volume = initial_volume.clamp(0.0, volume_cap); // 用户设定的基准音量，只在用户调整音量时改变
+    let mut track_gain = TrackGain::default(); // 当前歌曲在基准音量之上的增益（随机浮动、记忆音量、响度均衡）
+    // 每首歌记住的音量，--no-volume-memory 时不读取也不记录
+    let mut volume_memory = (!args.no_volume_memory).then(VolumeMemory::load);
+    // 已加载过的歌曲信息 (标题, 艺术家, 时长)，保存 m3u 时使用
+    let mut known_track_info: HashMap<PathBuf, (String, String, Duration)> = HashMap::new();
+    // --playlist-progress：后台探测到的和已加载过的歌曲时长（不显示时为 None）
+    let mut playlist_durations: Option<HashMap<PathBuf, Duration>> = args.playlist_progress.then(HashMap::new);
+    let duration_probe = args.playlist_progress.then(|| metadata::spawn_duration_probe(playlist.clone()));
+    let save_playlist_path = args.save_playlist.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_SAVE_PLAYLIST));
+    let early_end_threshold = args.early_end_threshold as f64 / 100.0;
+    let mut skipped_by_user = false; // 当前歌曲是否由用户手动切换而来
+    let mut first_track = true; // 是否为本次启动后播放的第一首歌（--announce chime 只在两首歌之间提示）
+    let mut remove_current = false; // 当前歌曲已标记移除，切到下一首之前从播放列表中删除
+    let mut moving_backward = false; // 上一次切歌是否是向前（←），跳过重复歌曲时沿同一方向
+    let mut out_of_range_run: usize = 0; // 连续因时长不在范围内跳过的歌曲数，达到播放列表长度时说明没有可播放的歌曲
+    // --dedup audio：后台线程按音频内容查找重复的歌曲，发现的重复文件在轮到时跳过
+    let duplicate_rx = (args.dedup == Some(DedupMode::Audio)).then(|| {
+        let (duplicate_tx, duplicate_rx) = channel();
+        dedup::start_audio_dedup(playlist.clone(), duplicate_tx);
+        duplicate_rx
+    });
+    let mut duplicate_tracks: HashSet<PathBuf> = HashSet::new();
+    let mut retry_start: Option<usize> = None; // 播放列表末尾追加的重试歌曲从哪个位置开始
+    // --watch-playlist：后台检查播放列表文件，新增的歌曲追加到播放列表末尾
+    let append_options = watch::AppendOptions {
+        use_excluded: args.use_excluded,
+        dedup: args.dedup,
+        skip_duplicates_by_title: args.skip_duplicates_by_title,
+        fix_encoding: args.fix_encoding,
+        min_duration: args.min_duration,
+        max_duration: args.max_duration,
+        strict: args.strict,
+        max_tracks: args.max_tracks,
+    };
+    let playlist_watch = args.watch_playlist.as_ref().map(|file| watch::watch_playlist(file.clone(), &playlist, scan_options, append_options));
+    // 退出时保存的播放状态（当前歌曲和位置在播放中更新）
+    let mut session_state = SessionState { playlist_hash, track_index: start_index, seek_position_secs: 0, shuffle_seed };
+
+    // 播放每首歌时不变的设置和通道
+    let context = PlaybackContext {
+        args: &args,
+        sources: &sources,
+        headless,
+        is_simple_mode,
+        is_random_enabled,
+        taskbar_progress,
+        loop_count,
+        max_volume,
+        volume_cap,
+        status_line,
+        early_end_threshold,
+        title_template,
+        theme,
+        save_playlist_path: &save_playlist_path,
+        load_options: &load_options,
+        output_options: &output_options,
+        channel_mix: &channel_mix,
+        stop_requested: &stop_requested,
+        ipc_rx: &ipc_rx,
+        tx: &tx,
+        duration_probe: duration_probe.as_ref(),
+        playlist_watch: playlist_watch.as_ref(),
+    };
 
     // --- 主循环：迭代播放列表 ---
     'outer: loop {
-        // 🌟 关键修正：在进入阻塞等待前，快速检查是否有 Ctrl+C/Q 按下
-        if event::poll(Duration::from_millis(0))? {
-            if let Event::Key(key_event) = event::read()? {
-                if key_event.code == KeyCode::Char('q') || key_event.code == KeyCode::Char('Q') || key_event.code == KeyCode::Char('c') {
-                    graceful_exit(&mut stdout)?;
-                    return Ok(());
-                }
-            }
+        // 切到新的歌曲后，退出时保存的位置从这首歌的开头算起
+        if current_track_index < total_tracks && session_state.track_index != current_track_index {
+            session_state.track_index = current_track_index;
+            session_state.seek_position_secs = 0;
+        }
+        // 🌟 关键修正：在进入阻塞等待前，快速检查是否收到停止信号或有 Ctrl+C/Q 按下
+        if stop_requested.load(Ordering::SeqCst) {
+            return graceful_exit(&mut stdout, &exit_context, &session_stats, &session_state, ExitCode::SUCCESS);
+        }
+        if !headless
+            && event::poll(Duration::from_millis(0))?
+            && let Event::Key(key_event) = event::read()?
+            && matches!(key_event.code, KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('c'))
+        {
+            return graceful_exit(&mut stdout, &exit_context, &session_stats, &session_state, ExitCode::SUCCESS);
         }
         
-        // 循环播放检查 (如果当前索引超限，则尝试循环或退出)
+        // 循环播放检查 (如果当前索引超限，则尝试循环或退出；循环次数用尽时同样退出)
         if current_track_index >= total_tracks {
-            if is_loop_enabled {
-                current_track_index = 0;
-                start_preload_if_valid(&playlist, 0, &tx);
+            // 先重试本次加载失败的歌曲：--retry-failed 时直接重试，否则询问（无终端模式下不重试）。
+            // 重试的歌曲追加到播放列表末尾作为最后一段，每首最多重试 MAX_RETRY_ATTEMPTS 次
+            let retry = session_stats.retry_candidates(&playlist);
+            if !retry.is_empty() && (args.retry_failed || (!headless && confirm_retry(&mut stdout, retry.len(), &stop_requested)?)) {
+                info!(count = retry.len(), "重试加载失败的歌曲");
+                retry_start.get_or_insert(total_tracks);
+                current_track_index = total_tracks;
+                playlist.extend(retry);
+                total_tracks = playlist.len();
+                preload_cache.request(&playlist, current_track_index, &tx);
+                continue 'outer;
+            }
+            if loop_mode != LoopMode::Off && loop_count.is_none_or(|count| repeat_cycle < count) {
+                repeat_cycle += 1;
+                // 去掉追加的重试歌曲，下一轮它们在原来的位置播放（其间 --watch-playlist 新增的歌曲不会与原有的重复，保留）
+                if let Some(start) = retry_start.take() {
+                    let originals: HashSet<PathBuf> = playlist[..start].iter().cloned().collect();
+                    for index in (start..playlist.len()).rev() {
+                        if originals.contains(&playlist[index]) {
+                            playlist.remove(index);
+                            history.on_removed(index);
+                        }
+                    }
+                    total_tracks = playlist.len();
+                    preload_cache.invalidate();
+                }
+                // 随机模式下每轮重新洗牌，并避免新一轮的第一首与刚播完的最后一首相同
+                if is_random_enabled && !args.no_reshuffle && total_tracks > 1 {
+                    // 重新洗牌后的顺序无法用种子复现，之后不再支持 --resume 恢复
+                    session_state.shuffle_seed = None;
+                    let mut rng = rand::thread_rng();
+                    let last_played = playlist[total_tracks - 1].clone();
+                    if let Some(group_by) = args.group_by {
+                        // 分组模式下只重新打乱各组的顺序
+                        playlist = group_playlist(std::mem::take(&mut playlist), group_by, &group_tags, Some(&mut rng));
+                    } else {
+                        playlist.shuffle(&mut rng);
+                        if args.shuffle == ShuffleMode::Spread {
+                            // 新一轮打散时用上一轮播放时读到的艺术家，开头与刚播完的歌保持间隔
+                            let artist_of = |path: &Path| known_track_info.get(path).map(|(_, artist, _)| artist.clone());
+                            playlist = spread_playlist(playlist, Some(&last_played), artist_of, &mut rng);
+                        } else if playlist[0] == last_played {
+                            let swap_with = rng.gen_range(1..total_tracks);
+                            playlist.swap(0, swap_with);
+                        }
+                    }
+                    preload_cache.invalidate();
+                }
+                current_track_index = std::mem::take(&mut skip_carry) % total_tracks;
+                preload_cache.request(&playlist, current_track_index, &tx);
             } else {
                 break;
             }
         }
 
-        // --- 5. 文件加载、解码、添加到 Sink (使用预加载结果) ---
-        let (preloaded_data, _preloaded_index) = loop {
-            // 尝试接收预加载结果，等待时间较长以确保有时间加载
-            match rx.recv_timeout(Duration::from_secs(5)) {
-                // ⚠️ 接收到成功结果
-                Ok(PreloadResult::Success(data, index)) => {
-                    if index == current_track_index {
-                        break (data, index);
-                    } else {
-                        continue;
-                    }
-                },
-                // ⚠️ 接收到失败结果
-                Ok(PreloadResult::Failure(index, err_type, filename)) => {
-                    if index == current_track_index {
-                        display_error_and_wait(&mut stdout, current_track_index, total_tracks, &err_type, &filename)?;
-                        current_track_index += 1;
-                        start_preload_if_valid(&playlist, current_track_index, &tx);
-                        continue 'outer;
-                    } else {
-                        continue;
-                    }
-                },
-                // 如果超时...
-                Err(e) if e == std::sync::mpsc::RecvTimeoutError::Timeout => {
-                    display_error_and_wait(&mut stdout, current_track_index, total_tracks, "加载超时", "")?;
+        // 跳过后台发现的重复歌曲
+        if let Some(duplicate_rx) = &duplicate_rx {
+            for duplicate in duplicate_rx.try_iter() {
+                info!(kept = %duplicate.kept.display(), dropped = %duplicate.dropped.display(), "发现音频重复的歌曲");
+                duplicate_tracks.insert(duplicate.dropped);
+            }
+            if duplicate_tracks.contains(&playlist[current_track_index]) {
+                let filename = playlist[current_track_index].file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                osd.show(format!("跳过重复: {}", filename));
+                if moving_backward {
+                    current_track_index = if current_track_index == 0 { total_tracks - 1 } else { current_track_index - 1 };
+                } else {
                     current_track_index += 1;
-                    start_preload_if_valid(&playlist, current_track_index, &tx);
-                    continue 'outer;
                 }
-                // 接收通道断开
-                Err(_) => {
-                    eprintln!("\n[致命错误] 预加载通道关闭，退出播放器...");
+                repeat_pass = 1;
+                continue 'outer;
+            }
+        }
+
+        // --- 5. 文件加载、解码、添加到 Sink (使用预加载结果) ---
+        // 当前歌曲既没有缓存也没有在加载时（例如向前切歌），立即启动加载
+        preload_cache.request(&playlist, current_track_index, &tx);
+        let preloaded_data = match wait_for_track(&mut preload_cache, &rx, &playlist, current_track_index, args.binary_sizes) {
+            TrackLoad::Loaded(data) => {
+                out_of_range_run = 0;
+                data
+            }
+            TrackLoad::Failed(err_type, filename, detail) => {
+                session_stats.record_failure(&playlist[current_track_index], format!("{}: {}", err_type, detail));
+                display_error_and_wait(&mut stdout, current_track_index, total_tracks, &err_type, &filename, args.verbose.then_some(detail.as_str()), headless, args.bell_on_error)?;
+                current_track_index += 1;
+                repeat_pass = 1;
+                preload_cache.request(&playlist, current_track_index, &tx);
+                continue 'outer;
+            }
+            // 时长不在 --min-duration/--max-duration 范围内：与跳过重复歌曲一样只做简短提示，不计为出错
+            TrackLoad::OutOfRange(duration) => {
+                let filename = playlist[current_track_index].file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                info!(path = %playlist[current_track_index].display(), duration = ?duration, "时长不在范围内，跳过");
+                osd.show(format!("跳过 {} ({})", filename, format_duration(duration)));
+                // 循环播放或向前切歌时会一直绕圈，连续跳过了整个播放列表就停止
+                out_of_range_run += 1;
+                if out_of_range_run >= total_tracks {
+                    eprintln!("\n[错误]播放列表中没有时长在 --min-duration/--max-duration 范围内的歌曲，退出播放器...");
+                    exit_code = ExitCode::FAILURE;
                     break 'outer;
                 }
+                if moving_backward {
+                    current_track_index = if current_track_index == 0 { total_tracks - 1 } else { current_track_index - 1 };
+                } else {
+                    current_track_index += 1;
+                }
+                repeat_pass = 1;
+                continue 'outer;
+            }
+            // 放弃这次加载，之后再轮到这首歌时可以重新尝试
+            TrackLoad::TimedOut => {
+                session_stats.record_failure(&playlist[current_track_index], "加载超时".to_string());
+                warn!(path = %playlist[current_track_index].display(), "加载超时");
+                display_error_and_wait(&mut stdout, current_track_index, total_tracks, "加载超时", "", None, headless, args.bell_on_error)?;
+                current_track_index += 1;
+                repeat_pass = 1;
+                preload_cache.request(&playlist, current_track_index, &tx);
+                continue 'outer;
+            }
+            TrackLoad::Disconnected => {
+                eprintln!("\n[致命错误] 预加载通道关闭，退出播放器...");
+                exit_code = ExitCode::FAILURE;
+                break 'outer;
             }
         };
+        session_stats.record_loaded(&playlist[current_track_index]);
         // ... (歌曲预加载成功后的逻辑，与原代码一致)
         let track_path_str = playlist[current_track_index].to_string_lossy().to_string();
-        sink.clear();
-        sink.append(preloaded_data.decoder);
-        
-        // -----------------------------------------------------------------
-        // 🌟 BUG 修复：切歌后重新应用静音状态或恢复音量
-        // -----------------------------------------------------------------
-        if let Some(_vol) = muted_volume {
-            // 如果处于静音状态，保持静音（音量 0.0）
-            sink.set_volume(0.0);
+        // --announce：歌曲开始前播放提示音或朗读歌名，不计入播放进度；自动切歌后等待按键开始的歌曲不提示。
+        // 重复播放同一首歌时不提示，也不等待按键
+        let repeating = repeat_pass > 1;
+        let waits_for_start = args.auto_advance == AutoAdvance::Manual && !skipped_by_user && !headless && !repeating;
+        if !waits_for_start
+            && !repeating
+            && let Some(announcement) = announce::announcement(args.announce, preloaded_data.speech, first_track)
+            && play_announcement(&sink, announcement, headless, &stop_requested)?
+        {
+            return graceful_exit(&mut stdout, &exit_context, &session_stats, &session_state, ExitCode::SUCCESS);
+        }
+        first_track = false;
+        // --tmux-title：切歌后更新 tmux/screen 窗口名
+        if let Some(tmux_title) = &mut exit_context.tmux_title {
+            tmux_title.set(&mut stdout, &tmux::window_name(&preloaded_data.title, &preloaded_data.artist))?;
+        }
+        // --resume 恢复的歌曲从上次的位置开始；--preview 从距结尾指定秒数处开始（时长未知时从头试听）
+        let start_offset = match (resume_position.take(), args.preview) {
+            (Some(position), _) => position,
+            (None, Some(_)) if preloaded_data.total_duration.is_zero() => {
+                warn!(path = %track_path_str, "时长未知，无法定位到结尾，从头试听");
+                show_notice(&mut osd, headless, "时长未知，无法定位到结尾，从头试听");
+                Duration::ZERO
+            }
+            (None, Some(window)) => preloaded_data.total_duration.saturating_sub(Duration::from_secs(window)),
+            (None, None) => Duration::ZERO,
+        };
+        // 从中间开始时按相同的设置重新打开并直接定位，无法定位时从头播放
+        let (decoder, start_offset) = if start_offset.is_zero() {
+            (preloaded_data.decoder, start_offset)
         } else {
-            // 如果不是静音状态，确保音量是上次退出内部循环时的音量（或初始音量）
-            // 注意：初始音量已经在 main 277 行设置过，这里可以不处理，
-            // 但为了健壮性（例如用户在切歌过程中调整了系统音量），可以重新应用。
-            // 保持当前 sink 的 volume 即可，因为 adjust_volume 已经更新了它
+            match open_track_at(&playlist[current_track_index], start_offset, &load_options) {
+                Ok(decoder) => (decoder, start_offset),
+                Err(e) => {
+                    warn!(path = %track_path_str, position = ?start_offset, error = %e, "无法定位，从头播放");
+                    show_notice(&mut osd, headless, &format!("无法定位到 {}，从头播放", format_duration(start_offset)));
+                    (preloaded_data.decoder, Duration::ZERO)
+                }
+            }
+        };
+        // --preview 的试听在这个位置结束（时长已知时即为歌曲结尾），之后按播放完毕切到下一首
+        let preview_end = args.preview.map(|window| start_offset + Duration::from_secs(window));
+        // 🌟 BUG 修复：切歌后重新应用静音状态
+        replace_track_source(&sink, decoder, &channel_mix, muted_volume);
+
+        // 每首歌的音量：基准音量乘以这首歌的随机浮动、记忆音量和响度均衡系数（静音时只更新取消静音后的音量）。
+        // 基准音量单独保存，不从被音量上限截断过的 sink 音量反推
+        if let Some(pct) = args.randomize_volume {
+            let variation = rand::thread_rng().gen_range(-(pct as f32)..=pct as f32);
+            track_gain.variation = 1.0 + variation / 100.0;
+        }
+        // 切歌时保存上一首调整过的记忆音量（退出时由 VolumeMemory 的 drop 保存）
+        if let Some(Err(e)) = volume_memory.as_mut().map(VolumeMemory::save) {
+            warn!(error = %e, "无法保存记忆音量");
+        }
+        track_gain.memory = volume_memory.as_ref().map_or(1.0, |memory| memory.get(&playlist[current_track_index]));
+        track_gain.loudness = preloaded_data.loudness_gain.unwrap_or(1.0);
+        if muted_volume.is_some() {
+            muted_volume = Some(compose_volume(base_volume, &track_gain, false, volume_cap));
+        }
+        sink.set_volume(compose_volume(base_volume, &track_gain, muted_volume.is_some(), volume_cap));
+        if track_gain.memory != 1.0 {
+            osd.show(format!("[记忆音量] {:+.0}%", (track_gain.memory - 1.0) * 100.0));
         }
-        // -----------------------------------------------------------------
 
 
-        if sink.is_paused() {
+        // --auto-advance manual：自动切到的歌曲加载后保持暂停，等待按 → 开始（无终端模式下无法读取按键，忽略）
+        let awaiting_start = waits_for_start;
+        if awaiting_start {
+            sink.pause();
+        } else if sink.is_paused() {
             sink.play();
         }
 
         let title = preloaded_data.title;
         let artist = preloaded_data.artist;
+        let album = preloaded_data.album.unwrap_or_else(|| "未知".to_string());
         let total_duration = preloaded_data.total_duration;
+        let (disc_number, track_number) = (preloaded_data.disc_number, preloaded_data.track_number);
+        known_track_info.insert(playlist[current_track_index].clone(), (title.clone(), artist.clone(), total_duration));
+        if let Some(durations) = playlist_durations.as_mut().filter(|_| !total_duration.is_zero()) {
+            durations.insert(playlist[current_track_index].clone(), total_duration);
+        }
 
-        let current_initial_title = format!("{}-{}-{}v{}", title, artist, NAME, VERSION); // 使用新的局部变量
+        info!(index = current_track_index + 1, total = total_tracks, path = %track_path_str, "开始播放: {} - {}", title, artist);
 
-        // 根据静音状态设置标题
-        let display_title = if muted_volume.is_some() {
-            format!("[静音]{}", current_initial_title)
-        } else {
-            current_initial_title.clone()
-        };
-        execute!(stdout, SetTitle(display_title))?;
-        // 更新外层 initial_title 以便内部循环使用
-        initial_title = current_initial_title;
+        // 窗口标题在内部播放循环中按暂停/静音状态更新
+        if headless {
+            // 无终端模式下每首歌只输出一行
+            println!("正在播放: {} - {}", title, artist);
+        } else if awaiting_start {
+            let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+            flush_progress_line(&mut stdout, &pad_to_width("按 → 开始播放...", terminal_width))?;
+        }
+
+        // 切歌提示音，方便最小化窗口或使用读屏软件的用户
+        if args.bell_on_track_change {
+            ring_bell(&mut stdout)?;
+        }
 
+        // 历史面板打开时，切歌后刷新面板内容
+        if show_history {
+            history_selected = 0;
+            draw_history_overlay(&mut stdout, &history, history_selected, status_line)?;
+        }
 
-        let next_index = (current_track_index + 1) % total_tracks;
 
-        if next_index != current_track_index && (is_loop_enabled || current_track_index < total_tracks.saturating_sub(1)) {
-            start_preload_if_valid(&playlist, next_index, &tx);
+        // 预加载之后的 preload_depth 首歌曲（循环模式下绕回开头），并丢弃窗口外的缓存
+        for offset in 1..=preload_depth {
+            let next_index = current_track_index + offset;
+            if next_index >= total_tracks && loop_mode == LoopMode::Off {
+                break;
+            }
+            preload_cache.request(&playlist, next_index % total_tracks, &tx);
         }
+        preload_cache.evict_outside(current_track_index, preload_depth, total_tracks);
 
-        let start_time = Instant::now();
-        let mut paused_duration = Duration::from_secs(0);
-        let mut last_pause_time: Option<Instant> = None;
-        let mut last_running_time = Duration::from_secs(0);
-        let mut last_progress_update = Instant::now();
-        let mut forced_stop = false;
-        let mut last_toggle_time = Instant::now() - Duration::from_millis(300); // 按键防抖
+        // 快速浏览中累计的偏移是相对于上一首歌的，换歌后丢弃
+        skip_accelerator.track_changed();
+        // 歌曲内的播放位置，从 start_offset 开始计时（--auto-advance manual 时歌曲以暂停状态开始）
+        let mut clock = PlaybackClock::new(start_offset, playback_speed, sink.is_paused(), Instant::now());
+        let track = CurrentTrack {
+            title: &title,
+            artist: &artist,
+            album: &album,
+            path_str: &track_path_str,
+            disc_number,
+            track_number,
+            total_duration,
+            preview_end,
+        };
+        let player = PlayerState {
+            stdout: &mut stdout,
+            output_stream: &mut _stream,
+            sink: &mut sink,
+            device_recovery: &mut device_recovery,
+            playlist: &mut playlist,
+            total_tracks: &mut total_tracks,
+            current_track_index: &mut current_track_index,
+            group_tags: &mut group_tags,
+            preload_cache: &mut preload_cache,
+            session_state: &mut session_state,
+            session_stats: &mut session_stats,
+            history: &mut history,
+            retry_start: &mut retry_start,
+            known_track_info: &known_track_info,
+            playlist_durations: &mut playlist_durations,
+            loop_mode: &mut loop_mode,
+            repeat_cycle,
+            repeat_pass: &mut repeat_pass,
+            playback_speed: &mut playback_speed,
+            muted_volume: &mut muted_volume,
+            base_volume: &mut base_volume,
+            track_gain: &mut track_gain,
+            volume_memory: &mut volume_memory,
+            skip_accelerator: &mut skip_accelerator,
+            osd: &mut osd,
+            show_history: &mut show_history,
+            show_help: &mut show_help,
+            help_page: &mut help_page,
+            history_selected: &mut history_selected,
+            remove_current: &mut remove_current,
+            window_title_shown: &mut window_title_shown,
+        };
+        // 8. 播放这首歌，直到播完、被切走或退出
+        let mut forced_stop = match play_track(&context, player, &track, &mut clock, awaiting_start)? {
+            TrackAdvance::Finished => false,
+            TrackAdvance::Skip(offset) => {
+                index_offset = offset;
+                true
+            }
+            TrackAdvance::Jump(index) => {
+                jump_target = Some(index);
+                true
+            }
+            TrackAdvance::Quit => return graceful_exit(&mut stdout, &exit_context, &session_stats, &session_state, ExitCode::SUCCESS),
+        };
 
-        // 8. 内部播放循环 
-        'inner: while !sink.empty() {
-            // ... (时间计算)
+        // 播完或被切走的歌曲记入播放历史
+        history.push(current_track_index, &title, &artist);
+        // 收听时长从本次开始播放的位置算起（--resume、--preview 跳过的部分不计入）
+        session_stats.record_listened(clock.listened(Instant::now()), total_duration);
 
-            if sink.is_paused() {
-                if last_pause_time.is_none() {
-                    last_pause_time = Some(Instant::now());
-                    last_running_time = start_time.elapsed().saturating_sub(paused_duration);
-                }
-            } else {
-                if let Some(pause_start) = last_pause_time.take() {
-                    paused_duration += pause_start.elapsed();
-                }
+        // 歌曲自然结束，但实际播放时长明显短于总时长：文件可能被截断或损坏（时长未知时跳过检查）
+        if !forced_stop && !total_duration.is_zero() {
+            let played = clock.position(Instant::now());
+            if played.as_secs_f64() < total_duration.as_secs_f64() * early_end_threshold {
+                session_stats.errors += 1;
+                session_stats.early_ends += 1;
+                warn!(path = %track_path_str, played = ?played, total = ?total_duration, "播放提前结束，文件可能损坏");
+                let warning = format!(
+                    "[{}/{}] 文件可能损坏，提前结束于 {}/{}",
+                    current_track_index + 1, total_tracks, format_duration(played), format_duration(total_duration)
+                );
+                display_warning_and_wait(&mut stdout, &warning, headless, args.bell_on_error)?;
             }
-            let current_time = if sink.is_paused() {
-                last_running_time
-            } else {
-                start_time.elapsed().saturating_sub(paused_duration)
-            };
+        }
 
-            // 刷新显示 (与原代码一致)
-            if last_progress_update.elapsed() >= UPDATE_INTERVAL {
-                // BUG 修复：如果处于静音状态，在 update_progress_display 中显示 0% 音量，否则显示实际音量
-                let display_volume = if muted_volume.is_some() {
-                    0.0
-                } else {
-                    sink.volume()
-                };
-
-                update_progress_display(
-                    &mut stdout,
-                    current_track_index,
-                    total_tracks,
-                    is_random_enabled,
-                    is_loop_enabled,
-                    &title,
-                    &artist,
-                    &track_path_str,
-                    current_time,
-                    total_duration,
-                    display_volume, // 使用修复后的音量
-                )?;
-                last_progress_update = Instant::now();
-            }
-
-            // --- 用户输入处理 (非阻塞) ---
-            if event::poll(Duration::from_millis(100))? {
+        // 自然播完后是否再播一遍：单曲循环，或 --repeat-each 的遍数还没播完（移除的歌曲不再重复）
+        let repeat_track = !forced_stop && !remove_current && (loop_mode == LoopMode::Track || repeat_pass < args.repeat_each);
+
+        // 手动切歌模式：歌曲自然播完后等待用户选择（无终端模式下无法读取按键，忽略该选项）
+        if !forced_stop && !repeat_track && manual_advance && !headless {
+            let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+            flush_progress_line(&mut stdout, &pad_to_width("播放完毕，[→]下一首 [←]重播 [Q]退出", terminal_width))?;
+            loop {
+                if stop_requested.load(Ordering::SeqCst) {
+                    return graceful_exit(&mut stdout, &exit_context, &session_stats, &session_state, ExitCode::SUCCESS);
+                }
+                if !event::poll(Duration::from_millis(100))? {
+                    continue;
+                }
                 if let Event::Key(key_event) = event::read()? {
                     match key_event.code {
-                        // P键：静音/取消静音
-                        KeyCode::Char('p') | KeyCode::Char('P') => {
-                            if last_toggle_time.elapsed() < Duration::from_millis(200) { continue; }
-                            last_toggle_time = Instant::now();
-                            if let Some(vol) = muted_volume {
-                                // 取消静音
-                                sink.set_volume(vol);
-                                muted_volume = None;
-                                execute!(stdout, SetTitle(initial_title.clone()))?;
-                            } else {
-                                // 静音
-                                muted_volume = Some(sink.volume());
-                                sink.set_volume(0.0);
-                                let mute_title = format!("[静音]{}", initial_title);
-                                execute!(stdout, SetTitle(mute_title))?;
-                            }
-                        }
-                        // 空格键：暂停/播放
-                        KeyCode::Char(' ') => {
-                            if last_toggle_time.elapsed() < Duration::from_millis(200) { continue; }
-                            last_toggle_time = Instant::now();
-                            if sink.is_paused() {
-                                sink.play();
-                                // BUG 修复：播放时标题应恢复正常（如果非静音）或保持静音（如果静音）
-                                let display_title = if muted_volume.is_some() {
-                                    format!("[静音]{}", initial_title)
-                                } else {
-                                    initial_title.clone()
-                                };
-                                execute!(stdout, SetTitle(display_title))?;
-                            } else {
-                                sink.pause();
-                                let pause_title = format!("[暂停]{}", initial_title);
-                                execute!(stdout, SetTitle(pause_title))?;
-                            }
+                        // 下一首：与自动切歌的索引逻辑一致
+                        KeyCode::Right => break,
+                        // 重播：沿用当前歌曲的路径重新加载
+                        KeyCode::Left => {
+                            jump_target = Some(current_track_index);
+                            forced_stop = true;
+                            break;
                         }
-                        // 音量控制
-                        KeyCode::Up => {
-                            // 调整音量时，如果处于静音状态，应先取消静音，恢复音量并增加
-                            if let Some(vol) = muted_volume.take() {
-                                // 先恢复到静音前的音量
-                                sink.set_volume(vol);
-                                execute!(stdout, SetTitle(initial_title.clone()))?;
-                            }
-                            adjust_volume(&sink, VOLUME_STEP);
-                        },
-                        KeyCode::Down => {
-                            // 调整音量时，如果处于静音状态，应先取消静音，恢复音量并减小
+                        // 等待期间仍可调节音量
+                        KeyCode::Up | KeyCode::Down => {
                             if let Some(vol) = muted_volume.take() {
-                                // 先恢复到静音前的音量
-                                sink.set_volume(vol);
-                                execute!(stdout, SetTitle(initial_title.clone()))?;
+                                sink.set_volume(vol.min(volume_cap));
                             }
-                            adjust_volume(&sink, -VOLUME_STEP);
-                        },
-                        // 切歌：下一首
-                        KeyCode::Right => {
-                            if last_skip_time.elapsed() < MIN_SKIP_INTERVAL { continue; }
-                            if current_track_index < total_tracks.saturating_sub(1) || is_loop_enabled {
-                                sink.stop(); index_offset = 1; forced_stop = true; last_skip_time = Instant::now(); break 'inner; }
-                        }
-                        // 切歌：上一首
-                        KeyCode::Left => {
-                            if last_skip_time.elapsed() < MIN_SKIP_INTERVAL { continue; }
-                            if current_track_index > 0 || is_loop_enabled {
-                                sink.stop(); index_offset = -1; forced_stop = true; last_skip_time = Instant::now(); break 'inner; }
+                            let step = volume_step(key_event.modifiers, is_simple_mode);
+                            let delta = if key_event.code == KeyCode::Up { step } else { -step };
+                            let old_volume = sink.volume();
+                            adjust_volume(&sink, delta, volume_cap);
+                            // 这首歌已经播完，不再记忆音量，变化全部计入基准音量
+                            let target = target_volume(base_volume, &track_gain, old_volume, sink.volume());
+                            rescale_base_volume(&mut base_volume, &track_gain, target);
+                            let title_info = TitleInfo {
+                                title: &title,
+                                artist: &artist,
+                                album: &album,
+                                index: current_track_index,
+                                total: total_tracks,
+                                volume: sink.volume(),
+                                state: playback_state(&sink, muted_volume),
+                                // 这首歌已经播完
+                                position: total_duration,
+                                duration: total_duration,
+                            };
+                            update_window_title(&mut stdout, &mut window_title_shown, window_title(title_template, &title_info))?;
                         }
-                        // 退出 (Q/q 或 Ctrl+C)
                         KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('c') => {
-                            graceful_exit(&mut stdout)?;
-                            return Ok(());
+                            return graceful_exit(&mut stdout, &exit_context, &session_stats, &session_state, ExitCode::SUCCESS);
                         }
                         _ => {}
                     }
                 }
             }
-        } // 内部播放循环结束
+        }
+
+        // 移除当前歌曲（D 键）：在切歌之前从播放列表中删除，后面的歌曲前移一位，
+        // 因此向后切歌或自然播完时索引保持不变
+        let track_removed = remove_current;
+        if remove_current {
+            remove_current = false;
+            let removed = playlist.remove(current_track_index);
+            history.on_removed(current_track_index);
+            if let Some(start) = retry_start.as_mut()
+                && *start > current_track_index
+            {
+                *start -= 1;
+            }
+            if let Some(target) = jump_target.as_mut()
+                && *target > current_track_index
+            {
+                *target -= 1;
+            }
+            // 后面的歌曲已经前移一位，向后切歌时少走一步
+            if index_offset > 0 {
+                index_offset -= 1;
+            }
+            total_tracks = playlist.len();
+            session_state.playlist_hash = state::playlist_hash(&playlist);
+            preload_cache.invalidate();
+            info!(path = %removed.display(), "从播放列表中移除");
+            if let Some(file) = excluded_file_of(&args, &sources, &removed)
+                && let Err(e) = excluded::append_excluded(&file, &removed)
+            {
+                warn!(file = %file.display(), error = %e, "无法写入排除列表");
+            }
+            session_stats.removed_tracks.push((current_track_index, removed));
+            if playlist.is_empty() {
+                break 'outer;
+            }
+        }
 
         // 9. 索引更新逻辑 (处理自动播放和强制切歌)
+        skipped_by_user = forced_stop;
+        moving_backward = forced_stop && jump_target.is_none() && index_offset < 0;
         if forced_stop {
-            if index_offset > 0 {
-                current_track_index = (current_track_index + 1) % total_tracks;
-            } else if index_offset < 0 {
-                current_track_index = if current_track_index == 0 { total_tracks.saturating_sub(1) } else { current_track_index - 1 };
+            if let Some(target) = jump_target.take() {
+                current_track_index = target;
+            } else if index_offset > 0 && current_track_index + index_offset as usize >= total_tracks && loop_mode != LoopMode::Off {
+                // 越过末尾时交给主循环开头的循环检查处理，以便统计轮次；多出的部分在新一轮中继续前进
+                skip_carry = current_track_index + index_offset as usize - total_tracks;
+                current_track_index = total_tracks;
+            } else if index_offset != 0 {
+                current_track_index = skip_target(current_track_index, index_offset, total_tracks, loop_mode != LoopMode::Off);
             }
             index_offset = 0;
+            repeat_pass = 1;
             // -----------------------------------------------------------------
             // 🌟 修复：手动切歌后，必须立即启动新目标歌曲的预加载
             // -----------------------------------------------------------------
-            start_preload_if_valid(&playlist, current_track_index, &tx);
+            preload_cache.request(&playlist, current_track_index, &tx);
         } else {
-            execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
-            current_track_index += 1;
+            if !headless {
+                execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
+            }
+            if repeat_track {
+                // 不前进，主循环重新加载同一首歌
+                repeat_pass += 1;
+            } else {
+                repeat_pass = 1;
+                if !track_removed {
+                    current_track_index += 1;
+                }
+            }
         }
     } // 主循环结束 'outer
 
-    // 10. 播放列表结束后的清理工作
-    graceful_exit(&mut stdout)?;
+    // 10. 播放列表结束后的清理工作（已播完，下次 --resume 从头开始）
+    session_state.track_index = 0;
+    session_state.seek_position_secs = 0;
+    graceful_exit(&mut stdout, &exit_context, &session_stats, &session_state, exit_code)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{fixture_dir, write_fixture, write_wav_fixture};
 
-    Ok(())
-}
\ No newline at end of file
+    // 按命令行解析 list 子命令，走与 run() 相同的整理流程，返回输出的各行（路径去掉 dir 前缀）
+    fn run_list(dir: &std::path::Path, argv: &[&str]) -> Vec<String> {
+        let cli = Cli::try_parse_from(["mddplayer", "list"].iter().chain(argv)).unwrap();
+        let Some(Command::List { args, json, .. }) = cli.command else { panic!("不是 list 子命令") };
+        let prepared = prepare_playlist(&args, &args.files, None).unwrap();
+        let sources = (args.files.len() > 1).then_some(&prepared.sources);
+        let prefix = format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR);
+        list::format_playlist(&prepared.tracks, sources, json)
+            .into_iter()
+            .map(|line| line.replace(&prefix, ""))
+            .collect()
+    }
+
+    #[test]
+    fn list_subcommand_merges_inputs_and_applies_options() {
+        let dir = fixture_dir("list-subcommand");
+        for file in ["album/01.mp3", "album/02.mp3", "album/03.mp3", "extra.ogg"] {
+            write_fixture(&dir, file, "");
+        }
+        let playlist = write_fixture(&dir, "mix.m3u", "#EXTM3U\nextra.ogg\nalbum/02.mp3\n");
+        let album = dir.join("album");
+        let (album, playlist) = (album.to_str().unwrap(), playlist.to_str().unwrap());
+
+        // 两个输入：标出来源，album/02.mp3 出现两次
+        let lines = run_list(&dir, &[album, playlist]);
+        assert_eq!(lines.len(), 6, "{:?}", lines);
+        assert!(lines[0].starts_with("1. album/01.mp3  <- "), "{}", lines[0]);
+        assert!(lines[3].starts_with("4. extra.ogg  <- ") && lines[3].ends_with("mix.m3u"), "{}", lines[3]);
+        assert_eq!(lines[5], "共 5 首，不存在 0 首，扩展名不受支持 0 首。");
+
+        // --dedup 去掉重复的路径，--max-tracks 截断
+        let lines = run_list(&dir, &[album, playlist, "--dedup", "--max-tracks", "3"]);
+        let tracks: Vec<&str> = lines[..3].iter().map(|line| line.split("  <- ").next().unwrap()).collect();
+        assert_eq!(tracks, ["1. album/01.mp3", "2. album/02.mp3", "3. album/03.mp3"]);
+        assert_eq!(lines[3], "共 3 首，不存在 0 首，扩展名不受支持 0 首。");
+    }
+
+    const fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    fn test_cache() -> PreloadCache {
+        let options = LoadOptions {
+            encoding_fix: EncodingFix::Off,
+            buffer_limit: None,
+            equalizer: None,
+            min_duration: None,
+            max_duration: None,
+            loudness_normalize: false,
+            network_retry: 0,
+            announce_speech: false,
+        };
+        let mut cache = PreloadCache::new(options, HashMap::new(), false);
+        cache.wait_timeout = Duration::from_millis(50);
+        cache
+    }
+
+    fn failure(index: usize, detail: &str) -> PreloadResult {
+        PreloadResult::Failure(index, "解码错误".to_string(), format!("{}.mp3", index), detail.to_string())
+    }
+
+    #[test]
+    fn wait_for_track_skips_stale_and_other_results() {
+        let playlist: Vec<PathBuf> = (0..4).map(|index| PathBuf::from(format!("{}.mp3", index))).collect();
+        let mut cache = test_cache();
+        let (tx, rx) = channel();
+        cache.invalidate();
+        cache.pending.extend([1, 2]);
+        // 重新洗牌之前的结果（旧代数）直接丢弃，其他歌曲的结果存入缓存
+        tx.send((0, failure(1, "旧的结果"))).unwrap();
+        tx.send((1, PreloadResult::OutOfRange(2, secs(5)))).unwrap();
+        tx.send((1, failure(1, "新的结果"))).unwrap();
+        let TrackLoad::Failed(_, _, detail) = wait_for_track(&mut cache, &rx, &playlist, 1, false) else { panic!("应为加载失败") };
+        assert_eq!(detail, "新的结果");
+        assert!(!cache.pending.contains(&1));
+        // 之前收到的其他歌曲的结果从缓存中取出，不再等待
+        assert!(matches!(wait_for_track(&mut cache, &rx, &playlist, 2, false), TrackLoad::OutOfRange(duration) if duration == secs(5)));
+        assert!(cache.pending.is_empty());
+    }
+
+    #[test]
+    fn wait_for_track_times_out_and_disconnects() {
+        let playlist: Vec<PathBuf> = (0..4).map(|index| PathBuf::from(format!("{}.mp3", index))).collect();
+        let mut cache = test_cache();
+        let (tx, rx) = channel();
+        // 只收到旧代数和其他歌曲的结果：超时，之后可以重新请求这首歌
+        cache.invalidate();
+        cache.pending.insert(3);
+        tx.send((0, failure(3, "旧的结果"))).unwrap();
+        tx.send((1, failure(0, "其他歌曲"))).unwrap();
+        assert!(matches!(wait_for_track(&mut cache, &rx, &playlist, 3, false), TrackLoad::TimedOut));
+        assert!(!cache.pending.contains(&3));
+        assert!(cache.results.contains_key(&0));
+        drop(tx);
+        assert!(matches!(wait_for_track(&mut cache, &rx, &playlist, 3, false), TrackLoad::Disconnected));
+    }
+
+    #[test]
+    fn listened_time_counts_only_what_was_played() {
+        let mut stats = SessionStats::default();
+        // 播完的歌：计时略长于时长（刷新间隔）时按时长计
+        stats.record_listened(secs(181), secs(180));
+        // 播放 20 秒后切走
+        stats.record_listened(secs(20), secs(240));
+        // 加载后立即切走
+        stats.record_listened(Duration::ZERO, secs(200));
+        // 时长未知的网络流不限制
+        stats.record_listened(secs(600), Duration::ZERO);
+        assert_eq!(stats.listened, secs(180 + 20 + 600));
+    }
+
+    #[test]
+    fn playlist_progress_from_known_durations() {
+        let playlist: Vec<PathBuf> = ["a.mp3", "b.mp3", "c.mp3", "d.mp3"].iter().map(PathBuf::from).collect();
+        let mut durations: HashMap<PathBuf, Duration> = playlist.iter().map(|path| (path.clone(), secs(100))).collect();
+        let assumed = secs(240);
+        // 第一首播完，第二首播放到 30 秒
+        let progress = playlist_progress(&playlist, 1, secs(30), secs(130), &durations, assumed).unwrap();
+        assert_eq!(progress, PlaylistProgress { percent: 32, remaining: secs(70 + 200), estimated: false });
+        // 跳过第二首：收听时长只含第一首，剩余从第三首算起
+        let progress = playlist_progress(&playlist, 2, Duration::ZERO, secs(100), &durations, assumed).unwrap();
+        assert_eq!(progress, PlaylistProgress { percent: 25, remaining: secs(200), estimated: false });
+        // 重复收听超过列表总时长时不超过 100%
+        assert_eq!(playlist_progress(&playlist, 3, secs(100), secs(900), &durations, assumed).unwrap().percent, 100);
+
+        // 时长未知的歌曲按估算值计算
+        durations.remove(&playlist[3]);
+        let progress = playlist_progress(&playlist, 0, Duration::ZERO, Duration::ZERO, &durations, assumed).unwrap();
+        assert_eq!(progress, PlaylistProgress { percent: 0, remaining: secs(300 + 240), estimated: true });
+        // 已知时长的歌曲不到一半时不显示
+        durations.remove(&playlist[2]);
+        assert!(playlist_progress(&playlist, 0, Duration::ZERO, Duration::ZERO, &durations, assumed).is_some());
+        durations.remove(&playlist[1]);
+        assert!(playlist_progress(&playlist, 0, Duration::ZERO, Duration::ZERO, &durations, assumed).is_none());
+    }
+
+    // 切歌时换上的解码器：一段很短的静音
+    fn silent_track(dir: &Path, name: &str) -> TrackDecoder {
+        let path = write_wav_fixture(dir, name, 8000, 1, &[0; 800]);
+        TrackDecoder::Seeked(SeekableDecoder::open(&path, false, Duration::ZERO).unwrap())
+    }
+
+    #[test]
+    fn mute_survives_an_automatic_track_change() {
+        let dir = fixture_dir("mute-track-change");
+        let (sink, mut queue) = Sink::new_idle();
+        // 没有输出设备，由这个线程消费 sink 的输出，sink.clear() 才能等到上一首被移除
+        let draining = Arc::new(AtomicBool::new(true));
+        let drain = {
+            let draining = Arc::clone(&draining);
+            thread::spawn(move || {
+                while draining.load(Ordering::SeqCst) {
+                    queue.next();
+                }
+            })
+        };
+        let channel_mix = ChannelMix::new(false, 0.0);
+        sink.set_volume(0.6);
+        replace_track_source(&sink, silent_track(&dir, "01.wav"), &channel_mix, None);
+        sink.play();
+
+        // 播放中静音，然后上一首播完，自动切到下一首（与主循环相同：换音源后暂停的 sink 继续播放）
+        let muted_volume = Some(sink.volume());
+        sink.set_volume(0.0);
+        replace_track_source(&sink, silent_track(&dir, "02.wav"), &channel_mix, muted_volume);
+        if sink.is_paused() {
+            sink.play();
+        }
+        assert_eq!(sink.volume(), 0.0);
+        assert!(muted_volume.is_some());
+        let state = playback_state(&sink, muted_volume);
+        assert_eq!(state, PlaybackState::Muted);
+        let info = TitleInfo {
+            title: "晴天",
+            artist: "周杰伦",
+            album: "叶惠美",
+            index: 1,
+            total: 2,
+            volume: 0.0,
+            state,
+            position: Duration::ZERO,
+            duration: Duration::from_millis(100),
+        };
+        assert_eq!(window_title("{title}-{artist}", &info), "[静音]晴天-周杰伦");
+
+        // 取消静音后再切歌：音量保持不变
+        sink.set_volume(0.6);
+        replace_track_source(&sink, silent_track(&dir, "03.wav"), &channel_mix, None);
+        assert_eq!(sink.volume(), 0.6);
+        draining.store(false, Ordering::SeqCst);
+        drain.join().unwrap();
+    }
+
+    #[test]
+    fn network_open_retries_only_io_errors() {
+        let calls = std::cell::Cell::new(0);
+        let count = || calls.set(calls.get() + 1);
+        // 无法识别格式：重试也不会成功，不再请求
+        let result = retry_network_open("http://a/x.txt", 3, || false, || {
+            count();
+            Ok::<Result<(), _>, _>(Err(DecoderError::UnrecognizedFormat))
+        });
+        assert!(matches!(result, Ok(Err(DecoderError::UnrecognizedFormat))));
+        assert_eq!(calls.get(), 1);
+        // 连接失败和读取中断：重试到成功为止
+        calls.set(0);
+        let result = retry_network_open("http://a/x.mp3", 3, || false, || {
+            count();
+            match calls.get() {
+                1 => Err(io::Error::other("连接被重置")),
+                2 => Ok(Err(DecoderError::IoError("读取中断".to_string()))),
+                _ => Ok(Ok(())),
+            }
+        });
+        assert!(matches!(result, Ok(Ok(()))));
+        assert_eq!(calls.get(), 3);
+        // 超过重试次数：返回最后一次的错误
+        calls.set(0);
+        let result = retry_network_open("http://a/x.mp3", 1, || false, || {
+            count();
+            Err::<Result<(), DecoderError>, _>(io::Error::other("连接超时"))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn network_retry_stops_when_cancelled() {
+        let calls = std::cell::Cell::new(0);
+        let result = retry_network_open("http://a/x.mp3", 3, || true, || {
+            calls.set(calls.get() + 1);
+            Err::<Result<(), DecoderError>, _>(io::Error::other("连接超时"))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+        // 等待中途被取消时不用等完整个间隔
+        let start = Instant::now();
+        let cancel_at = start + Duration::from_millis(30);
+        assert!(!sleep_unless_cancelled(Duration::from_secs(5), &|| Instant::now() >= cancel_at));
+        assert!(start.elapsed() < Duration::from_secs(1), "{:?}", start.elapsed());
+        assert!(sleep_unless_cancelled(Duration::from_millis(30), &|| false));
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+}
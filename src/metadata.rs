@@ -1,38 +1,46 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 // 引入 lofty 库的 Trait 和函数
-use lofty::prelude::TaggedFileExt; 
+use lofty::prelude::{AudioFile, TaggedFileExt};
 use lofty::read_from_path; 
 // 添加 Accessor Trait
-use lofty::tag::Accessor;
+use lofty::tag::{Accessor, ItemKey};
 // 引入 symphonia 库的格式和元数据选项
 use symphonia::core::{
-    formats::FormatOptions, meta::MetadataOptions, probe::Hint,
+    codecs::CODEC_TYPE_NULL, formats::FormatOptions, meta::MetadataOptions, probe::Hint,
     io::{MediaSource, MediaSourceStream},
 };
+use crate::utils::{natural_cmp, split_artist_title, stream_url};
+use crate::cli::{EncodingFix, GroupBy};
+use rand::Rng;
+use rand::seq::SliceRandom;
+// 引入 encoding_rs 库用于修复 GBK/Big5 等旧编码的标签
+use encoding_rs::{Encoding, BIG5, GBK, SHIFT_JIS};
 
 /// 安全地获取标题和艺术家信息，优先使用主标签。
+/// fix 指定是否尝试修复被误当作 latin-1 读取的 GBK/Big5 等旧编码标签。
 /// 返回 (title, artist)
-pub fn get_title_artist_info(path: &Path) -> (String, String) {
+pub fn get_title_artist_info(path: &Path, fix: EncodingFix) -> (String, String) {
     // 尝试从路径读取 tagged file
     match read_from_path(path) { 
         Ok(tagged_file) => {
             // 获取文件的主要标签（如 ID3v2, Vorbis Comment 等）
             if let Some(tag) = tagged_file.primary_tag() {
                 
-                // 获取标题，使用 and_then 链式调用
+                // 获取标题
                 let title = tag.title()
-                    // 闭包返回 Some(String)，以满足 and_then 对 Option<U> 的要求
-                    .and_then(|t| Some(t.to_string())) 
+                    .map(|t| t.to_string())
                     .unwrap_or_else(|| "未知音乐名".to_string());
                 
                 // 获取艺术家
                 let artist = tag.artist()
-                    // 闭包返回 Some(String)，以满足 and_then 对 Option<U> 的要求
-                    .and_then(|a| Some(a.to_string())) 
+                    .map(|a| a.to_string())
                     .unwrap_or_else(|| "未知作者".to_string());
 
-                return (title, artist);
+                return (repair_encoding(&title, fix), repair_encoding(&artist, fix));
             }
         },
         Err(_) => {
@@ -43,8 +51,283 @@ pub fn get_title_artist_info(path: &Path) -> (String, String) {
     ("未知".to_string(), "未知".to_string())
 }
 
+/// 获取显示用的标题和艺术家：嵌入标签 > 播放列表的显示名（#EXTINF）> 文件名，
+/// 显示名和文件名为 "艺术家 - 标题" 格式时拆开，每个字段分别回退。返回 (title, artist)
+pub fn resolve_title_artist(path: &Path, fix: EncodingFix, display_name: Option<&str>) -> (String, String) {
+    let (title, artist) = get_title_artist_info(path, fix);
+    let (hint_artist, hint_title) = display_name.map_or((None, ""), split_artist_title);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let (stem_artist, stem_title) = split_artist_title(&stem);
+
+    let title = if title == "未知" || title == "未知音乐名" {
+        [hint_title, stem_title].into_iter().find(|t| !t.is_empty()).map_or(title, str::to_string)
+    } else {
+        title
+    };
+    let artist = if artist == "未知" || artist == "未知作者" {
+        hint_artist.or(stem_artist).map_or(artist, str::to_string)
+    } else {
+        artist
+    };
+    (title, artist)
+}
+
+/// 获取总时长：解码器探测不到时使用播放列表（#EXTINF）提供的时长，都没有时为 0
+pub fn resolve_total_duration(path: &Path, duration_hint: Option<Duration>) -> Duration {
+    let duration = get_total_duration(path);
+    // 网络流按时长未知处理（不显示进度、不检查提前结束）
+    if duration == Duration::MAX {
+        return duration_hint.unwrap_or_default();
+    }
+    if !duration.is_zero() {
+        return duration;
+    }
+    duration_hint.unwrap_or(duration)
+}
+
+/// 修复乱码标签：旧的中文 MP3 常把 GBK/Big5 字节声明为 latin-1，读出来是 "ÖÜ½ÜÂ×" 这样的乱码。
+/// 仅当字符串全部位于 U+0000–U+00FF 且大部分是高位字符时，才把字符还原为原始字节重新解码；
+/// auto 模式依次尝试 GBK、Big5、Shift-JIS，选择中日文字符比例最高的结果。
+pub fn repair_encoding(text: &str, fix: EncodingFix) -> String {
+    let candidates: &[&'static Encoding] = match fix {
+        EncodingFix::Off => return text.to_string(),
+        EncodingFix::Auto => &[GBK, BIG5, SHIFT_JIS],
+        EncodingFix::Gbk => &[GBK],
+        EncodingFix::Big5 => &[BIG5],
+    };
+    if text.chars().any(|c| c as u32 > 0xFF) {
+        return text.to_string();
+    }
+    let high_count = text.chars().filter(|&c| c as u32 >= 0x80).count();
+    if high_count == 0 || high_count * 2 < text.chars().filter(|c| !c.is_ascii_whitespace()).count() {
+        return text.to_string();
+    }
+
+    let bytes: Vec<u8> = text.chars().map(|c| c as u8).collect();
+    let mut best: Option<(f64, String)> = None;
+    for encoding in candidates {
+        let Some(decoded) = encoding.decode_without_bom_handling_and_without_replacement(&bytes) else {
+            continue;
+        };
+        let score = cjk_ratio(&decoded);
+        if best.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+            best = Some((score, decoded.into_owned()));
+        }
+    }
+    match best {
+        // 中日文字符过少时说明猜错了编码，保留原文
+        Some((score, decoded)) if score >= 0.5 => decoded,
+        _ => text.to_string(),
+    }
+}
+
+// 非 ASCII 字符中，中日文字符（汉字、假名、全角标点）所占比例
+fn cjk_ratio(text: &str) -> f64 {
+    let non_ascii: Vec<char> = text.chars().filter(|c| !c.is_ascii()).collect();
+    if non_ascii.is_empty() {
+        return 0.0;
+    }
+    let cjk = non_ascii
+        .iter()
+        .filter(|&&c| matches!(c as u32, 0x3000..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xFF00..=0xFFEF))
+        .count();
+    cjk as f64 / non_ascii.len() as f64
+}
+
+/// 读取碟号和音轨号，返回 (disc, track)，缺失的字段为 None。
+pub fn get_disc_track_info(path: &Path) -> (Option<u32>, Option<u32>) {
+    match read_from_path(path) {
+        Ok(tagged_file) => match tagged_file.primary_tag() {
+            Some(tag) => (tag.disk(), tag.track()),
+            None => (None, None),
+        },
+        Err(_) => (None, None),
+    }
+}
+
+/// 按碟号、音轨号排序播放列表。
+/// 有音轨号的排在前面（缺少碟号视为第 1 碟），没有音轨号的按文件名自然顺序排在后面。
+pub fn sort_by_track_number(playlist: &mut Vec<PathBuf>) {
+    sort_by_disc_track(playlist, |path| {
+        let (disc, track) = get_disc_track_info(path);
+        track.map(|t| (disc.unwrap_or(1), t))
+    });
+}
+
+// 按 disc_track 给出的 (碟号, 音轨号) 排序，没有的按文件名自然顺序排在后面
+fn sort_by_disc_track(playlist: &mut Vec<PathBuf>, disc_track: impl Fn(&Path) -> Option<(u32, u32)>) {
+    let mut keyed: Vec<(Option<(u32, u32)>, PathBuf)> = playlist
+        .drain(..)
+        .map(|path| (disc_track(&path), path))
+        .collect();
+    keyed.sort_by(|(a_key, a_path), (b_key, b_path)| {
+        let by_tag = match (a_key, b_key) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        };
+        by_tag.then_with(|| natural_cmp(&a_path.to_string_lossy(), &b_path.to_string_lossy()))
+    });
+    playlist.extend(keyed.into_iter().map(|(_, path)| path));
+}
+
+/// 按专辑整理播放列表：专辑按名称排序，专辑内按碟号、音轨号排序（缺少碟号视为第 1 碟）。
+/// 排序是稳定的：没有音轨号的歌曲排在专辑的最后并保持原来的顺序，没有专辑标签的歌曲同样保持原顺序排在最后。
+pub fn sort_by_album(playlist: &mut Vec<PathBuf>, fix: EncodingFix) {
+    let mut keyed: Vec<(AlbumKey, PathBuf)> = playlist
+        .drain(..)
+        .map(|path| {
+            let tagged_file = read_from_path(&path).ok();
+            let tag = tagged_file.as_ref().and_then(|file| file.primary_tag());
+            let album = tag
+                .and_then(|t| t.album())
+                .map(|album| album.trim().to_string())
+                .filter(|album| !album.is_empty())
+                .map(|album| repair_encoding(&album, fix));
+            let track = tag.and_then(|t| t.track().map(|track| (t.disk().unwrap_or(1), track)));
+            ((album, track), path)
+        })
+        .collect();
+    keyed.sort_by(|((a_album, a_track), _), ((b_album, b_track), _)| {
+        let by_album = match (a_album, b_album) {
+            (Some(a), Some(b)) => natural_cmp(a, b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        };
+        by_album.then_with(|| match (a_track, b_track) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        })
+    });
+    playlist.extend(keyed.into_iter().map(|(_, path)| path));
+}
+
+// 按专辑排序时的键：(专辑名, (碟号, 音轨号))
+type AlbumKey = (Option<String>, Option<(u32, u32)>);
+
+/// 读取专辑标签（按 fix 修复编码），没有标签或专辑为空时返回 None
+pub fn get_album(path: &Path, fix: EncodingFix) -> Option<String> {
+    let tagged_file = read_from_path(path).ok()?;
+    let tag = tagged_file.primary_tag()?;
+    let album = tag.album()?;
+    let album = album.trim();
+    (!album.is_empty()).then(|| repair_encoding(album, fix))
+}
+
+/// --group-by 分组时用到的标签。启动时读取一次，之后每轮重新分组时直接使用，不再读取文件
+#[derive(Clone, Default)]
+pub struct GroupTags {
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    /// (碟号, 音轨号)，缺少碟号视为第 1 碟
+    pub disc_track: Option<(u32, u32)>,
+}
+
+/// 读取分组用的专辑、专辑艺术家（按 fix 修复编码）和碟号、音轨号，读取失败时各项为 None
+pub fn read_group_tags(path: &Path, fix: EncodingFix) -> GroupTags {
+    let Ok(tagged_file) = read_from_path(path) else { return GroupTags::default() };
+    let Some(tag) = tagged_file.primary_tag() else { return GroupTags::default() };
+    let text = |value: Option<&str>| value.map(str::trim).filter(|value| !value.is_empty()).map(|value| repair_encoding(value, fix));
+    GroupTags {
+        album: text(tag.album().as_deref()),
+        album_artist: text(tag.get_string(&ItemKey::AlbumArtist)),
+        disc_track: tag.track().map(|track| (tag.disk().unwrap_or(1), track)),
+    }
+}
+
+/// 计算歌曲所属的分组：album 模式使用专辑艺术家和专辑名（没有专辑艺术家时用所在目录区分同名专辑），
+/// 没有专辑标签时（以及 dir 模式）使用所在目录
+pub fn group_key(path: &Path, tags: &GroupTags, group_by: GroupBy) -> String {
+    let dir = path.parent().unwrap_or(Path::new("")).to_string_lossy();
+    match (group_by, &tags.album) {
+        (GroupBy::Album, Some(album)) => match &tags.album_artist {
+            Some(album_artist) => format!("album:{}\u{0}{}", album_artist, album),
+            None => format!("album:{}\u{0}{}", dir, album),
+        },
+        _ => format!("dir:{}", dir),
+    }
+}
+
+/// 按专辑或目录分组整理播放列表：组内按碟号、音轨号（没有的按文件名）排序，
+/// 传入 rng 时打乱各组的顺序，否则各组按首次出现的顺序排列。
+/// 标签取自 tags（read_group_tags 的结果），不在其中的歌曲按没有标签处理
+pub fn group_playlist<R: Rng>(playlist: Vec<PathBuf>, group_by: GroupBy, tags: &HashMap<PathBuf, GroupTags>, rng: Option<&mut R>) -> Vec<PathBuf> {
+    let no_tags = GroupTags::default();
+    let tags_of = |path: &Path| tags.get(path).unwrap_or(&no_tags);
+    let mut groups: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    for path in playlist {
+        let key = group_key(&path, tags_of(&path), group_by);
+        match groups.iter_mut().find(|(group, _)| *group == key) {
+            Some((_, tracks)) => tracks.push(path),
+            None => groups.push((key, vec![path])),
+        }
+    }
+    for (_, tracks) in groups.iter_mut() {
+        sort_by_disc_track(tracks, |path| tags_of(path).disc_track);
+    }
+    if let Some(rng) = rng {
+        groups.shuffle(rng);
+    }
+    groups.into_iter().flat_map(|(_, tracks)| tracks).collect()
+}
+
+/// 时长是否在 [min, max] 范围内；时长未知（为 0）和网络流（Duration::MAX）无法判断，视为在范围内
+pub fn duration_in_range(duration: Duration, min: Option<Duration>, max: Option<Duration>) -> bool {
+    duration.is_zero()
+        || duration == Duration::MAX
+        || (min.is_none_or(|min| duration >= min) && max.is_none_or(|max| duration <= max))
+}
+
+/// 按时长过滤播放列表，只保留时长在 [min, max] 范围内的歌曲（见 duration_in_range）。
+/// 需要逐个探测时长，过滤进度输出到标准错误。
+pub fn filter_by_duration(playlist: Vec<PathBuf>, min: Option<Duration>, max: Option<Duration>) -> Vec<PathBuf> {
+    let total = playlist.len();
+    let mut kept = Vec::with_capacity(total);
+    for (index, path) in playlist.into_iter().enumerate() {
+        eprint!("\r过滤中... {}/{}", index + 1, total);
+        if duration_in_range(get_total_duration(&path), min, max) {
+            kept.push(path);
+        }
+    }
+    eprintln!();
+    kept
+}
+
+/// 在后台线程中依次探测播放列表中每首歌的时长（--playlist-progress），探测到一首就发送一首。
+/// 网络流和探测不到时长的歌曲不发送；接收端关闭后线程提前结束
+pub fn spawn_duration_probe(playlist: Vec<PathBuf>) -> std::sync::mpsc::Receiver<(PathBuf, Duration)> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for path in playlist {
+            let duration = get_total_duration(&path);
+            if duration.is_zero() || duration == Duration::MAX {
+                continue;
+            }
+            if tx.send((path, duration)).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
 /// 使用 symphonia 库，通过探测媒体流来获取音频文件的总时长。
+/// .opus 文件先尝试只读取首尾两页的快速方法，失败时再完整探测。
+/// 网络流没有固定的时长，直接返回 Duration::MAX。
 pub fn get_total_duration(path: &Path) -> Duration {
+    if stream_url(path).is_some() {
+        return Duration::MAX;
+    }
+    if path.extension().and_then(|s| s.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("opus"))
+        && let Some(duration) = get_opus_duration_fast(path)
+    {
+        return duration;
+    }
+
     // 尝试打开文件并创建 MediaSource
     let source = match std::fs::File::open(path) {
         Ok(file) => Box::new(file) as Box<dyn MediaSource>,
@@ -72,13 +355,369 @@ pub fn get_total_duration(path: &Path) -> Duration {
     };
     
     // 从默认音轨参数中计算总秒数
-    if let Some(track) = probe_result.format.default_track() {
-        if let (Some(n_frames), Some(sample_rate)) = (track.codec_params.n_frames, track.codec_params.sample_rate) {
-            // 计算总秒数: (总帧数 / 采样率)
-            let seconds = (n_frames as f64) / (sample_rate as f64);
-            return Duration::from_secs_f64(seconds);
-        }
+    if let Some(track) = probe_result.format.default_track()
+        && let (Some(n_frames), Some(sample_rate)) = (track.codec_params.n_frames, track.codec_params.sample_rate)
+    {
+        // 计算总秒数: (总帧数 / 采样率)
+        let seconds = (n_frames as f64) / (sample_rate as f64);
+        return Duration::from_secs_f64(seconds);
     }
     
     Duration::from_secs(0)
-}
\ No newline at end of file
+}
+
+// 在文件末尾读取的字节数，最后一个 Ogg 页通常在这个范围内
+const OGG_TAIL_BYTES: u64 = 4096;
+
+/// 快速读取 Opus 文件的时长：最后一个 Ogg 页的 granule position 是解码后的总采样数（Opus 内部固定 48kHz），
+/// 减去第一页 OpusHead 中的 pre-skip 即为时长。只读取文件开头和最后 4096 字节，找不到时返回 None。
+pub fn get_opus_duration_fast(path: &Path) -> Option<Duration> {
+    let mut file = File::open(path).ok()?;
+    // 1. 第一页：OpusHead 中的 pre-skip，以及逻辑流的序列号
+    let mut head = [0u8; 27 + 255 + 19];
+    let head_len = read_up_to(&mut file, &mut head)?;
+    let head = &head[..head_len];
+    if head.len() < 27 || &head[0..4] != b"OggS" {
+        return None;
+    }
+    let serial = &head[14..18];
+    let packet_start = 27 + head[26] as usize;
+    let opus_head = head.get(packet_start..packet_start + 19)?;
+    if &opus_head[0..8] != b"OpusHead" {
+        return None;
+    }
+    let pre_skip = u16::from_le_bytes([opus_head[10], opus_head[11]]) as u64;
+
+    // 2. 从文件末尾向前查找属于同一逻辑流、granule position 有效的最后一页
+    let file_len = file.metadata().ok()?.len();
+    let tail_start = file_len.saturating_sub(OGG_TAIL_BYTES);
+    file.seek(SeekFrom::Start(tail_start)).ok()?;
+    let mut tail = Vec::with_capacity(OGG_TAIL_BYTES as usize);
+    file.read_to_end(&mut tail).ok()?;
+    let granule = (0..tail.len().saturating_sub(27)).rev().find_map(|i| {
+        let page = &tail[i..i + 27];
+        if &page[0..4] != b"OggS" || page[4] != 0 || &page[14..18] != serial {
+            return None;
+        }
+        let granule = i64::from_le_bytes(page[6..14].try_into().ok()?);
+        // -1 表示这一页没有结束任何数据包
+        (granule >= 0).then_some(granule as u64)
+    })?;
+    let samples = granule.checked_sub(pre_skip)?;
+    Some(Duration::from_secs_f64(samples as f64 / 48000.0))
+}
+
+// 尽量读满缓冲区，文件较短时返回实际读取的字节数
+fn read_up_to(file: &mut File, buffer: &mut [u8]) -> Option<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]).ok()? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Some(filled)
+}
+
+/// lofty 读取到的专辑、封面和音频属性（info 子命令使用，标题和艺术家见 get_title_artist_info）
+pub struct TagDetails {
+    pub file_type: String,
+    pub album: Option<String>,
+    pub duration: Duration,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    /// 码率（kbps）
+    pub bitrate: Option<u32>,
+    /// 第一张内嵌封面的字节数，没有封面时为 None
+    pub cover_size: Option<usize>,
+}
+
+/// 用 lofty 读取专辑、封面和音频属性，专辑名按 fix 修复编码
+pub fn read_tag_details(path: &Path, fix: EncodingFix) -> Result<TagDetails, lofty::error::LoftyError> {
+    let tagged_file = read_from_path(path)?;
+    let properties = tagged_file.properties();
+    let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag());
+    Ok(TagDetails {
+        file_type: format!("{:?}", tagged_file.file_type()),
+        album: tag.and_then(|t| t.album()).map(|album| repair_encoding(&album, fix)),
+        duration: properties.duration(),
+        sample_rate: properties.sample_rate(),
+        channels: properties.channels(),
+        bitrate: properties.audio_bitrate().or(properties.overall_bitrate()),
+        cover_size: tag.and_then(|t| t.pictures().first()).map(|picture| picture.data().len()),
+    })
+}
+
+/// 导出元数据（--export-metadata）时每首歌的完整信息，读不到的字段为 None
+pub struct TrackMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    pub duration: Duration,
+    /// 码率（kbps）
+    pub bitrate: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+}
+
+/// 读取一首歌的全部标签和音频属性，文本字段按 fix 修复编码；lofty 读不到时长的用 symphonia 探测
+pub fn get_all_metadata(path: &Path, fix: EncodingFix) -> TrackMetadata {
+    let tagged_file = read_from_path(path).ok();
+    let properties = tagged_file.as_ref().map(|file| file.properties());
+    let tag = tagged_file.as_ref().and_then(|file| file.primary_tag().or_else(|| file.first_tag()));
+    let text = |value: Option<std::borrow::Cow<'_, str>>| {
+        value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).map(|v| repair_encoding(&v, fix))
+    };
+    let duration = properties.map(|p| p.duration()).filter(|d| !d.is_zero()).unwrap_or_else(|| get_total_duration(path));
+    // 网络流的时长未知
+    let duration = if duration == Duration::MAX { Duration::ZERO } else { duration };
+    TrackMetadata {
+        title: text(tag.and_then(|t| t.title())),
+        artist: text(tag.and_then(|t| t.artist())),
+        album: text(tag.and_then(|t| t.album())),
+        year: tag.and_then(|t| t.year()),
+        genre: text(tag.and_then(|t| t.genre())),
+        track: tag.and_then(|t| t.track()),
+        disc: tag.and_then(|t| t.disk()),
+        duration,
+        bitrate: properties.and_then(|p| p.audio_bitrate().or(p.overall_bitrate())),
+        sample_rate: properties.and_then(|p| p.sample_rate()),
+        channels: properties.and_then(|p| p.channels()),
+    }
+}
+
+/// symphonia 探测到的音频流属性
+pub struct StreamProperties {
+    pub codec: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<usize>,
+    pub bits_per_sample: Option<u32>,
+}
+
+/// 快速检查文件能否被识别为音频：只读取容器头部探测格式和音轨，不解码。
+/// 文件必须可以定位：moov 在 mdat 之后的 M4A/MP4 要跳到文件末尾才能读到音轨信息
+pub fn quick_probe(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let media_source_stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probe_result = symphonia::default::get_probe().format(
+        &hint,
+        media_source_stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    probe_result.format.default_track().ok_or("未找到音轨")?;
+    Ok(())
+}
+
+/// 用 symphonia 探测文件格式和默认音轨的编码参数（不解码音频数据）
+pub fn probe_properties(path: &Path) -> Result<StreamProperties, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path)?;
+    let media_source_stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probe_result = symphonia::default::get_probe().format(
+        &hint,
+        media_source_stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let track = probe_result.format.default_track().ok_or("未找到音轨")?;
+    let params = &track.codec_params;
+    let codec = if params.codec == CODEC_TYPE_NULL {
+        "未知".to_string()
+    } else {
+        symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .map_or_else(|| format!("{}", params.codec), |descriptor| descriptor.short_name.to_string())
+    };
+    Ok(StreamProperties {
+        codec,
+        sample_rate: params.sample_rate,
+        channels: params.channels.map(|channels| channels.count()),
+        bits_per_sample: params.bits_per_sample,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{fixture_dir, write_fixture, write_tagged_mp3, write_wav_fixture};
+
+    #[test]
+    fn quick_probe_checks_the_container() {
+        let dir = fixture_dir("quick-probe");
+        assert!(quick_probe(&write_wav_fixture(&dir, "tone.wav", 8000, 1, &[0; 800])).is_ok());
+        assert!(quick_probe(&write_fixture(&dir, "fake.mp3", "not audio")).is_err());
+        assert!(quick_probe(&dir.join("missing.mp3")).is_err());
+    }
+
+    // 按 latin-1 读出的标签：每个字节成为 U+0000–U+00FF 中的一个字符
+    fn misread_as_latin1(bytes: &[u8]) -> String {
+        bytes.iter().map(|&b| b as char).collect()
+    }
+
+    // GBK 编码的 "周杰伦"、"王菲 - 红豆"，Big5 编码的 "周杰倫"、"東風破"
+    const GBK_JAY: &[u8] = &[0xD6, 0xDC, 0xBD, 0xDC, 0xC2, 0xD7];
+    const GBK_FAYE: &[u8] = &[0xCD, 0xF5, 0xB7, 0xC6, 0x20, 0x2D, 0x20, 0xBA, 0xEC, 0xB6, 0xB9];
+    const BIG5_JAY: &[u8] = &[0xA9, 0x50, 0xAA, 0x4E, 0xAD, 0xDB];
+    const BIG5_EAST_WIND: &[u8] = &[0xAA, 0x46, 0xAD, 0xB7, 0xAF, 0x7D];
+
+    #[test]
+    fn gbk_and_big5_mojibake_is_repaired() {
+        assert_eq!(misread_as_latin1(GBK_JAY), "ÖÜ½ÜÂ×");
+        let cases = [
+            (GBK_JAY, EncodingFix::Auto, "周杰伦"),
+            (GBK_FAYE, EncodingFix::Auto, "王菲 - 红豆"),
+            (BIG5_JAY, EncodingFix::Auto, "周杰倫"),
+            (BIG5_EAST_WIND, EncodingFix::Auto, "東風破"),
+            (GBK_JAY, EncodingFix::Gbk, "周杰伦"),
+            (GBK_FAYE, EncodingFix::Gbk, "王菲 - 红豆"),
+            (BIG5_JAY, EncodingFix::Big5, "周杰倫"),
+            (BIG5_EAST_WIND, EncodingFix::Big5, "東風破"),
+        ];
+        for (bytes, fix, expected) in cases {
+            assert_eq!(repair_encoding(&misread_as_latin1(bytes), fix), expected, "{:?}", fix);
+        }
+    }
+
+    #[test]
+    fn mojibake_is_kept_when_repair_is_off() {
+        for bytes in [GBK_JAY, GBK_FAYE, BIG5_JAY, BIG5_EAST_WIND] {
+            let text = misread_as_latin1(bytes);
+            assert_eq!(repair_encoding(&text, EncodingFix::Off), text);
+        }
+    }
+
+    #[test]
+    fn correct_utf8_tags_are_left_unchanged() {
+        for text in ["周杰伦", "東風破", "王菲 - 红豆", "Beyoncé", "Sigur Rós", "Motörhead", "Café Tacvba", "Mötley Crüe", "Ñandú", "AC/DC", ""] {
+            for fix in [EncodingFix::Auto, EncodingFix::Gbk, EncodingFix::Big5] {
+                assert_eq!(repair_encoding(text, fix), text, "{:?} {:?}", text, fix);
+            }
+        }
+    }
+
+    #[test]
+    fn embedded_tags_win_over_extinf_and_file_name() {
+        let dir = fixture_dir("resolve-tags");
+        let path = write_tagged_mp3(&dir, "文件艺术家 - 文件标题.mp3", &[("TIT2", "标签标题"), ("TPE1", "标签艺术家")]);
+        let resolved = resolve_title_artist(&path, EncodingFix::Off, Some("列表艺术家 - 列表标题"));
+        assert_eq!(resolved, ("标签标题".to_string(), "标签艺术家".to_string()));
+    }
+
+    #[test]
+    fn missing_tag_fields_fall_back_separately() {
+        let dir = fixture_dir("resolve-partial");
+        // 只有标题标签：艺术家取自 #EXTINF
+        let path = write_tagged_mp3(&dir, "文件艺术家 - 文件标题.mp3", &[("TIT2", "标签标题")]);
+        let resolved = resolve_title_artist(&path, EncodingFix::Off, Some("列表艺术家 - 列表标题"));
+        assert_eq!(resolved, ("标签标题".to_string(), "列表艺术家".to_string()));
+        // 显示名没有 "艺术家 - " 前缀：标题取自显示名，艺术家取自文件名
+        let path = write_fixture(&dir, "文件艺术家 - 文件标题.flac", "");
+        let resolved = resolve_title_artist(&path, EncodingFix::Off, Some("列表标题"));
+        assert_eq!(resolved, ("列表标题".to_string(), "文件艺术家".to_string()));
+    }
+
+    #[test]
+    fn untagged_file_uses_extinf_then_file_name() {
+        let dir = fixture_dir("resolve-untagged");
+        let path = write_fixture(&dir, "文件艺术家 - 文件标题.mp3", "");
+        let resolved = resolve_title_artist(&path, EncodingFix::Off, Some("列表艺术家 - 列表标题"));
+        assert_eq!(resolved, ("列表标题".to_string(), "列表艺术家".to_string()));
+        let resolved = resolve_title_artist(&path, EncodingFix::Off, None);
+        assert_eq!(resolved, ("文件标题".to_string(), "文件艺术家".to_string()));
+        // 文件名不是 "艺术家 - 标题" 格式：整个文件名作为标题，艺术家未知
+        let path = write_fixture(&dir, "track01.mp3", "");
+        assert_eq!(resolve_title_artist(&path, EncodingFix::Off, None), ("track01".to_string(), "未知".to_string()));
+    }
+
+    fn tags(album: Option<&str>, album_artist: Option<&str>, disc_track: Option<(u32, u32)>) -> GroupTags {
+        GroupTags { album: album.map(str::to_string), album_artist: album_artist.map(str::to_string), disc_track }
+    }
+
+    #[test]
+    fn group_key_separates_same_album_by_album_artist() {
+        let hits = |artist| tags(Some("Greatest Hits"), Some(artist), None);
+        let a = group_key(Path::new("/music/a/01.mp3"), &hits("Queen"), GroupBy::Album);
+        let b = group_key(Path::new("/music/a/02.mp3"), &hits("ABBA"), GroupBy::Album);
+        assert_ne!(a, b);
+        // 同一专辑艺术家的同名专辑即使分散在不同目录也归为一组
+        assert_eq!(a, group_key(Path::new("/music/b/03.mp3"), &hits("Queen"), GroupBy::Album));
+    }
+
+    #[test]
+    fn group_key_falls_back_to_directory() {
+        // 没有专辑艺术家：同名专辑按目录区分
+        let untagged_artist = tags(Some("Greatest Hits"), None, None);
+        assert_ne!(
+            group_key(Path::new("/music/a/01.mp3"), &untagged_artist, GroupBy::Album),
+            group_key(Path::new("/music/b/01.mp3"), &untagged_artist, GroupBy::Album)
+        );
+        // 没有专辑标签，以及 dir 模式：只看目录
+        let no_album = group_key(Path::new("/music/a/01.mp3"), &GroupTags::default(), GroupBy::Album);
+        assert_eq!(no_album, group_key(Path::new("/music/a/02.mp3"), &tags(Some("X"), Some("Y"), None), GroupBy::Dir));
+        assert_ne!(no_album, group_key(Path::new("/music/b/01.mp3"), &GroupTags::default(), GroupBy::Album));
+    }
+
+    #[test]
+    fn reads_album_artist_and_track_for_grouping() {
+        let dir = fixture_dir("group-tags");
+        let path = write_tagged_mp3(&dir, "01.mp3", &[("TALB", " Greatest Hits "), ("TPE2", "Queen"), ("TRCK", "3/12")]);
+        let read = read_group_tags(&path, EncodingFix::Off);
+        assert_eq!(read.album.as_deref(), Some("Greatest Hits"));
+        assert_eq!(read.album_artist.as_deref(), Some("Queen"));
+        assert_eq!(read.disc_track, Some((1, 3)));
+        let untagged = read_group_tags(&write_fixture(&dir, "02.mp3", ""), EncodingFix::Off);
+        assert!(untagged.album.is_none() && untagged.album_artist.is_none() && untagged.disc_track.is_none());
+    }
+
+    #[test]
+    fn sort_by_track_orders_discs_then_tracks_then_untagged_names() {
+        let dir = fixture_dir("sort-track");
+        let disc2_track1 = write_tagged_mp3(&dir, "a.mp3", &[("TPOS", "2/2"), ("TRCK", "1")]);
+        let disc1_track10 = write_tagged_mp3(&dir, "b.mp3", &[("TPOS", "1"), ("TRCK", "10/12")]);
+        let disc1_track2 = write_tagged_mp3(&dir, "c.mp3", &[("TPOS", "1"), ("TRCK", "2")]);
+        // 没有碟号视为第 1 碟
+        let no_disc_track1 = write_tagged_mp3(&dir, "d.mp3", &[("TRCK", "01")]);
+        // 没有音轨号（包括只有碟号）的按文件名自然顺序排在最后
+        let disc_only = write_tagged_mp3(&dir, "Track 3.mp3", &[("TPOS", "1")]);
+        let untagged_10 = write_fixture(&dir, "Track 10.flac", "");
+        let untagged_2 = write_fixture(&dir, "track 2.flac", "");
+        let mut playlist = vec![
+            untagged_10.clone(),
+            disc2_track1.clone(),
+            disc_only.clone(),
+            disc1_track10.clone(),
+            untagged_2.clone(),
+            no_disc_track1.clone(),
+            disc1_track2.clone(),
+        ];
+        sort_by_track_number(&mut playlist);
+        assert_eq!(playlist, [no_disc_track1, disc1_track2, disc1_track10, disc2_track1, untagged_2, disc_only, untagged_10]);
+    }
+
+    #[test]
+    fn group_playlist_uses_cached_tags() {
+        // 文件都不存在：分组和组内顺序只能来自传入的标签
+        let path = |name: &str| PathBuf::from(format!("/nonexistent/{}", name));
+        let cached = HashMap::from([
+            (path("a2.mp3"), tags(Some("A"), Some("X"), Some((1, 2)))),
+            (path("b1.mp3"), tags(Some("B"), Some("X"), Some((1, 1)))),
+            (path("a1.mp3"), tags(Some("A"), Some("X"), Some((1, 1)))),
+            (path("a3.mp3"), tags(Some("A"), Some("X"), Some((2, 1)))),
+        ]);
+        let playlist = vec![path("a2.mp3"), path("b1.mp3"), path("a3.mp3"), path("a1.mp3")];
+        let grouped = group_playlist(playlist, GroupBy::Album, &cached, None::<&mut rand::rngs::ThreadRng>);
+        assert_eq!(grouped, [path("a1.mp3"), path("a2.mp3"), path("a3.mp3"), path("b1.mp3")]);
+    }
+}
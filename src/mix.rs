@@ -0,0 +1,261 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rodio::Source;
+
+/// 声道设置：--mono 单声道混音，以及播放中可用 </> 调整的左右平衡。
+/// 克隆后共享同一个平衡值，调整对正在播放的歌曲立即生效，并保持到之后的歌曲
+#[derive(Clone)]
+pub struct ChannelMix {
+    mono: bool,
+    // 平衡值 (-1.0 全左 … 1.0 全右) 的 f32 位模式
+    balance: Arc<AtomicU32>,
+}
+
+impl ChannelMix {
+    pub fn new(mono: bool, balance: f32) -> ChannelMix {
+        ChannelMix { mono, balance: Arc::new(AtomicU32::new(balance.clamp(-1.0, 1.0).to_bits())) }
+    }
+
+    pub fn balance(&self) -> f32 {
+        f32::from_bits(self.balance.load(Ordering::Relaxed))
+    }
+
+    /// 设置平衡值（限制在 -1.0 到 1.0 之间），返回实际设置的值
+    pub fn set_balance(&self, balance: f32) -> f32 {
+        // 按 0.1 的步进调整时消除浮点误差，保证能回到正中
+        let balance = (balance.clamp(-1.0, 1.0) * 100.0).round() / 100.0;
+        self.balance.store(balance.to_bits(), Ordering::Relaxed);
+        balance
+    }
+
+    /// 左、右声道的增益：偏向一侧时只衰减另一侧
+    fn gains(&self) -> (f32, f32) {
+        let balance = self.balance();
+        ((1.0 - balance).min(1.0), (1.0 + balance).min(1.0))
+    }
+}
+
+/// 平衡值的提示文字，用于 OSD
+pub fn balance_message(balance: f32) -> String {
+    if balance.abs() < 0.005 {
+        "平衡 居中".to_string()
+    } else if balance < 0.0 {
+        format!("平衡 偏左 {:.0}%", -balance * 100.0)
+    } else {
+        format!("平衡 偏右 {:.0}%", balance * 100.0)
+    }
+}
+
+/// 声道在左右平衡中属于哪一侧
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+    Center,
+}
+
+// 按常见的声道排列（WAV/FLAC 的默认顺序）判断声道位置：
+// 四声道为 前左 前右 后左 后右；5.1 为 前左 前右 中置 低音 环绕左 环绕右；7.1 再加 侧左 侧右
+fn channel_side(index: usize, channels: usize) -> Side {
+    match (channels, index) {
+        (1, _) => Side::Center,
+        (_, 0) => Side::Left,
+        (_, 1) => Side::Right,
+        (4, 2) => Side::Left,
+        (4, 3) => Side::Right,
+        (6 | 8, 4 | 6) => Side::Left,
+        (6 | 8, 5 | 7) => Side::Right,
+        _ => Side::Center,
+    }
+}
+
+// 5.1 及以上的第 4 个声道是低音声道，单声道混音时不计入
+fn is_lfe(index: usize, channels: usize) -> bool {
+    channels >= 6 && index == 3
+}
+
+/// 在解码器和 sink 之间按帧处理声道的 Source 适配器：
+/// 单声道模式下把各声道（低音声道除外）的平均值写回每个声道，再按左右平衡对各声道施加增益。
+/// 单声道音源原样通过
+pub struct ChannelMixer<S: Source<Item = i16>> {
+    source: S,
+    mix: ChannelMix,
+    // 当前帧处理后的样本，以及下一个要输出的位置
+    frame: Vec<i16>,
+    position: usize,
+}
+
+impl<S: Source<Item = i16>> ChannelMixer<S> {
+    pub fn new(source: S, mix: ChannelMix) -> Self {
+        ChannelMixer { source, mix, frame: Vec::new(), position: 0 }
+    }
+
+    // 读取并处理下一帧，音源结束时返回 false
+    fn fill_frame(&mut self) -> bool {
+        let channels = self.source.channels().max(1) as usize;
+        self.frame.clear();
+        self.position = 0;
+        self.frame.extend(self.source.by_ref().take(channels));
+        if self.frame.is_empty() {
+            return false;
+        }
+        // 音源在帧中间结束时，剩余的样本原样输出
+        if channels == 1 || self.frame.len() < channels {
+            return true;
+        }
+        if self.mix.mono {
+            let (sum, count) = self
+                .frame
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !is_lfe(*index, channels))
+                .fold((0i32, 0i32), |(sum, count), (_, &sample)| (sum + sample as i32, count + 1));
+            let average = (sum / count.max(1)) as i16;
+            for (index, sample) in self.frame.iter_mut().enumerate() {
+                if !is_lfe(index, channels) {
+                    *sample = average;
+                }
+            }
+        }
+        let (left, right) = self.mix.gains();
+        if left < 1.0 || right < 1.0 {
+            for (index, sample) in self.frame.iter_mut().enumerate() {
+                let gain = match channel_side(index, channels) {
+                    Side::Left => left,
+                    Side::Right => right,
+                    Side::Center => continue,
+                };
+                *sample = (*sample as f32 * gain).round() as i16;
+            }
+        }
+        true
+    }
+}
+
+impl<S: Source<Item = i16>> Iterator for ChannelMixer<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.position >= self.frame.len() && !self.fill_frame() {
+            return None;
+        }
+        let sample = self.frame[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let buffered = self.frame.len() - self.position;
+        let (lower, upper) = self.source.size_hint();
+        (lower + buffered, upper.map(|upper| upper + buffered))
+    }
+}
+
+impl<S: Source<Item = i16>> Source for ChannelMixer<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        let buffered = self.frame.len() - self.position;
+        self.source.current_frame_len().map(|len| len + buffered)
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按给定声道数逐个输出样本的测试音源
+    struct Frames {
+        samples: std::vec::IntoIter<i16>,
+        channels: u16,
+    }
+
+    impl Iterator for Frames {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            self.samples.next()
+        }
+    }
+
+    impl Source for Frames {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            self.channels
+        }
+
+        fn sample_rate(&self) -> u32 {
+            44100
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    fn mix(samples: &[i16], channels: u16, mono: bool, balance: f32) -> Vec<i16> {
+        let source = Frames { samples: Vec::from(samples).into_iter(), channels };
+        ChannelMixer::new(source, ChannelMix::new(mono, balance)).collect()
+    }
+
+    #[test]
+    fn stereo_passes_through_unchanged() {
+        let samples = [1000, -2000, 3000, 4000, i16::MAX, i16::MIN];
+        assert_eq!(mix(&samples, 2, false, 0.0), samples);
+    }
+
+    #[test]
+    fn mono_averages_each_frame() {
+        assert_eq!(mix(&[1000, 3000, -500, 500, i16::MAX, i16::MAX], 2, true, 0.0), [2000, 2000, 0, 0, i16::MAX, i16::MAX]);
+    }
+
+    #[test]
+    fn mono_leaves_out_the_lfe_channel() {
+        // 5.1：前左 前右 中置 低音 环绕左 环绕右
+        assert_eq!(mix(&[600, 1200, 0, 9000, 300, 900], 6, true, 0.0), [600, 600, 600, 9000, 600, 600]);
+    }
+
+    #[test]
+    fn balance_attenuates_only_the_other_side() {
+        assert_eq!(mix(&[1000, 1000, -2000, -2000], 2, false, 0.5), [500, 1000, -1000, -2000]);
+        assert_eq!(mix(&[1000, 1000], 2, false, -0.25), [1000, 750]);
+        assert_eq!(mix(&[1000, 1000], 2, false, 1.0), [0, 1000]);
+    }
+
+    #[test]
+    fn mono_is_mixed_before_balance() {
+        assert_eq!(mix(&[2000, 0], 2, true, -1.0), [1000, 0]);
+    }
+
+    #[test]
+    fn mono_sources_and_partial_frames_are_untouched() {
+        assert_eq!(mix(&[1000, 2000, 3000], 1, true, 0.5), [1000, 2000, 3000]);
+        // 音源在帧中间结束
+        assert_eq!(mix(&[1000, 3000, 5000], 2, true, 0.0), [2000, 2000, 5000]);
+    }
+
+    #[test]
+    fn balance_changes_apply_to_the_next_frame() {
+        let channel_mix = ChannelMix::new(false, 0.0);
+        let source = Frames { samples: vec![1000, 1000, 1000, 1000].into_iter(), channels: 2 };
+        let mut mixer = ChannelMixer::new(source, channel_mix.clone());
+        assert_eq!((mixer.next(), mixer.next()), (Some(1000), Some(1000)));
+        assert_eq!(channel_mix.set_balance(0.304), 0.3);
+        assert_eq!((mixer.next(), mixer.next()), (Some(700), Some(1000)));
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute};
+
+use crate::display::draw_list_rows;
+use crate::utils::{has_audio_files, is_hidden, is_playlist_file, read_playlist_file, scan_audio_files, sort_naturally, ScanOptions};
+
+/// 选择列表一次最多显示的条数
+const PICKER_VISIBLE: usize = 10;
+
+// 目录中的播放列表文件和其中的条目数（无法读取时为 None）
+struct Candidate {
+    path: PathBuf,
+    entries: Option<usize>,
+}
+
+impl Candidate {
+    fn describe(&self, name: &str) -> String {
+        match self.entries {
+            Some(count) => format!("{}（{} 首）", name, count),
+            None => format!("{}（无法读取）", name),
+        }
+    }
+}
+
+/// 启动时选择播放列表：输入中的目录含有播放列表文件（.m3u/.m3u8/.txt）时，列出各播放列表的条目数，
+/// 用 ↑↓ 和 Enter 选择一个代替该目录，或者选择 “全部音频文件” 按原来的方式扫描目录。
+/// 指定 --pick（pick 为 true）时总是选择；否则只在目录中只有播放列表、没有音频文件时选择，只有一个播放列表时直接使用。
+/// 不能交互时列出候选的播放列表并返回错误，便于在脚本中直接指定；用户按 Esc/Q 取消时返回 Ok(None)
+pub fn pick_playlists(inputs: &[PathBuf], pick: bool, interactive: bool, options: ScanOptions) -> Result<Option<Vec<PathBuf>>, String> {
+    let mut resolved = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let candidates = if input.is_dir() { find_playlists(input, options) } else { Vec::new() };
+        if candidates.is_empty() {
+            resolved.push(input.clone());
+            continue;
+        }
+        // 只需要知道有没有音频文件，找到第一个就停止，不扫描整个目录
+        let only_playlists = !has_audio_files(input, options);
+        if !pick && !only_playlists {
+            resolved.push(input.clone());
+            continue;
+        }
+        if only_playlists && candidates.len() == 1 {
+            eprintln!("目录中只有播放列表 {}，直接播放。", candidates[0].path.display());
+            resolved.push(candidates[0].path.clone());
+            continue;
+        }
+        if !interactive {
+            let mut message = format!("[错误]无法在非交互模式下选择 {} 中的播放列表，请直接指定其中一个：", input.display());
+            for candidate in &candidates {
+                message.push_str(&format!("\n  {}", candidate.describe(&candidate.path.to_string_lossy())));
+            }
+            return Err(message);
+        }
+
+        let mut items: Vec<String> = candidates
+            .iter()
+            .map(|candidate| candidate.describe(&candidate.path.file_name().unwrap_or_default().to_string_lossy()))
+            .collect();
+        if !only_playlists {
+            let audio_count = scan_audio_files(input, options).map_or(0, |files| files.len());
+            items.push(format!("全部音频文件（{} 首）", audio_count));
+        }
+        match run_picker(input, &items).map_err(|e| format!("[错误]无法显示播放列表选择: {}", e))? {
+            Some(choice) if choice < candidates.len() => resolved.push(candidates[choice].path.clone()),
+            Some(_) => resolved.push(input.clone()),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(resolved))
+}
+
+// 目录下（不含子目录）的播放列表文件，按自然顺序排列
+fn find_playlists(dir: &Path, options: ScanOptions) -> Vec<Candidate> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_playlist_file(path) && (options.include_hidden || !is_hidden(path)))
+        .collect();
+    sort_naturally(&mut paths);
+    paths
+        .into_iter()
+        .map(|path| {
+            let entries = read_playlist_file(&path, &mut Vec::new(), options).ok().map(|entries| entries.len());
+            Candidate { path, entries }
+        })
+        .collect()
+}
+
+// 在终端中显示选择列表，返回选中的下标；按 Esc/Q/Ctrl+C 取消时返回 None。结束后恢复终端并清屏
+fn run_picker(dir: &Path, items: &[String]) -> io::Result<Option<usize>> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, cursor::Hide, terminal::Clear(ClearType::All))?;
+    let result = picker_loop(&mut stdout, dir, items);
+    execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0), cursor::Show)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn picker_loop(stdout: &mut io::Stdout, dir: &Path, items: &[String]) -> io::Result<Option<usize>> {
+    let visible = items.len().min(PICKER_VISIBLE);
+    let mut selected = 0;
+    loop {
+        execute!(stdout, cursor::MoveTo(0, 0), terminal::Clear(ClearType::CurrentLine))?;
+        print!("=================【 选 择 播 放 列 表 】===================");
+        execute!(stdout, cursor::MoveTo(0, 1), terminal::Clear(ClearType::CurrentLine))?;
+        print!(" {}", dir.display());
+        draw_list_rows(stdout, 2, visible, selected, |position| items.get(position).cloned())?;
+        execute!(stdout, cursor::MoveTo(0, visible as u16 + 2), terminal::Clear(ClearType::CurrentLine))?;
+        print!(" [↑↓]选择    [Enter]播放    [Esc/Q]退出");
+        stdout.flush()?;
+
+        let Event::Key(key_event) = event::read()? else { continue };
+        // Windows 下按键抬起也会产生事件
+        if key_event.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key_event.code {
+            KeyCode::Up => selected = selected.checked_sub(1).unwrap_or(items.len() - 1),
+            KeyCode::Down => selected = (selected + 1) % items.len(),
+            KeyCode::Enter => return Ok(Some(selected)),
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => return Ok(None),
+            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+
+use rodio::Source;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder, DecoderOptions},
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+// 连续解码失败超过该次数时结束播放（与 rodio 的解码器一致）
+const MAX_DECODE_ERRORS: usize = 3;
+
+/// 可以定位的解码器：rodio 0.17 的 Decoder 不支持定位，这里直接用 symphonia 解码。
+/// 打开时用 FormatReader::seek 跳到目标位置附近，只丢弃目标所在数据包中位置之前的采样，
+/// 不需要在音频回调中解码整段开头
+pub struct SeekableDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    // 当前数据包解码出的交错采样，以及下一个要输出的位置
+    samples: Vec<i16>,
+    offset: usize,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl SeekableDecoder {
+    /// 打开本地文件并定位到 position（in_memory 时先把整个文件读入内存，对应 --buffer full）
+    pub fn open(path: &Path, in_memory: bool, position: Duration) -> Result<SeekableDecoder, String> {
+        let source: Box<dyn MediaSource> = if in_memory {
+            Box::new(Cursor::new(std::fs::read(path).map_err(|e| e.to_string())?))
+        } else {
+            Box::new(File::open(path).map_err(|e| e.to_string())?)
+        };
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+            hint.with_extension(ext);
+        }
+        SeekableDecoder::from_source(source, &hint, position).map_err(|e| e.to_string())
+    }
+
+    fn from_source(source: Box<dyn MediaSource>, hint: &Hint, position: Duration) -> Result<SeekableDecoder, SymphoniaError> {
+        let stream = MediaSourceStream::new(source, Default::default());
+        let probed = symphonia::default::get_probe().format(hint, stream, &FormatOptions::default(), &MetadataOptions::default())?;
+        let mut format = probed.format;
+        let track = format.default_track().ok_or(SymphoniaError::Unsupported("未找到可解码的音轨"))?;
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+        // 定位后从 actual_ts 所在的数据包开始读取，required_ts（目标位置）之前的采样丢弃
+        let required_ts = if position.is_zero() {
+            0
+        } else {
+            format.seek(SeekMode::Accurate, SeekTo::Time { time: position.into(), track_id: Some(track_id) })?.required_ts
+        };
+        let mut decoder = SeekableDecoder { format, decoder, track_id, samples: Vec::new(), offset: 0, channels: 0, sample_rate: 0 };
+        if !decoder.decode_next(required_ts) {
+            return Err(SymphoniaError::DecodeError("定位的位置之后没有可以播放的数据"));
+        }
+        Ok(decoder)
+    }
+
+    // 解码下一个数据包，skip_until（时间戳）之前的采样丢弃。读到结尾或连续解码失败时返回 false
+    fn decode_next(&mut self, skip_until: u64) -> bool {
+        let mut errors = 0;
+        loop {
+            let Ok(packet) = self.format.next_packet() else { return false };
+            if packet.track_id() != self.track_id || packet.ts() + packet.dur() <= skip_until {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(_)) if errors < MAX_DECODE_ERRORS => {
+                    errors += 1;
+                    continue;
+                }
+                Err(_) => return false,
+            };
+            let spec = *decoded.spec();
+            let channels = spec.channels.count();
+            let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+            let skipped = (skip_until.saturating_sub(packet.ts()) as usize * channels).min(buffer.samples().len());
+            self.samples.clear();
+            self.samples.extend_from_slice(&buffer.samples()[skipped..]);
+            self.offset = 0;
+            self.channels = channels as u16;
+            self.sample_rate = spec.rate;
+            if !self.samples.is_empty() {
+                return true;
+            }
+        }
+    }
+}
+
+impl Iterator for SeekableDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.offset == self.samples.len() && !self.decode_next(0) {
+            return None;
+        }
+        let sample = self.samples[self.offset];
+        self.offset += 1;
+        Some(sample)
+    }
+}
+
+impl Source for SeekableDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len() - self.offset)
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{fixture_dir, write_wav_fixture};
+
+    // 单声道 8000 Hz，每个采样的值就是它的序号（对 30000 取余），便于检查定位到的位置
+    fn ramp(frames: usize) -> Vec<i16> {
+        (0..frames).map(|i| (i % 30000) as i16).collect()
+    }
+
+    #[test]
+    fn opens_at_the_exact_position() {
+        let dir = fixture_dir("seek-exact");
+        let path = write_wav_fixture(&dir, "ramp.wav", 8000, 1, &ramp(24000));
+        for in_memory in [false, true] {
+            let decoder = SeekableDecoder::open(&path, in_memory, Duration::from_millis(1500)).unwrap();
+            assert_eq!((decoder.channels(), decoder.sample_rate()), (1, 8000));
+            let samples: Vec<i16> = decoder.collect();
+            assert_eq!(samples.len(), 24000 - 12000);
+            assert_eq!(samples[0], 12000);
+            assert_eq!(*samples.last().unwrap(), 23999);
+        }
+    }
+
+    #[test]
+    fn stereo_frames_stay_aligned_after_seeking() {
+        let dir = fixture_dir("seek-stereo");
+        // 左声道为帧序号，右声道为其相反数
+        let samples: Vec<i16> = (0..8000i16).flat_map(|frame| [frame, -frame]).collect();
+        let path = write_wav_fixture(&dir, "stereo.wav", 4000, 2, &samples);
+        let decoded: Vec<i16> = SeekableDecoder::open(&path, false, Duration::from_millis(250)).unwrap().take(4).collect();
+        assert_eq!(decoded, [1000, -1000, 1001, -1001]);
+    }
+
+    #[test]
+    fn zero_position_plays_from_the_start() {
+        let dir = fixture_dir("seek-zero");
+        let path = write_wav_fixture(&dir, "ramp.wav", 8000, 1, &ramp(800));
+        let samples: Vec<i16> = SeekableDecoder::open(&path, false, Duration::ZERO).unwrap().collect();
+        assert_eq!(samples, ramp(800));
+    }
+
+    #[test]
+    fn seeking_past_the_end_fails() {
+        let dir = fixture_dir("seek-end");
+        let path = write_wav_fixture(&dir, "short.wav", 8000, 1, &ramp(800));
+        assert!(SeekableDecoder::open(&path, false, Duration::from_secs(5)).is_err());
+        assert!(SeekableDecoder::open(&dir.join("missing.wav"), false, Duration::ZERO).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+/// --shuffle spread 时同一艺术家（或目录）的两首歌之间至少间隔的歌曲数
+pub const SPREAD_MIN_GAP: usize = 2;
+
+/// 打散时区分歌曲来源的键：已知艺术家时按艺术家，否则按所在目录
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TrackKey {
+    Artist(String),
+    Directory(PathBuf),
+}
+
+/// 歌曲的键：artist 为已读取过的艺术家（没有或为 “未知” 时按所在目录）
+pub fn track_key(path: &Path, artist: Option<&str>) -> TrackKey {
+    match artist {
+        Some(artist) if !matches!(artist, "未知" | "未知作者") => TrackKey::Artist(artist.to_string()),
+        _ => TrackKey::Directory(path.parent().map(Path::to_path_buf).unwrap_or_default()),
+    }
+}
+
+/// 在已洗牌的顺序上重新排列，使键相同的两首歌之间尽量至少隔 min_gap 首，返回新顺序中各元素的原下标。
+/// previous 为排在这些歌之前刚播放的歌的键（新一轮的开头同样与它保持间隔）。
+/// 贪心选择：每次从间隔内没出现过、且安排后剩下的歌仍能保持间隔的键中按剩余数量加权随机选一个。
+/// 某位艺术家占大多数、剩余位置不够时，间隔缩小到剩余位置能满足的最大值，其他歌夹在他的歌之间，而不是把多出来的歌堆在最后
+pub fn spread_order<K: Eq + Hash, R: Rng>(keys: Vec<K>, previous: Option<&K>, min_gap: usize, rng: &mut R) -> Vec<usize> {
+    // 每个键的剩余下标（保持原来的洗牌顺序）和最后一次安排的位置
+    let mut groups: Vec<(Vec<usize>, Option<usize>)> = Vec::new();
+    let mut group_of: HashMap<K, usize> = HashMap::new();
+    for (index, key) in keys.into_iter().enumerate() {
+        let group = *group_of.entry(key).or_insert_with(|| {
+            groups.push((Vec::new(), None));
+            groups.len() - 1
+        });
+        groups[group].0.push(index);
+    }
+    for (indices, _) in &mut groups {
+        indices.reverse();
+    }
+    // 有上一首时它占位置 0，新顺序从位置 1 开始
+    let start = match previous.and_then(|key| group_of.get(key)) {
+        Some(&group) => {
+            groups[group].1 = Some(0);
+            1
+        }
+        None => 0,
+    };
+
+    let total: usize = groups.iter().map(|(indices, _)| indices.len()).sum();
+    let mut order = Vec::with_capacity(total);
+    // 剩余 largest 首的键有 ties 个时，保持间隔 gap 至少需要 (largest - 1) * (gap + 1) + ties 个位置
+    let fits = |largest: usize, ties: usize, remaining: usize, gap: usize| largest == 0 || (largest - 1) * (gap + 1) + ties <= remaining;
+    for position in start..start + total {
+        let remaining = total - order.len();
+        let count = |group: usize| groups[group].0.len();
+        let largest = (0..groups.len()).map(count).max().unwrap_or(0);
+        let ties = (0..groups.len()).filter(|&group| count(group) == largest).count();
+        let below = (0..groups.len()).filter(|&group| count(group) + 1 == largest).count();
+        // 当前剩下的歌能保持的最大间隔；为 0 时剩余位置连间隔 1 都不够，仍尽量不让同一个键相邻
+        let gap = (0..=min_gap).rev().find(|&gap| fits(largest, ties, remaining, gap)).unwrap_or(0);
+        let spacing = if gap == 0 { min_gap.min(1) } else { gap };
+        let eligible: Vec<usize> =
+            (0..groups.len()).filter(|&group| count(group) > 0 && groups[group].1.is_none_or(|last| position - last > spacing)).collect();
+        // 安排之后剩下的歌仍能保持这个间隔的键
+        let safe: Vec<usize> = eligible
+            .iter()
+            .copied()
+            .filter(|&group| {
+                let (largest, ties) = if count(group) < largest {
+                    (largest, ties)
+                } else if ties > 1 {
+                    (largest, ties - 1)
+                } else {
+                    (largest - 1, below + 1)
+                };
+                gap > 0 && fits(largest, ties, remaining - 1, gap)
+            })
+            .collect();
+        let chosen = if !safe.is_empty() {
+            let weight: usize = safe.iter().map(|&group| count(group)).sum();
+            let mut pick = rng.gen_range(0..weight);
+            *safe
+                .iter()
+                .find(|&&group| {
+                    if pick < count(group) {
+                        return true;
+                    }
+                    pick -= count(group);
+                    false
+                })
+                .unwrap()
+        } else if let Some(group) = eligible.iter().copied().max_by_key(|&group| (count(group), Reverse(groups[group].1))) {
+            // 间隔已无法保持：先安排剩余最多的键，其他键夹在它的歌之间
+            group
+        } else {
+            // 都在间隔内：选最早安排过的键，尽量拉开距离
+            (0..groups.len()).filter(|&group| count(group) > 0).min_by_key(|&group| groups[group].1).unwrap()
+        };
+        let (indices, last) = &mut groups[chosen];
+        order.push(indices.pop().unwrap());
+        *last = Some(position);
+    }
+    order
+}
+
+/// --shuffle spread：把已洗牌的播放列表打散，避免同一艺术家的歌连着播放。
+/// artist_of 返回已读取过标签的歌曲的艺术家，其他歌曲按所在目录区分，不额外读取文件。
+/// previous 为上一轮最后播放的歌：新一轮的开头与它保持间隔，且不会以它开头
+pub fn spread_playlist<R: Rng>(
+    playlist: Vec<PathBuf>,
+    previous: Option<&Path>,
+    artist_of: impl Fn(&Path) -> Option<String>,
+    rng: &mut R,
+) -> Vec<PathBuf> {
+    let keys: Vec<TrackKey> = playlist.iter().map(|path| track_key(path, artist_of(path).as_deref())).collect();
+    let previous_key = previous.map(|path| track_key(path, artist_of(path).as_deref()));
+    let order = spread_order(keys.clone(), previous_key.as_ref(), SPREAD_MIN_GAP, rng);
+    let mut slots: Vec<Option<PathBuf>> = playlist.into_iter().map(Some).collect();
+    let keys: Vec<TrackKey> = order.iter().map(|&index| keys[index].clone()).collect();
+    let mut spread: Vec<PathBuf> = order.into_iter().map(|index| slots[index].take().unwrap()).collect();
+    // 间隔无法保持时开头可能仍是上一首：与同一个键的另一首交换，键的顺序不变，间隔不受影响
+    if spread.len() > 1 && previous == Some(spread[0].as_path()) {
+        let swap_with = (1..spread.len()).find(|&index| keys[index] == keys[0]).unwrap_or_else(|| rng.gen_range(1..spread.len()));
+        spread.swap(0, swap_with);
+    }
+    spread
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // 按 spread_order 的结果排列键，并检查结果是原下标的一个排列
+    fn arrange(keys: &[char], previous: Option<char>, seed: u64) -> Vec<char> {
+        let order = spread_order(keys.to_vec(), previous.as_ref(), SPREAD_MIN_GAP, &mut StdRng::seed_from_u64(seed));
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..keys.len()).collect::<Vec<_>>());
+        order.into_iter().map(|index| keys[index]).collect()
+    }
+
+    // 相同的键之间最少隔了几首（没有重复的键时为 usize::MAX）
+    fn smallest_gap(arranged: &[char]) -> usize {
+        let mut smallest = usize::MAX;
+        for (position, key) in arranged.iter().enumerate() {
+            if let Some(previous) = arranged[..position].iter().rposition(|other| other == key) {
+                smallest = smallest.min(position - previous - 1);
+            }
+        }
+        smallest
+    }
+
+    #[test]
+    fn keeps_the_minimum_gap_when_possible() {
+        let keys: Vec<char> = "AAAAABBBBBCCCCCDDDDD".chars().collect();
+        for seed in 0..50 {
+            let arranged = arrange(&keys, None, seed);
+            assert!(smallest_gap(&arranged) >= SPREAD_MIN_GAP, "seed {}: {:?}", seed, arranged);
+        }
+        // 三个键各三首：刚好能排成每隔两首出现一次
+        let keys: Vec<char> = "AAABBBCCC".chars().collect();
+        for seed in 0..50 {
+            let arranged = arrange(&keys, None, seed);
+            assert_eq!(smallest_gap(&arranged), SPREAD_MIN_GAP, "seed {}: {:?}", seed, arranged);
+        }
+    }
+
+    #[test]
+    fn dominant_key_is_spread_evenly() {
+        // 6 首 A 只有 3 首其他歌：间隔不可能达到 2，但 A 不应该堆在一起
+        let keys: Vec<char> = "AAAAAABCD".chars().collect();
+        for seed in 0..50 {
+            let arranged = arrange(&keys, None, seed);
+            let adjacent = arranged.windows(2).filter(|pair| pair == &['A', 'A']).count();
+            // 3 首其他歌最多把 A 分成 4 段，至少有 2 对 A 相邻
+            assert_eq!(adjacent, 2, "seed {}: {:?}", seed, arranged);
+        }
+        // 只有一个键时原样保留洗牌顺序
+        assert_eq!(spread_order(vec![1, 1, 1], None, SPREAD_MIN_GAP, &mut StdRng::seed_from_u64(0)), [0, 1, 2]);
+    }
+
+    #[test]
+    fn keeps_the_shuffled_order_within_a_key() {
+        let keys = ['A', 'B', 'A', 'C', 'A', 'B', 'C', 'B', 'C'];
+        for seed in 0..20 {
+            let order = spread_order(keys.to_vec(), None, SPREAD_MIN_GAP, &mut StdRng::seed_from_u64(seed));
+            for key in ['A', 'B', 'C'] {
+                let indices: Vec<usize> = order.iter().copied().filter(|&index| keys[index] == key).collect();
+                assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_the_gap_from_the_previous_track() {
+        let keys: Vec<char> = "AAABBBCCC".chars().collect();
+        for seed in 0..50 {
+            let arranged = arrange(&keys, Some('A'), seed);
+            // 上一首是 A：A 最早出现在第三首
+            let mut with_previous = vec!['A'];
+            with_previous.extend(&arranged);
+            assert!(smallest_gap(&with_previous) >= SPREAD_MIN_GAP, "seed {}: {:?}", seed, arranged);
+        }
+        // 上一首的键不在列表中时没有影响
+        assert_eq!(smallest_gap(&arrange(&keys, Some('Z'), 0)), SPREAD_MIN_GAP);
+    }
+
+    #[test]
+    fn new_round_does_not_start_with_the_previous_track() {
+        // 都在同一个目录（同一个键）：间隔无法保持，但不会以上一首开头
+        let playlist: Vec<PathBuf> = ["/music/a.mp3", "/music/b.mp3", "/music/c.mp3"].iter().map(PathBuf::from).collect();
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let spread = spread_playlist(playlist.clone(), Some(Path::new("/music/a.mp3")), |_| None, &mut rng);
+            assert_ne!(spread[0], Path::new("/music/a.mp3"));
+            assert_eq!(spread.len(), 3);
+        }
+        // 艺术家不同：上一首的艺术家排在新一轮的前两首之后
+        let artist_of = |path: &Path| Some(path.file_stem().unwrap().to_string_lossy()[..1].to_string());
+        let playlist: Vec<PathBuf> = ["x1", "x2", "y1", "y2", "z1", "z2"].iter().map(PathBuf::from).collect();
+        for seed in 0..20 {
+            let spread = spread_playlist(playlist.clone(), Some(Path::new("x2")), artist_of, &mut StdRng::seed_from_u64(seed));
+            assert!(spread[..2].iter().all(|path| !path.starts_with("x")), "seed {}: {:?}", seed, spread);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+/// 两次按键间隔不超过该值时视为按住不放（终端的按键重复间隔通常为 30-500ms）
+const HOLD_GAP: Duration = Duration::from_millis(500);
+/// 连续快速切歌达到该次数后进入快速浏览
+const ACCELERATE_AFTER: u32 = 5;
+/// 快速浏览时的最小切歌间隔
+const SCRUB_INTERVAL: Duration = Duration::from_millis(50);
+/// 快速浏览时松开按键超过该时长才真正切歌
+const RELEASE_DELAY: Duration = Duration::from_millis(300);
+
+/// 一次 ←/→ 按键的处理结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipAction {
+    /// 距上次切歌太近，忽略
+    Ignore,
+    /// 立即切歌，偏移为按键的步长
+    Skip(i32),
+    /// 快速浏览中：只记录累计的偏移，松开按键后再切歌
+    Scrub(i32),
+}
+
+/// 切歌按键的加速：按住 ←/→（或 Ctrl+←/→）连续快速切歌 5 次后，缩短切歌间隔并进入快速浏览，
+/// 之后的按键只累计偏移，不停止当前歌曲、不预加载中间的歌曲，松开按键 300ms 后一次切到目标歌曲
+pub struct SkipAccelerator {
+    min_interval: Duration,
+    // 上一次按键（包括被忽略的）和上一次切歌的时刻
+    last_press: Option<Instant>,
+    last_skip: Option<Instant>,
+    // 本次按住以来已经切歌的次数
+    streak: u32,
+    // 快速浏览中累计的偏移，不在快速浏览时为 None
+    pending: Option<i32>,
+}
+
+impl SkipAccelerator {
+    /// min_interval 为平时的最小切歌间隔
+    pub fn new(min_interval: Duration) -> Self {
+        SkipAccelerator { min_interval, last_press: None, last_skip: None, streak: 0, pending: None }
+    }
+
+    /// 按下切歌键，step 为 ±1（←/→）或 ±10（Ctrl+←/→）
+    pub fn press(&mut self, step: i32, now: Instant) -> SkipAction {
+        let held = self.last_press.is_some_and(|last| now.saturating_duration_since(last) <= HOLD_GAP);
+        self.last_press = Some(now);
+        if !held {
+            self.streak = 0;
+        }
+        let interval = if self.streak >= ACCELERATE_AFTER { SCRUB_INTERVAL } else { self.min_interval };
+        if self.last_skip.is_some_and(|last| now.saturating_duration_since(last) < interval) {
+            return SkipAction::Ignore;
+        }
+        self.last_skip = Some(now);
+        self.streak += 1;
+        if self.streak <= ACCELERATE_AFTER && self.pending.is_none() {
+            return SkipAction::Skip(step);
+        }
+        let pending = self.pending.get_or_insert(0);
+        *pending += step;
+        SkipAction::Scrub(*pending)
+    }
+
+    /// 换了一首歌（例如播放结束、远程控制切歌）：丢弃快速浏览中累计的偏移，它是相对于之前那首歌的
+    pub fn track_changed(&mut self) {
+        self.pending = None;
+    }
+
+    /// 快速浏览中松开按键超过 RELEASE_DELAY 时结束浏览，返回累计的偏移（可能为 0），由调用方切歌
+    pub fn release(&mut self, now: Instant) -> Option<i32> {
+        self.pending?;
+        if self.last_press.is_some_and(|last| now.saturating_duration_since(last) < RELEASE_DELAY) {
+            return None;
+        }
+        self.streak = 0;
+        self.pending.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    // 从 start 开始每隔 gap 按一次键，返回各次的结果和最后一次按键的时刻
+    fn presses(accelerator: &mut SkipAccelerator, start: Instant, gap: Duration, count: u32, step: i32) -> (Vec<SkipAction>, Instant) {
+        let times: Vec<Instant> = (0..count).map(|i| start + gap * i).collect();
+        (times.iter().map(|&now| accelerator.press(step, now)).collect(), *times.last().unwrap())
+    }
+
+    #[test]
+    fn five_quick_skips_start_scrubbing() {
+        let t0 = Instant::now();
+        let mut accelerator = SkipAccelerator::new(MIN_INTERVAL);
+        let (actions, last) = presses(&mut accelerator, t0, ms(300), 5, 1);
+        assert_eq!(actions, [SkipAction::Skip(1); 5]);
+        // 之后的按键只累计偏移，Ctrl+→ 一次加 10
+        assert_eq!(accelerator.press(1, last + ms(60)), SkipAction::Scrub(1));
+        assert_eq!(accelerator.press(10, last + ms(120)), SkipAction::Scrub(11));
+        assert_eq!(accelerator.press(-1, last + ms(180)), SkipAction::Scrub(10));
+    }
+
+    #[test]
+    fn presses_inside_the_interval_are_ignored() {
+        let t0 = Instant::now();
+        let mut accelerator = SkipAccelerator::new(MIN_INTERVAL);
+        assert_eq!(accelerator.press(1, t0), SkipAction::Skip(1));
+        assert_eq!(accelerator.press(1, t0 + ms(100)), SkipAction::Ignore);
+        assert_eq!(accelerator.press(1, t0 + ms(250)), SkipAction::Skip(1));
+        // 快速浏览时间隔缩短为 SCRUB_INTERVAL
+        let (_, last) = presses(&mut accelerator, t0 + ms(500), ms(300), 3, 1);
+        assert_eq!(accelerator.press(1, last + ms(60)), SkipAction::Scrub(1));
+        assert_eq!(accelerator.press(1, last + ms(90)), SkipAction::Ignore);
+        assert_eq!(accelerator.press(1, last + ms(110)), SkipAction::Scrub(2));
+    }
+
+    #[test]
+    fn release_applies_the_accumulated_offset() {
+        let t0 = Instant::now();
+        let mut accelerator = SkipAccelerator::new(MIN_INTERVAL);
+        // 不在快速浏览中时松开没有效果
+        assert_eq!(accelerator.release(t0), None);
+        let (_, last) = presses(&mut accelerator, t0, ms(300), 5, -1);
+        let (actions, last) = presses(&mut accelerator, last + ms(60), ms(60), 3, -1);
+        assert_eq!(actions.last(), Some(&SkipAction::Scrub(-3)));
+        assert_eq!(accelerator.release(last + ms(299)), None);
+        assert_eq!(accelerator.release(last + ms(300)), Some(-3));
+        assert_eq!(accelerator.release(last + ms(400)), None);
+        // 松开后重新计数，下一次按键立即切歌
+        assert_eq!(accelerator.press(-1, last + ms(600)), SkipAction::Skip(-1));
+    }
+
+    #[test]
+    fn a_long_gap_resets_the_streak() {
+        let t0 = Instant::now();
+        let mut accelerator = SkipAccelerator::new(MIN_INTERVAL);
+        let (_, last) = presses(&mut accelerator, t0, ms(300), 4, 1);
+        // 超过 HOLD_GAP 视为松开后重新按下，又要连续切歌 5 次才进入快速浏览
+        let (actions, last) = presses(&mut accelerator, last + ms(501), ms(300), 5, 1);
+        assert_eq!(actions, [SkipAction::Skip(1); 5]);
+        assert_eq!(accelerator.press(1, last + ms(60)), SkipAction::Scrub(1));
+    }
+
+    #[test]
+    fn track_change_drops_the_pending_offset() {
+        let t0 = Instant::now();
+        let mut accelerator = SkipAccelerator::new(MIN_INTERVAL);
+        let (_, last) = presses(&mut accelerator, t0, ms(300), 5, 1);
+        assert_eq!(accelerator.press(1, last + ms(60)), SkipAction::Scrub(1));
+        accelerator.track_changed();
+        assert_eq!(accelerator.release(last + ms(360)), None);
+        // 仍按住时从新的歌曲重新累计
+        assert_eq!(accelerator.press(1, last + ms(400)), SkipAction::Scrub(1));
+        assert_eq!(accelerator.release(last + ms(700)), Some(1));
+    }
+}
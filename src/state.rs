@@ -0,0 +1,81 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 退出时保存的播放状态，--resume 时从这里继续播放
+pub struct SessionState {
+    /// 播放列表（排序后的规范化路径）的哈希，用于判断播放列表是否改变
+    pub playlist_hash: u64,
+    pub track_index: usize,
+    pub seek_position_secs: u64,
+    /// 随机模式下洗牌使用的种子，顺序模式下为 None
+    pub shuffle_seed: Option<u64>,
+}
+
+impl SessionState {
+    /// 读取上次保存的状态
+    pub fn load() -> io::Result<SessionState> {
+        let path = data_file_path("session.json").ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "无法确定数据目录"))?;
+        let content = fs::read_to_string(path)?;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "播放状态文件格式错误");
+        let shuffle_seed = match json_field(&content, "shuffle_seed").ok_or_else(invalid)? {
+            "null" => None,
+            seed => Some(seed.parse().map_err(|_| invalid())?),
+        };
+        Ok(SessionState {
+            playlist_hash: json_field(&content, "playlist_hash").and_then(|v| v.parse().ok()).ok_or_else(invalid)?,
+            track_index: json_field(&content, "track_index").and_then(|v| v.parse().ok()).ok_or_else(invalid)?,
+            seek_position_secs: json_field(&content, "seek_position_secs").and_then(|v| v.parse().ok()).ok_or_else(invalid)?,
+            shuffle_seed,
+        })
+    }
+
+    /// 保存到数据目录下的 mddplayer/session.json（目录不存在时创建）
+    pub fn save(&self) -> io::Result<()> {
+        let path = data_file_path("session.json").ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "无法确定数据目录"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let shuffle_seed = self.shuffle_seed.map_or_else(|| "null".to_string(), |seed| seed.to_string());
+        let content = format!(
+            "{{\"playlist_hash\":{},\"track_index\":{},\"seek_position_secs\":{},\"shuffle_seed\":{}}}\n",
+            self.playlist_hash, self.track_index, self.seek_position_secs, shuffle_seed
+        );
+        fs::write(path, content)
+    }
+}
+
+/// 计算播放列表的哈希：与顺序无关（先按规范化路径排序），使用固定的 FNV-1a 算法，不同版本间结果一致
+pub fn playlist_hash(playlist: &[PathBuf]) -> u64 {
+    let mut paths: Vec<PathBuf> = playlist
+        .iter()
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect();
+    paths.sort();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for path in &paths {
+        // 合法 Unicode 的路径与 UTF-8 字节相同，非 UTF-8 的路径也不会因替换字符而相互冲突
+        for &byte in path.as_os_str().as_encoded_bytes().iter().chain(std::iter::once(&0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// 数据文件位置：$XDG_DATA_HOME 或 ~/.local/share 下的 mddplayer/<name>（Windows 使用 %APPDATA%）
+pub fn data_file_path(name: &str) -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join("mddplayer").join(name))
+}
+
+// 从单层 JSON 对象中取出数字或 null 字段的原始文本
+fn json_field<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let start = json.find(&format!("\"{}\"", key))? + key.len() + 2;
+    let rest = json[start..].trim_start().strip_prefix(':')?;
+    let end = rest.find([',', '}']).unwrap_or(rest.len());
+    Some(rest[..end].trim())
+}
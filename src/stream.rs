@@ -0,0 +1,63 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// 识别格式时保留的开头部分的大小，解码器在这个范围内可以回退重读
+const HEAD_LIMIT: usize = 256 * 1024;
+
+/// 网络流的读取器：rodio 的解码器要求 Read + Seek，而 HTTP 响应只能顺序读取。
+/// 开头 HEAD_LIMIT 字节保留在内存中，供解码器识别格式时回退；之后只能顺序读取，不能定位
+pub struct NetworkReader {
+    inner: Box<dyn Read + Send + Sync>,
+    // 保留的开头部分，超过 HEAD_LIMIT 后清空，不再保留
+    head: Vec<u8>,
+    retaining: bool,
+    // 已经从网络读取的字节数，以及当前的读取位置
+    received: u64,
+    position: u64,
+}
+
+/// 请求网络流，返回响应内容的读取器
+pub fn open(url: &str) -> io::Result<NetworkReader> {
+    let response = ureq::get(url).call().map_err(io::Error::other)?;
+    Ok(NetworkReader { inner: response.into_reader(), head: Vec::new(), retaining: true, received: 0, position: 0 })
+}
+
+impl Read for NetworkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // 回退后先读保留的部分
+        if self.position < self.received {
+            let start = self.position as usize;
+            let count = buf.len().min(self.head.len() - start);
+            buf[..count].copy_from_slice(&self.head[start..start + count]);
+            self.position += count as u64;
+            return Ok(count);
+        }
+        let count = self.inner.read(buf)?;
+        if self.retaining {
+            self.head.extend_from_slice(&buf[..count]);
+            if self.head.len() > HEAD_LIMIT {
+                self.retaining = false;
+                self.head = Vec::new();
+            }
+        }
+        self.received += count as u64;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for NetworkReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => None,
+        };
+        match target {
+            Some(target) if target == self.position || (self.retaining && target <= self.received) => {
+                self.position = target;
+                Ok(target)
+            }
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, "网络流不支持定位")),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crossterm::style::Color;
+
+use crate::cli::ColorTheme;
+
+/// 状态栏各部分的颜色
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub track_count: Color,
+    pub mode: Color,
+    pub title: Color,
+    pub time: Color,
+    pub volume: Color,
+}
+
+impl Theme {
+    /// 主题的配色，none 返回 None（不输出颜色）
+    pub fn new(theme: ColorTheme) -> Option<Theme> {
+        let theme = match theme {
+            ColorTheme::None => return None,
+            ColorTheme::Default => Theme {
+                track_count: Color::DarkCyan,
+                mode: Color::DarkYellow,
+                title: Color::DarkGreen,
+                time: Color::DarkCyan,
+                volume: Color::DarkMagenta,
+            },
+            // 深色背景：使用高亮色
+            ColorTheme::Dark => Theme {
+                track_count: Color::Cyan,
+                mode: Color::Yellow,
+                title: Color::White,
+                time: Color::Cyan,
+                volume: Color::Magenta,
+            },
+            // 浅色背景：避免黄色、白色等看不清的颜色
+            ColorTheme::Light => Theme {
+                track_count: Color::DarkBlue,
+                mode: Color::DarkRed,
+                title: Color::Black,
+                time: Color::DarkBlue,
+                volume: Color::DarkMagenta,
+            },
+            // Solarized 调色板（需要终端支持 24 位色）
+            ColorTheme::Solarized => Theme {
+                track_count: Color::Rgb { r: 0x26, g: 0x8b, b: 0xd2 },
+                mode: Color::Rgb { r: 0xb5, g: 0x89, b: 0x00 },
+                title: Color::Rgb { r: 0x2a, g: 0xa1, b: 0x98 },
+                time: Color::Rgb { r: 0x6c, g: 0x71, b: 0xc4 },
+                volume: Color::Rgb { r: 0xd3, g: 0x36, b: 0x82 },
+            },
+        };
+        Some(theme)
+    }
+}
+
+/// --color 指定的主题；未指定时按环境变量判断：设置了 NO_COLOR 时不输出颜色，
+/// 设置了 COLORTERM 时使用默认主题，否则 TERM 为空或 dumb 时不输出颜色（Windows 控制台没有 TERM，默认输出颜色）
+pub fn resolve_theme(theme: Option<ColorTheme>) -> Option<Theme> {
+    let theme = theme.unwrap_or_else(|| {
+        let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+        if set("NO_COLOR") {
+            ColorTheme::None
+        } else if set("COLORTERM") {
+            ColorTheme::Default
+        } else {
+            let supported = match std::env::var("TERM") {
+                Ok(term) => !term.is_empty() && term != "dumb",
+                Err(_) => cfg!(windows),
+            };
+            if supported { ColorTheme::Default } else { ColorTheme::None }
+        }
+    });
+    Theme::new(theme)
+}
@@ -0,0 +1,164 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use tracing::warn;
+
+use crate::utils::truncate_string;
+
+/// tmux 窗口名的最大显示宽度，超出时截断并加省略号
+const WINDOW_NAME_WIDTH: usize = 40;
+
+/// --tmux-title：在 tmux（或 screen）中运行时，切歌后把所在窗口改名为 “标题 - 艺术家”。
+/// 普通的 SetTitle 只设置终端标题，不会改变 tmux 的窗口名
+pub struct TmuxTitle {
+    // 是否在 tmux 中运行（否则在 screen 中，只能发送转义序列）
+    tmux: bool,
+    // 播放器所在的 tmux 面板（$TMUX_PANE），tmux 命令作用于它所在的窗口而不是当前活动的窗口
+    pane: Option<String>,
+    // 启动时 tmux 窗口原来的名称，退出时恢复
+    original_name: Option<String>,
+    // 启动时窗口自己的 automatic-rename 设置（None 为沿用全局设置）。rename-window 会关闭它，退出时恢复
+    original_automatic_rename: Option<String>,
+    // 上次设置的窗口名，没有变化时不重复设置
+    shown: String,
+}
+
+impl TmuxTitle {
+    /// 检测是否在 tmux（$TMUX）或 screen（$STY）中运行，不在其中时返回 None，之后不会发送任何转义序列。
+    /// 在 tmux 中时查询一次所在窗口的名称和 automatic-rename 设置，退出时用于恢复
+    pub fn detect() -> Option<TmuxTitle> {
+        let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+        let tmux = set("TMUX");
+        if !tmux && !set("STY") {
+            return None;
+        }
+        let pane = std::env::var("TMUX_PANE").ok().filter(|pane| !pane.is_empty());
+        let (original_name, original_automatic_rename) = if tmux {
+            let pane = pane.as_deref();
+            let name = query_tmux(tmux_command("display-message", pane, &["-p", "#W"]));
+            // 窗口没有单独设置时输出为空
+            let automatic_rename = query_tmux(tmux_command("show-window-options", pane, &["-v", "automatic-rename"]));
+            (name, automatic_rename.filter(|value| !value.is_empty()))
+        } else {
+            (None, None)
+        };
+        Some(TmuxTitle { tmux, pane, original_name, original_automatic_rename, shown: String::new() })
+    }
+
+    /// 把窗口名设为 name：发送 tmux/screen 的标题转义序列，在 tmux 中另外执行 tmux rename-window
+    /// （关闭了 allow-rename 时转义序列不起作用）
+    pub fn set(&mut self, stdout: &mut io::Stdout, name: &str) -> io::Result<()> {
+        if self.shown == name {
+            return Ok(());
+        }
+        self.shown = name.to_string();
+        stdout.write_all(title_sequence(name).as_bytes())?;
+        stdout.flush()?;
+        if self.tmux {
+            run_tmux(tmux_command("rename-window", self.pane.as_deref(), &["--", name]));
+        }
+        Ok(())
+    }
+
+    /// 退出时恢复启动时的 tmux 窗口名（screen 中无法得知原来的窗口名，不恢复）
+    pub fn restore(&self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let Some(original_name) = &self.original_name else { return Ok(()) };
+        if self.shown.is_empty() {
+            return Ok(());
+        }
+        stdout.write_all(title_sequence(original_name).as_bytes())?;
+        stdout.flush()?;
+        let pane = self.pane.as_deref();
+        run_tmux(tmux_command("rename-window", pane, &["--", original_name]));
+        run_tmux(match &self.original_automatic_rename {
+            Some(value) => tmux_command("set-window-option", pane, &["automatic-rename", value]),
+            None => tmux_command("set-window-option", pane, &["-u", "automatic-rename"]),
+        });
+        Ok(())
+    }
+}
+
+/// 窗口名：“标题 - 艺术家”（艺术家未知时只有标题），去掉控制字符并截断到 WINDOW_NAME_WIDTH
+pub fn window_name(title: &str, artist: &str) -> String {
+    let name = if matches!(artist, "未知" | "未知作者") { title.to_string() } else { format!("{} - {}", title, artist) };
+    truncate_string(&sanitize(&name), WINDOW_NAME_WIDTH)
+}
+
+/// 设置 tmux/screen 窗口名的转义序列 `ESC k <name> ESC \`。
+/// name 中的控制字符（包括 ESC，否则会提前结束序列）替换为空格
+pub fn title_sequence(name: &str) -> String {
+    format!("\x1bk{}\x1b\\", sanitize(name))
+}
+
+// 把控制字符替换为空格
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+// tmux 子命令，指定了面板时加上 -t 作用于面板所在的窗口
+fn tmux_command(subcommand: &str, pane: Option<&str>, args: &[&str]) -> Command {
+    let mut command = Command::new("tmux");
+    command.arg(subcommand);
+    if let Some(pane) = pane {
+        command.args(["-t", pane]);
+    }
+    command.args(args);
+    command
+}
+
+// 执行查询命令并返回输出（去掉行尾换行），失败时返回 None
+fn query_tmux(mut command: Command) -> Option<String> {
+    let output = command.stderr(Stdio::null()).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string();
+    Some(value)
+}
+
+// 执行 tmux 命令，失败时只记录警告
+fn run_tmux(mut command: Command) {
+    let result = command.stdout(Stdio::null()).stderr(Stdio::null()).status();
+    match result {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!(%status, ?command, "tmux 命令失败"),
+        Err(e) => warn!(error = %e, "无法执行 tmux"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_characters_cannot_end_the_sequence() {
+        // ESC \ 会提前结束序列，之后的文字被当作终端输出
+        assert_eq!(title_sequence("a\x1b\\b"), "\x1bka \\b\x1b\\");
+        // BEL 同样可以结束 OSC 序列
+        assert_eq!(title_sequence("a\x07b"), "\x1bka b\x1b\\");
+        // C1 控制字符：CSI (U+009B) 和 ST (U+009C)
+        assert_eq!(title_sequence("a\u{9b}1m\u{9c}b"), "\x1bka 1m b\x1b\\");
+        assert_eq!(title_sequence("晴天"), "\x1bk晴天\x1b\\");
+    }
+
+    #[test]
+    fn window_name_joins_and_truncates() {
+        assert_eq!(window_name("晴天", "周杰伦"), "晴天 - 周杰伦");
+        assert_eq!(window_name("晴天", "未知"), "晴天");
+        assert_eq!(window_name("a\tb", "c\nd"), "a b - c d");
+        let long = window_name(&"长".repeat(30), "周杰伦");
+        assert!(long.ends_with('…'));
+        assert!(unicode_width::UnicodeWidthStr::width(long.as_str()) <= WINDOW_NAME_WIDTH);
+    }
+
+    #[test]
+    fn commands_target_the_player_pane() {
+        let args = |command: Command| command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect::<Vec<_>>();
+        assert_eq!(args(tmux_command("rename-window", Some("%3"), &["--", "-晴天"])), ["rename-window", "-t", "%3", "--", "-晴天"]);
+        assert_eq!(args(tmux_command("display-message", None, &["-p", "#W"])), ["display-message", "-p", "#W"]);
+        assert_eq!(
+            args(tmux_command("set-window-option", Some("%3"), &["-u", "automatic-rename"])),
+            ["set-window-option", "-t", "%3", "-u", "automatic-rename"]
+        );
+    }
+}
@@ -0,0 +1,486 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+// 引入 symphonia 库的解码相关类型
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as SymphoniaError,
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+use crate::cli::OutputFormat;
+use crate::metadata::probe_properties;
+use crate::utils::{format_duration, parallel_map};
+
+// FLAC 每帧的采样数（固定块大小）
+const FLAC_BLOCK_SIZE: usize = 4096;
+
+/// 输出流参数，所有歌曲必须一致才能拼接到同一个输出中
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct StreamSpec {
+    sample_rate: u32,
+    channels: u16,
+}
+
+/// 16 位 PCM 编码器
+trait PcmEncoder {
+    /// 写入交错排列的采样
+    fn write_samples(&mut self, samples: &[i16]) -> io::Result<()>;
+    /// 写出剩余数据并刷新输出
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// WAV 编码器。标准输出无法回写文件头，数据长度按流式惯例写为 0xFFFFFFFF。
+struct WavEncoder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> WavEncoder<W> {
+    fn new(mut out: W, spec: StreamSpec) -> io::Result<Self> {
+        let block_align = spec.channels * 2;
+        out.write_all(b"RIFF")?;
+        out.write_all(&u32::MAX.to_le_bytes())?;
+        out.write_all(b"WAVE")?;
+        out.write_all(b"fmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&spec.channels.to_le_bytes())?;
+        out.write_all(&spec.sample_rate.to_le_bytes())?;
+        out.write_all(&(spec.sample_rate * block_align as u32).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&u32::MAX.to_le_bytes())?;
+        Ok(WavEncoder { out })
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// 写完文件后回写 WAV 头中的 RIFF 块长度和数据长度（超过 4GB 时保留 0xFFFFFFFF）
+fn patch_wav_header(file: &mut File, data_len: u64) -> io::Result<()> {
+    let Ok(data_len) = u32::try_from(data_len) else { return Ok(()) };
+    let Some(riff_len) = data_len.checked_add(36) else { return Ok(()) };
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&riff_len.to_le_bytes())?;
+    file.seek(SeekFrom::Start(40))?;
+    file.write_all(&data_len.to_le_bytes())?;
+    file.flush()
+}
+
+impl<W: Write> PcmEncoder for WavEncoder<W> {
+    fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        for sample in samples {
+            self.out.write_all(&sample.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// 简单的 FLAC 编码器：每个子帧使用 VERBATIM（不压缩）编码。
+/// 输出是标准 FLAC 流，可被任意解码器读取；总采样数和 MD5 标记为未知。
+struct FlacEncoder<W: Write> {
+    out: W,
+    channels: usize,
+    pending: Vec<i16>,
+    frame_number: u64,
+}
+
+impl<W: Write> FlacEncoder<W> {
+    fn new(mut out: W, spec: StreamSpec) -> io::Result<Self> {
+        if spec.channels == 0 || spec.channels > 8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "FLAC 仅支持 1-8 个声道"));
+        }
+        out.write_all(b"fLaC")?;
+        // 元数据块头：最后一个块 | STREAMINFO，长度 34
+        out.write_all(&[0x80, 0x00, 0x00, 34])?;
+        let block_size = (FLAC_BLOCK_SIZE as u16).to_be_bytes();
+        out.write_all(&block_size)?; // 最小块大小
+        out.write_all(&block_size)?; // 最大块大小
+        out.write_all(&[0; 6])?; // 最小/最大帧大小未知
+        // 采样率(20 位) | 声道数-1(3 位) | 位深-1(5 位) | 总采样数(36 位，0 表示未知)
+        let packed = (spec.sample_rate as u64) << 44 | ((spec.channels - 1) as u64) << 41 | 15u64 << 36;
+        out.write_all(&packed.to_be_bytes())?;
+        out.write_all(&[0; 16])?; // MD5 未知
+        Ok(FlacEncoder {
+            out,
+            channels: spec.channels as usize,
+            pending: Vec::with_capacity(FLAC_BLOCK_SIZE * spec.channels as usize),
+            frame_number: 0,
+        })
+    }
+
+    fn write_frame(&mut self, interleaved: &[i16]) -> io::Result<()> {
+        let block_size = interleaved.len() / self.channels;
+        let mut frame = Vec::with_capacity(16 + interleaved.len() * 2);
+        // 同步码 + 固定块大小策略
+        frame.extend_from_slice(&[0xFF, 0xF8]);
+        // 块大小从帧头末尾的 16 位读取，采样率沿用 STREAMINFO
+        frame.push(0x70);
+        // 独立声道 + 16 位采样
+        frame.push(((self.channels - 1) as u8) << 4 | 0b100 << 1);
+        push_utf8_number(&mut frame, self.frame_number);
+        frame.extend_from_slice(&((block_size - 1) as u16).to_be_bytes());
+        frame.push(crc8(&frame));
+        for channel in 0..self.channels {
+            // 子帧头：VERBATIM，无 wasted bits
+            frame.push(0x02);
+            for i in 0..block_size {
+                frame.extend_from_slice(&interleaved[i * self.channels + channel].to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&crc16(&frame).to_be_bytes());
+        self.out.write_all(&frame)?;
+        self.frame_number += 1;
+        Ok(())
+    }
+}
+
+impl<W: Write> PcmEncoder for FlacEncoder<W> {
+    fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.pending.extend_from_slice(samples);
+        let frame_len = FLAC_BLOCK_SIZE * self.channels;
+        while self.pending.len() >= frame_len {
+            let block: Vec<i16> = self.pending.drain(..frame_len).collect();
+            self.write_frame(&block)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let block = std::mem::take(&mut self.pending);
+            self.write_frame(&block)?;
+        }
+        self.out.flush()
+    }
+}
+
+// FLAC 帧号使用类 UTF-8 的变长编码
+fn push_utf8_number(out: &mut Vec<u8>, value: u64) {
+    if value < 0x80 {
+        out.push(value as u8);
+        return;
+    }
+    let bytes = match value {
+        0..0x800 => 2,
+        0x800..0x10000 => 3,
+        0x10000..0x200000 => 4,
+        0x200000..0x4000000 => 5,
+        0x4000000..0x80000000 => 6,
+        _ => 7,
+    };
+    let lead = (0xFFu16 << (8 - bytes)) as u8;
+    out.push(lead | (value >> (6 * (bytes - 1))) as u8);
+    for i in (0..bytes - 1).rev() {
+        out.push(0x80 | ((value >> (6 * i)) & 0x3F) as u8);
+    }
+}
+
+// CRC-8，多项式 x^8 + x^2 + x + 1
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// CRC-16，多项式 x^16 + x^15 + x^2 + 1
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// 使用 symphonia 解码一首歌曲，每解码出一个数据包就回调一次（交错排列的 16 位采样）
+fn decode_track(
+    path: &Path,
+    mut on_samples: impl FnMut(StreamSpec, &[i16]) -> io::Result<()>,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::open(path)?;
+    let media_source_stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probe_result = symphonia::default::get_probe().format(
+        &hint,
+        media_source_stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probe_result.format;
+    let track = format.default_track().ok_or("未找到可解码的音轨")?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+    let mut sample_buffer: Option<SampleBuffer<i16>> = None;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // 读到文件末尾
+            Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // 跳过损坏的数据包
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let signal_spec = *decoded.spec();
+        let spec = StreamSpec {
+            sample_rate: signal_spec.rate,
+            channels: signal_spec.channels.count() as u16,
+        };
+        // 数据包比缓冲区大时重新分配
+        let needed = decoded.capacity() * signal_spec.channels.count();
+        if sample_buffer.as_ref().is_none_or(|buffer| buffer.capacity() < needed) {
+            sample_buffer = Some(SampleBuffer::new(decoded.capacity() as u64, signal_spec));
+        }
+        if let Some(buffer) = sample_buffer.as_mut() {
+            buffer.copy_interleaved_ref(decoded);
+            on_samples(spec, buffer.samples())?;
+        }
+    }
+    Ok(())
+}
+
+fn new_encoder(format: OutputFormat, out: BufWriter<io::Stdout>, spec: StreamSpec) -> io::Result<Box<dyn PcmEncoder>> {
+    Ok(match format {
+        OutputFormat::Wav => Box::new(WavEncoder::new(out, spec)?),
+        OutputFormat::Flac => Box::new(FlacEncoder::new(out, spec)?),
+    })
+}
+
+/// 将播放列表依次解码并编码为指定格式写到标准输出，不经过音频设备。
+/// 所有歌曲按顺序拼接，采样率或声道数与第一首不同的歌曲会被跳过。
+pub fn transcode_playlist(playlist: &[PathBuf], format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let total_tracks = playlist.len();
+    let mut encoder: Option<(StreamSpec, Box<dyn PcmEncoder>)> = None;
+
+    for (index, path) in playlist.iter().enumerate() {
+        eprintln!("[{}/{}] 转码: {}", index + 1, total_tracks, path.display());
+        let result = decode_track(path, |spec, samples| {
+            let (first_spec, encoder) = match encoder.as_mut() {
+                Some((first_spec, encoder)) => (*first_spec, encoder),
+                None => {
+                    let created = new_encoder(format, BufWriter::new(io::stdout()), spec)?;
+                    let (_, encoder) = encoder.insert((spec, created));
+                    (spec, encoder)
+                }
+            };
+            if first_spec != spec {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "采样率或声道数与第一首不一致"));
+            }
+            encoder.write_samples(samples)
+        });
+        if let Err(e) = result {
+            eprintln!("[错误]转码 {} 失败: {}，跳过。", path.display(), e);
+        }
+    }
+
+    match encoder {
+        Some((_, mut encoder)) => {
+            encoder.finish()?;
+            Ok(())
+        }
+        None => Err("没有可转码的音频".into()),
+    }
+}
+
+/// 把一首歌的采样转换为输出的声道数和采样率：声道数不同时复制或混合声道，采样率不同时线性插值
+struct Converter {
+    from: StreamSpec,
+    to: StreamSpec,
+    // 每输出一帧前进的输入帧数
+    step: f64,
+    // 下一个输出帧在 pending 中的位置（帧）
+    position: f64,
+    // 已转换声道、尚未用于插值的输入帧
+    pending: Vec<i16>,
+}
+
+impl Converter {
+    fn new(from: StreamSpec, to: StreamSpec) -> Self {
+        Converter { from, to, step: from.sample_rate as f64 / to.sample_rate as f64, position: 0.0, pending: Vec::new() }
+    }
+
+    // 转换一段交错排列的采样，结果追加到 out
+    fn push(&mut self, samples: &[i16], out: &mut Vec<i16>) {
+        let (from, to) = (self.from.channels as usize, self.to.channels as usize);
+        for frame in samples.chunks_exact(from) {
+            match (from, to) {
+                _ if from == to => self.pending.extend_from_slice(frame),
+                // 混合为单声道
+                (_, 1) => self.pending.push((frame.iter().map(|&s| s as i32).sum::<i32>() / from as i32) as i16),
+                // 声道不够时重复最后一个声道，多出的声道丢弃
+                _ => self.pending.extend((0..to).map(|channel| frame[channel.min(from - 1)])),
+            }
+        }
+        if self.from.sample_rate == self.to.sample_rate {
+            out.append(&mut self.pending);
+            return;
+        }
+        let frames = self.pending.len() / to;
+        while self.position + 1.0 < frames as f64 {
+            let index = self.position as usize;
+            let fraction = self.position - index as f64;
+            for channel in 0..to {
+                let a = self.pending[index * to + channel] as f64;
+                let b = self.pending[(index + 1) * to + channel] as f64;
+                out.push((a + (b - a) * fraction).round() as i16);
+            }
+            self.position += self.step;
+        }
+        // 丢掉已经用完的帧，保留插值还需要的部分
+        let consumed = (self.position as usize).min(frames);
+        self.pending.drain(..consumed * to);
+        self.position -= consumed as f64;
+    }
+}
+
+// 各首歌的采样率和声道数中的最大值，无法探测的歌曲不计入
+fn common_spec(playlist: &[PathBuf], rate: Option<u32>) -> Option<StreamSpec> {
+    let properties = parallel_map(playlist, "读取采样率", |path| probe_properties(path).ok());
+    let sample_rate = rate.or_else(|| properties.iter().flatten().filter_map(|p| p.sample_rate).max())?;
+    let channels = properties.iter().flatten().filter_map(|p| p.channels).max().unwrap_or(2);
+    Some(StreamSpec { sample_rate, channels: channels as u16 })
+}
+
+/// render 子命令：把播放列表依次解码，转换为统一的采样率（rate，默认取各首歌中最高的）和声道数，
+/// 歌曲之间插入 gap 的静音，写入 WAV 文件。无法解码的歌曲跳过
+pub fn render_playlist(playlist: &[PathBuf], output: &Path, rate: Option<u32>, gap: Duration) -> Result<(), Box<dyn Error>> {
+    let spec = common_spec(playlist, rate).ok_or("无法读取任何歌曲的采样率")?;
+    let channels = spec.channels as usize;
+    let mut encoder = WavEncoder::new(BufWriter::new(File::create(output)?), spec)?;
+    let gap_samples = vec![0i16; (gap.as_secs_f64() * spec.sample_rate as f64) as usize * channels];
+    let mut written_frames: u64 = 0;
+    let total_tracks = playlist.len();
+
+    for (index, path) in playlist.iter().enumerate() {
+        eprintln!("[{}/{}] 渲染: {}", index + 1, total_tracks, path.display());
+        if written_frames > 0 && !gap_samples.is_empty() {
+            encoder.write_samples(&gap_samples)?;
+            written_frames += (gap_samples.len() / channels) as u64;
+        }
+        let mut converter: Option<Converter> = None;
+        let mut converted = Vec::new();
+        let result = decode_track(path, |track_spec, samples| {
+            // 歌曲中途改变参数时重新开始转换
+            if converter.as_ref().is_none_or(|c| c.from != track_spec) {
+                converter = Some(Converter::new(track_spec, spec));
+            }
+            if let Some(converter) = converter.as_mut() {
+                converter.push(samples, &mut converted);
+            }
+            written_frames += (converted.len() / channels) as u64;
+            encoder.write_samples(&converted)?;
+            converted.clear();
+            Ok(())
+        });
+        if let Err(e) = result {
+            eprintln!("[错误]渲染 {} 失败: {}，跳过。", path.display(), e);
+        }
+    }
+
+    encoder.finish()?;
+    let mut file = encoder.into_inner().into_inner().map_err(|e| e.into_error())?;
+    patch_wav_header(&mut file, written_frames * channels as u64 * 2)?;
+    let duration = Duration::from_secs_f64(written_frames as f64 / spec.sample_rate as f64);
+    eprintln!("已写入 {}（{}，{} Hz，{} 声道）", output.display(), format_duration(duration), spec.sample_rate, spec.channels);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{fixture_dir, write_wav_fixture};
+
+    const MONO_22K: StreamSpec = StreamSpec { sample_rate: 22050, channels: 1 };
+    const STEREO_44K: StreamSpec = StreamSpec { sample_rate: 44100, channels: 2 };
+
+    // 分几段送入转换器，返回全部输出
+    fn convert(from: StreamSpec, to: StreamSpec, chunks: &[&[i16]]) -> Vec<i16> {
+        let mut converter = Converter::new(from, to);
+        let mut out = Vec::new();
+        for chunk in chunks {
+            converter.push(chunk, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn converter_interpolates_when_upsampling() {
+        let to = StreamSpec { sample_rate: 44100, channels: 1 };
+        // 每个输入帧之间插入一个中点，最后一帧之后没有可插值的帧
+        assert_eq!(convert(MONO_22K, to, &[&[0, 100, 200, 300]]), [0, 50, 100, 150, 200, 250]);
+        // 分段送入时插值跨过分段边界，结果不变
+        assert_eq!(convert(MONO_22K, to, &[&[0, 100], &[200], &[300]]), [0, 50, 100, 150, 200, 250]);
+    }
+
+    #[test]
+    fn converter_steps_over_frames_when_downsampling() {
+        let from = StreamSpec { sample_rate: 48000, channels: 1 };
+        let to = StreamSpec { sample_rate: 32000, channels: 1 };
+        let input: Vec<i16> = (0..10).map(|i| i * 10).collect();
+        // 每输出一帧前进 1.5 个输入帧
+        assert_eq!(convert(from, to, &[&input]), [0, 15, 30, 45, 60, 75]);
+        assert_eq!(convert(from, to, &[&input[..3], &input[3..7], &input[7..]]), [0, 15, 30, 45, 60, 75]);
+    }
+
+    #[test]
+    fn converter_mixes_and_copies_channels() {
+        let mono = StreamSpec { sample_rate: 44100, channels: 1 };
+        assert_eq!(convert(STEREO_44K, mono, &[&[100, -100, 50, 150]]), [0, 100]);
+        assert_eq!(convert(mono, STEREO_44K, &[&[7, -3]]), [7, 7, -3, -3]);
+        // 同时转换声道和采样率
+        assert_eq!(convert(MONO_22K, STEREO_44K, &[&[0, 100, 200]]), [0, 0, 50, 50, 100, 100, 150, 150]);
+    }
+
+    #[test]
+    fn render_patches_wav_lengths() {
+        let dir = fixture_dir("render");
+        // 0.1 秒的单声道 22050 Hz 和 0.1 秒的立体声 44100 Hz
+        let mono: Vec<i16> = (0..2205).map(|i| (i % 1000) as i16).collect();
+        let stereo: Vec<i16> = (0..4410).flat_map(|i| [i as i16, -(i as i16)]).collect();
+        let playlist = [write_wav_fixture(&dir, "mono.wav", 22050, 1, &mono), write_wav_fixture(&dir, "stereo.wav", 44100, 2, &stereo)];
+        let output = dir.join("out.wav");
+        render_playlist(&playlist, &output, None, Duration::from_millis(500)).unwrap();
+
+        let data = std::fs::read(&output).unwrap();
+        let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        let read_u16 = |offset: usize| u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap());
+        // 输出取两首中较高的采样率和较多的声道数
+        assert_eq!((read_u16(22), read_u32(24), read_u16(32)), (2, 44100, 4));
+        // 第一首升采样后为 2 * 2205 - 2 帧，之后是 0.5 秒的静音和第二首
+        let frames = (2 * 2205 - 2) + 22050 + 4410;
+        assert_eq!(read_u32(40) as usize, frames * 4);
+        assert_eq!(read_u32(4) as usize, 36 + frames * 4);
+        assert_eq!(data.len(), 44 + frames * 4);
+        // 静音之后紧接着第二首的第一帧
+        let second = 44 + ((2 * 2205 - 2) + 22050) * 4;
+        assert_eq!(&data[second - 4..second + 8], [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0xff, 0xff]);
+    }
+}
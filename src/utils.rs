@@ -1,64 +1,142 @@
 
-use std::{fs, io, path::{Path, PathBuf}};
-use std::time::Duration;
+use std::{fs, io, io::{Read, Write}, path::{Path, PathBuf}};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 // 引入 unicode_width 库
-use unicode_width::{UnicodeWidthStr, UnicodeWidthChar}; 
+use unicode_width::UnicodeWidthStr;
+// 按字形簇截断字符串
+use unicode_segmentation::UnicodeSegmentation;
 // 引入 glob 库用于通配符匹配
-use glob::glob as glob_func;
+use glob::{glob_with, MatchOptions};
+
+// 截断字符串时使用的省略号（单个字符，比 "..." 少占两列）
+const ELLIPSIS: &str = "…";
+// parallel_map 进度条的格数
+const PROGRESS_BAR_WIDTH: usize = 30;
+// 嵌套播放列表的最大层数
+const MAX_PLAYLIST_DEPTH: usize = 8;
+// 扫描目录时识别的音频扩展名（ape/wv 等格式解码失败时会在播放时提示并跳过）
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "ogg", "opus", "flac", "aac", "m4a", "wav", "wv", "ape", "mka"];
 // ----------------------------------------------------
 // --- 新增工具函数：智能解析输入 ---
 // ----------------------------------------------------
+/// 扫描目录和匹配通配符时的选项
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScanOptions {
+    /// 包含隐藏文件和目录（以 . 开头）
+    pub include_hidden: bool,
+    /// 跳过包含 .nomedia 文件的目录（Android 的约定）
+    pub ignore_nomedia: bool,
+    /// 在标准错误输出被跳过的 .nomedia 目录
+    pub verbose: bool,
+}
+
 /// 根据输入字符串智能判断其类型（文件、目录、播放列表文件或通配符），
-/// 并返回生成的音频文件列表。
-pub fn get_playlist_from_input(input: &str) -> Result<Vec<PathBuf>, io::Error> {
+/// 并返回生成的音频文件列表。目录扫描和通配符匹配按 options 跳过隐藏文件和 .nomedia 目录。
+/// 扩展 m3u 播放列表中的 #EXTINF 信息保存在对应条目的 display_name/duration_hint 中。
+pub fn get_playlist_from_input(input: &Path, options: ScanOptions) -> Result<Vec<PlaylistEntry>, io::Error> {
+    resolve_input(input, &mut Vec::new(), options)
+}
+
+// get_playlist_from_input 的实现，chain 为正在展开的播放列表（规范化路径），用于检测循环引用。
+// 只有最外层输入会输出检测提示，避免嵌套播放列表刷屏。
+fn resolve_input(input: &Path, chain: &mut Vec<PathBuf>, scan_options: ScanOptions) -> Result<Vec<PlaylistEntry>, io::Error> {
+    let is_top_level = chain.is_empty();
+    // 0. "-" 表示从标准输入读取路径列表（例如 fd -e flac | mddplayer -）
+    if input == Path::new("-") {
+        if is_top_level { eprintln!("从标准输入读取播放列表..."); }
+        return read_stdin_playlist(chain, scan_options);
+    }
+    // http(s) 地址为网络流，不检查路径是否存在
+    if let Some(url) = stream_url(input) {
+        if is_top_level { eprintln!("检测到网络流地址..."); }
+        return Ok(vec![PlaylistEntry::stream(url)]);
+    }
     // 1. 检查是否为通配符模式 (*.mp3, *.flac)
     // ⚠️ 注意：Rust 的 std::fs 目前不直接支持 shell 通配符展开。
     // 这里我们将使用 glob 库来实现，您需要在 Cargo.toml 中添加 `glob = "0.3"`
     // 并将 `use glob::glob;` 添加到文件顶部。
     // 我们先假设您已在 main.rs 顶部添加了 use glob::glob;
     // 如果没有，可以先跳过通配符解析，只实现文件/目录/文本判断。
-    if input.contains('*') {
-        // 处理通配符，例如 "songs/*.mp3"
+    // 通配符模式只能是合法的 Unicode，非 UTF-8 的路径按普通路径处理
+    if let Some(input) = input.to_str().filter(|input| input.contains('*')) {
+        // 处理通配符，例如 "songs/*.mp3"；匹配到的目录按目录扫描，例如 "Music/周杰伦*"
+        // Windows 下路径不区分大小写，通配符同样不区分；反斜杠统一为 /（glob 在 Windows 下两者都视为分隔符）
+        let input = if cfg!(windows) { input.replace('\\', "/") } else { input.to_string() };
         let mut paths = Vec::new();
-        match glob_func(input) {
+        // 默认不匹配以 . 开头的文件和目录（例如 .git 下的文件）
+        let options = MatchOptions {
+            case_sensitive: !cfg!(windows),
+            require_literal_leading_dot: !scan_options.include_hidden,
+            ..MatchOptions::new()
+        };
+        // 通配符之前的固定部分，只检查这之下的目录是否有 .nomedia
+        let glob_base: PathBuf = Path::new(&input).components().take_while(|c| !c.as_os_str().to_string_lossy().contains('*')).collect();
+        let mut nomedia_dirs: HashMap<PathBuf, bool> = HashMap::new();
+        let mut matched_any = false;
+        match glob_with(&input, options) {
             Ok(entries) => {
                 for entry in entries {
                     match entry {
                         Ok(path) => {
-                            if path.is_file() {
+                            matched_any = true;
+                            if (path.is_file() || path.is_dir()) && !(scan_options.ignore_nomedia && in_nomedia_dir(&path, &glob_base, &mut nomedia_dirs, scan_options.verbose)) {
                                 paths.push(path);
                             }
                         },
                         Err(e) => eprintln!("通配符匹配错误: {:?}", e),
                     }
                 }
-                return Ok(paths);
             },
             Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("通配符模式错误: {}", e))),
         }
+        if !matched_any {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "通配符未匹配到任何文件"));
+        }
+        // 按匹配结果的自然顺序展开，目录中的歌曲接在目录所在的位置
+        sort_naturally(&mut paths);
+        let mut playlist = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                // 一个目录读取失败（例如没有权限）时跳过它，不影响其他匹配结果
+                match scan_audio_files(&path, scan_options) {
+                    Ok(entries) => playlist.extend(entries),
+                    Err(e) => eprintln!("[跳过]无法读取目录 {}: {}", path.display(), e),
+                }
+            } else {
+                playlist.push(PlaylistEntry::new(path));
+            }
+        }
+        return Ok(playlist);
     }
     // 2. 尝试将输入转换为 PathBuf
-    let path = PathBuf::from(input);
+    let path = input.to_path_buf();
     // 3. 判断路径是否存在
     if !path.exists() {
-        return Err(io::Error::new(io::ErrorKind::NotFound, format!("路径或文件不存在: {}", input)));
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("路径或文件不存在: {}", input.display())));
     }
     // 4. 判断类型
     if path.is_dir() {
         // 如果是目录，扫描目录下的所有音频文件
-        println!("检测到目录，扫描音频文件...");
-        scan_audio_files(&path) // 假设此函数在 utils 中
+        if is_top_level { eprintln!("检测到目录，扫描音频文件..."); }
+        scan_audio_files(&path, scan_options) // 假设此函数在 utils 中
     } else if path.is_file() {
         // 检查文件扩展名，判断是音频媒体文件还是播放列表文件
-        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
-        if ext == "txt" {
+        if is_playlist_file(&path) {
             // 如果是播放列表或文本文件，尝试解析播放列表
-            println!("检测到播放列表文件，开始解析...");
-            read_playlist_file(&path) // 假设此函数在 utils 中
+            if is_top_level { eprintln!("检测到播放列表文件，开始解析..."); }
+            read_playlist_file(&path, chain, scan_options)
         } else {
             // 默认视为单个音频文件
-            println!("检测到单个音频文件，作为单曲播放...");
-            Ok(vec![path])
+            if is_top_level { eprintln!("检测到单个音频文件，作为单曲播放..."); }
+            Ok(vec![PlaylistEntry::new(path)])
         }
     } else {
         // 其他类型 (如符号链接等，这里简化处理为无法解析)
@@ -66,86 +144,636 @@ pub fn get_playlist_from_input(input: &str) -> Result<Vec<PathBuf>, io::Error> {
     }
 }
 
-/// 根据终端显示宽度截断字符串，并在末尾添加 "..."。
+/// 根据终端显示宽度截断字符串，并在末尾添加 "…"。
+/// 按字形簇（grapheme cluster）截断，组合音标、emoji ZWJ 序列和谚文音节不会被从中间拆开；
+/// 只有确实需要截断时才为省略号预留宽度。
 pub fn truncate_string(s: &str, max_width: usize) -> String {
-    // 1. 保留 3 个列宽给 "..."
-    let ellipsis_width = 3;
-    if max_width < ellipsis_width { return String::new(); }
-    // 1. 获取最大显示宽度
-    let max_content_width = max_width.saturating_sub(ellipsis_width);
-    // 2. 检查原始字符串的显示宽度 (使用 .width() 替代 UnicodeWidthChar::width)
-    let original_display_width = s.width(); // 🌟 直接在 &str 上调用 .width()
-    // 如果原始字符串的显示宽度已经小于等于最大内容宽度，则直接返回
-    if original_display_width <= max_width {
+    // 1. 宽度足够时原样返回
+    if s.width() <= max_width {
         return s.to_string();
     }
-    // 3. 截断逻辑：基于宽度迭代
-    let mut current_width = 0; // 🎯 修复 E0425：声明并初始化宽度变量
+    // 2. 需要截断：预留省略号的宽度，连省略号都放不下时返回空字符串
+    let ellipsis_width = ELLIPSIS.width();
+    if max_width < ellipsis_width { return String::new(); }
+    let max_content_width = max_width - ellipsis_width;
+    // 3. 逐个字形簇累加宽度，放不下时停止
+    let mut current_width = 0;
     let mut truncated_string = String::new();
-    for c in s.chars() {
-        // 现在直接在 char 上调用 .width()
-        let char_width = c.width().unwrap_or(0);
-        // 如果加上这个字符后超过了可容纳的最大内容宽度，则停止
-        if current_width + char_width > max_content_width {
-            break; 
+    for grapheme in s.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if current_width + grapheme_width > max_content_width {
+            break;
         }
-        truncated_string.push(c);
-        current_width += char_width;
+        truncated_string.push_str(grapheme);
+        current_width += grapheme_width;
     }
-    
     // 4. 返回截断后的字符串并加上省略号
-    format!("{}...", truncated_string)
+    format!("{}{}", truncated_string, ELLIPSIS)
 }
 
-/// 递归/非递归扫描指定路径，返回支持的音频文件列表。
-pub fn scan_audio_files(input_path: &Path) -> io::Result<Vec<PathBuf>> {
+/// 判断是否为隐藏文件或目录（文件名以 . 开头，例如 .DS_Store、.#song.mp3）
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.'))
+}
+
+/// 目录中是否有 .nomedia 文件（Android 等用它标记不应被媒体库索引的目录）
+fn has_nomedia(dir: &Path) -> bool {
+    dir.join(".nomedia").exists()
+}
+
+// 通配符匹配到的文件是否位于带 .nomedia 的目录中（只检查 base 之下的各级目录，结果缓存在 checked 中）
+fn in_nomedia_dir(path: &Path, base: &Path, checked: &mut HashMap<PathBuf, bool>, verbose: bool) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(base))
+        // 相对路径的最后一级为空，表示当前目录
+        .map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+        .any(|dir| {
+            *checked.entry(dir.to_path_buf()).or_insert_with(|| {
+                let found = has_nomedia(dir);
+                if found && verbose {
+                    eprintln!("[跳过]目录包含 .nomedia: {}", dir.display());
+                }
+                found
+            })
+        })
+}
+
+/// 递归/非递归扫描指定路径，返回支持的音频文件列表（条目只有路径）。
+/// options.ignore_nomedia 为 true 时，包含 .nomedia 文件的目录整个跳过。
+pub fn scan_audio_files(input_path: &Path, options: ScanOptions) -> io::Result<Vec<PlaylistEntry>> {
     let mut files = Vec::new();
     
     // 如果是单个文件，直接添加
     if input_path.is_file() {
         // 在此处也可以添加扩展名检查，但为简化逻辑，假设用户直接指定的文件是音频文件
-        files.push(input_path.to_path_buf());
-        return Ok(files);
+        return Ok(vec![PlaylistEntry::new(input_path.to_path_buf())]);
     }
     
+    // 包含 .nomedia 的目录不扫描
+    if options.ignore_nomedia && input_path.is_dir() && has_nomedia(input_path) {
+        if options.verbose {
+            eprintln!("[跳过]目录包含 .nomedia: {}", input_path.display());
+        }
+        return Ok(Vec::new());
+    }
+
     // 如果是目录，遍历并筛选文件
     if input_path.is_dir() {
         for entry in fs::read_dir(input_path)? {
             let entry = entry?;
             let path = entry.path();
-            if path.is_file() {
-                if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                    let ext = ext.to_lowercase();
-                    // 核心筛选逻辑：仅添加支持的音频格式
-                    if ext == "mp3" || ext == "ogg" || ext == "flac" || ext == "aac" || ext == "m4a" || ext == "wav" { 
-                        files.push(path);
-                    }
+            if !options.include_hidden && is_hidden(&path) {
+                continue;
+            }
+            // 核心筛选逻辑：仅添加支持的音频格式
+            if path.is_file() && has_audio_extension(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    // read_dir 不保证顺序，按自然顺序排列（Track 2 在 Track 10 之前）
+    sort_naturally(&mut files);
+    Ok(files.into_iter().map(PlaylistEntry::new).collect())
+}
+
+/// 目录下（不含子目录）是否有音频文件，找到第一个就停止；跳过的文件和目录与 scan_audio_files 相同
+pub fn has_audio_files(dir: &Path, options: ScanOptions) -> bool {
+    if options.ignore_nomedia && has_nomedia(dir) {
+        return false;
+    }
+    let Ok(entries) = fs::read_dir(dir) else { return false };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .any(|path| (options.include_hidden || !is_hidden(&path)) && path.is_file() && has_audio_extension(&path))
+}
+
+/// 扩展名是否在目录扫描识别的音频格式列表中
+pub fn has_audio_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// 按 CPU 核数开启工作线程从同一个队列取任务，对播放列表中的每首歌调用 read，按播放列表顺序返回结果。
+/// 进度条（以 label 开头）输出到标准错误
+pub fn parallel_map<T: Send>(playlist: &[PathBuf], label: &str, read: impl Fn(&Path) -> T + Sync) -> Vec<T> {
+    let total = playlist.len();
+    let workers = thread::available_parallelism().map_or(4, |n| n.get()).min(total.max(1));
+    let next_index = AtomicUsize::new(0);
+    let mut results: Vec<Option<T>> = (0..total).map(|_| None).collect();
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..workers {
+            let tx = tx.clone();
+            let next_index = &next_index;
+            let read = &read;
+            scope.spawn(move || loop {
+                let index = next_index.fetch_add(1, AtomicOrdering::Relaxed);
+                let Some(path) = playlist.get(index) else { break };
+                if tx.send((index, read(path))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        let mut done = 0;
+        print_progress(label, done, total);
+        for (index, data) in rx {
+            results[index] = Some(data);
+            done += 1;
+            print_progress(label, done, total);
+        }
+    });
+    eprintln!();
+    results.into_iter().flatten().collect()
+}
+
+fn print_progress(label: &str, done: usize, total: usize) {
+    let filled = (done * PROGRESS_BAR_WIDTH).checked_div(total).unwrap_or(PROGRESS_BAR_WIDTH);
+    eprint!(
+        "\r{} [{}{}] {}/{}",
+        label,
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        done,
+        total
+    );
+}
+
+/// 转义 JSON 字符串中的特殊字符
+pub fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 自然排序比较：连续的数字按数值比较，其余字符不区分大小写比较。
+/// 例如 "Track 2" 排在 "Track 10" 之前。
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            // 完全相同时按原始字符串比较，保证结果稳定（如 "01" 与 "1"）
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_digits = take_digits(&mut a_chars);
+                let y_digits = take_digits(&mut b_chars);
+                // 去掉前导零后，先比较位数再逐位比较，避免大数溢出
+                let x_value = x_digits.trim_start_matches('0');
+                let y_value = y_digits.trim_start_matches('0');
+                let ordering = x_value.len().cmp(&y_value.len()).then_with(|| x_value.cmp(y_value));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
                 }
+                a_chars.next();
+                b_chars.next();
             }
         }
     }
+}
 
-    Ok(files)
+// 取出连续的数字字符
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.peek().copied() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        digits.push(c);
+        chars.next();
+    }
+    digits
 }
-/// 从 .txt 文件中读取播放列表路径，每行一个路径。
-pub fn read_playlist_file(path: &Path) -> io::Result<Vec<PathBuf>> {
-    // 尝试将整个文件内容读取为字符串
-    let content = fs::read_to_string(path)?;
-    
-    let files: Vec<PathBuf> = content
-        .lines()              // 按行迭代
-        .map(|line| line.trim()) // 移除每行首尾空白
-        .filter(|line| !line.is_empty()) // 忽略空行
-        .map(|line| PathBuf::from(line)) // 将字符串转换为 PathBuf
-        .collect();
-    
-    if files.is_empty() {
+
+/// 按路径的自然顺序排序
+pub fn sort_naturally(paths: &mut [PathBuf]) {
+    paths.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+}
+/// 判断是否为播放列表文件（.txt / .m3u / .m3u8）
+pub fn is_playlist_file(path: &Path) -> bool {
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    path.is_file() && matches!(ext.as_str(), "txt" | "m3u" | "m3u8")
+}
+
+/// 从 .txt/.m3u 文件中读取播放列表路径，每行一个路径。
+/// 每行也可以是目录、通配符或另一个播放列表，会按文件中的顺序展开；
+/// chain 记录当前正在展开的播放列表，用于检测循环引用和限制嵌套层数。
+/// 普通文件条目前的 #EXTINF 行填入条目的 display_name 和 duration_hint，标签缺失时作为后备。
+pub fn read_playlist_file(path: &Path, chain: &mut Vec<PathBuf>, scan_options: ScanOptions) -> io::Result<Vec<PlaylistEntry>> {
+    let canonical = fs::canonicalize(path)?;
+    if chain.contains(&canonical) {
+        // 错误信息列出完整的引用链，例如 a.txt -> b.txt -> a.txt
+        let cycle: Vec<String> = chain
+            .iter()
+            .skip_while(|p| **p != canonical)
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("播放列表循环引用: {}", cycle.join(" -> "))));
+    }
+    if chain.len() >= MAX_PLAYLIST_DEPTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("播放列表嵌套超过 {} 层: {}", MAX_PLAYLIST_DEPTH, path.display())));
+    }
+
+    // 按字节读取，路径不要求是合法的 UTF-8（见 path_from_bytes）
+    let content = fs::read(path)?;
+    chain.push(canonical);
+    // 相对路径相对于播放列表文件所在目录解析，而不是当前工作目录
+    let playlist_dir = path.parent().unwrap_or(Path::new("."));
+    let files = parse_playlist_lines(&content, playlist_dir, chain, scan_options);
+    chain.pop();
+    let files = files?;
+
+    if files.is_empty() && chain.is_empty() {
         return Err(io::Error::new(io::ErrorKind::InvalidData, "播放列表文件为空或不包含有效路径。"));
     }
-    
+
     Ok(files)
 }
 
+/// 从标准输入读取播放列表（输入参数为 "-" 时），格式与播放列表文件相同，相对路径相对于当前工作目录。
+/// 标准输入为空时返回空列表，由调用方按“未找到音频文件”处理。
+pub fn read_stdin_playlist(chain: &mut Vec<PathBuf>, scan_options: ScanOptions) -> io::Result<Vec<PlaylistEntry>> {
+    let mut content = Vec::new();
+    io::stdin().read_to_end(&mut content)?;
+    parse_playlist_lines(&content, Path::new(""), chain, scan_options)
+}
+
+// 逐行解析播放列表内容，相对路径相对于 base_dir 解析。
+// 内容按字节处理：#EXTINF 等注释按 UTF-8 解码，路径行原样转换为路径
+fn parse_playlist_lines(content: &[u8], base_dir: &Path, chain: &mut Vec<PathBuf>, scan_options: ScanOptions) -> io::Result<Vec<PlaylistEntry>> {
+    let mut files = Vec::new();
+    let lines = content
+        .split(|&byte| byte == b'\n') // 按行迭代
+        .map(|line| line.strip_prefix(b"\xEF\xBB\xBF".as_slice()).unwrap_or(line).trim_ascii()) // 移除 BOM 和每行首尾空白（包括 \r）
+        .filter(|line| !line.is_empty()); // 忽略空行
+    // 上一行 #EXTINF 的信息，属于紧随其后的条目
+    let mut pending_extinf = None;
+    for line in lines {
+        if line.starts_with(b"#") {
+            if let Some(info) = String::from_utf8_lossy(line).strip_prefix("#EXTINF:") {
+                pending_extinf = parse_extinf(info);
+            }
+            // 其他 m3u 注释和指令
+            continue;
+        }
+        let line_path = path_from_bytes(line);
+        // 网络流地址原样加入，不相对于 base_dir 解析
+        if let Some(url) = stream_url(&line_path) {
+            let (display_name, duration_hint) = pending_extinf.take().unwrap_or_default();
+            files.push(PlaylistEntry { display_name, duration_hint, ..PlaylistEntry::stream(url) });
+            continue;
+        }
+        let entry = if line_path.is_absolute() { line_path } else { base_dir.join(line_path) };
+        let entry_extinf = pending_extinf.take();
+        if line.contains(&b'*') || entry.is_dir() || is_playlist_file(&entry) {
+            // 目录、通配符和嵌套的播放列表交给 resolve_input 展开
+            files.extend(resolve_input(&entry, chain, scan_options)?);
+        } else {
+            // 普通条目直接加入，不存在的文件提示后保留，播放前统一去掉（--strict 时报错退出）
+            if !entry.exists() {
+                eprintln!("[警告]播放列表条目不存在: {}", entry.display());
+            }
+            let (display_name, duration_hint) = entry_extinf.unwrap_or_default();
+            files.push(PlaylistEntry { path: entry, display_name, duration_hint, source_type: SourceType::LocalFile });
+        }
+    }
+    Ok(files)
+}
+
+/// 播放列表条目：文件路径，以及播放列表文件（m3u 的 #EXTINF）提供的显示名和时长
+#[derive(Clone, Debug)]
+pub struct PlaylistEntry {
+    /// 网络流的路径即为其地址
+    pub path: PathBuf,
+    /// 显示名，通常为 "艺术家 - 标题"
+    pub display_name: Option<String>,
+    pub duration_hint: Option<Duration>,
+    pub source_type: SourceType,
+}
+
+/// 条目的来源：本地文件，或者 http(s) 网络流（没有固定时长，不能定位，边下载边解码）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceType {
+    LocalFile,
+    NetworkStream(String),
+}
+
+impl PlaylistEntry {
+    /// 只有路径、没有附加信息的条目（目录扫描、通配符、单个文件）
+    pub fn new(path: PathBuf) -> Self {
+        PlaylistEntry { path, display_name: None, duration_hint: None, source_type: SourceType::LocalFile }
+    }
+
+    /// 网络流条目
+    pub fn stream(url: &str) -> Self {
+        PlaylistEntry { source_type: SourceType::NetworkStream(url.to_string()), ..PlaylistEntry::new(PathBuf::from(url)) }
+    }
+}
+
+/// 路径是 http:// 或 https:// 开头的网络流地址时返回地址
+pub fn stream_url(path: &Path) -> Option<&str> {
+    let url = path.to_str()?;
+    let scheme = url.get(..url.find("://")?)?;
+    (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")).then_some(url)
+}
+
+// 解析 "#EXTINF:" 之后的部分，格式为 "时长,艺术家 - 标题"，时长 -1 或 0 表示未知。
+// 返回 (显示名, 时长)，都缺失时返回 None
+fn parse_extinf(info: &str) -> Option<(Option<String>, Option<Duration>)> {
+    let (seconds, name) = info.split_once(',').unwrap_or((info, ""));
+    // 时长之后可能带有 tvg-id="..." 之类的属性，只取第一个数字
+    let duration = seconds
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|secs| secs.is_finite() && *secs > 0.0)
+        .map(Duration::from_secs_f64);
+    let name = name.trim();
+    let display_name = Some(name).filter(|n| !n.is_empty()).map(str::to_string);
+    if display_name.is_none() && duration.is_none() {
+        return None;
+    }
+    Some((display_name, duration))
+}
+
+/// 把 "艺术家 - 标题" 拆成 (艺术家, 标题)；没有分隔符时整体作为标题
+pub fn split_artist_title(text: &str) -> (Option<&str>, &str) {
+    match text.split_once(" - ") {
+        Some((artist, title)) if !artist.trim().is_empty() && !title.trim().is_empty() => {
+            (Some(artist.trim()), title.trim())
+        }
+        _ => (None, text.trim()),
+    }
+}
+
+/// 将播放列表按当前顺序保存到文件。扩展名为 .m3u/.m3u8 时写出 #EXTM3U/#EXTINF 信息
+/// （标题、艺术家、时长取自 known_info，未知的用文件名代替），否则每行一个路径。
+/// 位于目标文件所在目录下的歌曲写相对路径，其余写绝对路径，网络流写原来的地址。
+/// 先写入临时文件再重命名，中途崩溃也不会留下写了一半的播放列表。
+pub fn save_playlist(
+    target: &Path,
+    playlist: &[PathBuf],
+    known_info: &HashMap<PathBuf, (String, String, Duration)>,
+) -> io::Result<()> {
+    let target = std::path::absolute(target)?;
+    let base_dir = target.parent().map(Path::to_path_buf).unwrap_or_default();
+    let ext = target.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    let is_m3u = ext == "m3u" || ext == "m3u8";
+
+    // 路径按 path_to_bytes 原样写入，非 UTF-8 的文件名也能在读取时还原
+    let mut content = Vec::new();
+    if is_m3u {
+        content.extend_from_slice(b"#EXTM3U\n");
+    }
+    for path in playlist {
+        if is_m3u {
+            match known_info.get(path) {
+                Some((title, artist, duration)) => {
+                    // 时长未知时按规范写 -1
+                    let seconds = if duration.is_zero() { -1 } else { duration.as_secs() as i64 };
+                    content.extend_from_slice(format!("#EXTINF:{},{} - {}\n", seconds, artist, title).as_bytes());
+                }
+                None => {
+                    let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                    content.extend_from_slice(format!("#EXTINF:-1,{}\n", name).as_bytes());
+                }
+            }
+        }
+        // 网络流地址原样写入，不是文件路径
+        if let Some(url) = stream_url(path) {
+            content.extend_from_slice(url.as_bytes());
+        } else {
+            let absolute = std::path::absolute(path)?;
+            let written = absolute.strip_prefix(&base_dir).unwrap_or(&absolute);
+            content.extend_from_slice(&path_to_bytes(written));
+        }
+        content.push(b'\n');
+    }
+
+    let file_name = target.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp_path = target.with_file_name(format!(".{}.tmp", file_name));
+    {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(&content)?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp_path, &target)
+}
+
+/// 路径保存到播放列表、排除列表等文件时使用的字节：Unix 上为文件名的原始字节，Windows 上为 WTF-8
+/// （未配对的代理项也能保存）。合法 Unicode 的路径与 UTF-8 编码相同。
+/// 不使用 OsStr::as_encoded_bytes：它的编码没有保证，不能写入文件
+#[cfg(unix)]
+pub fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+/// path_to_bytes 的 Windows 实现：把 UTF-16 编码为 WTF-8（代理对合并为一个字符，未配对的代理项按 3 字节编码）
+#[cfg(windows)]
+pub fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::windows::ffi::OsStrExt;
+    let mut bytes = Vec::new();
+    let mut buf = [0; 4];
+    for unit in char::decode_utf16(path.as_os_str().encode_wide()) {
+        match unit {
+            Ok(c) => bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes()),
+            Err(e) => {
+                let surrogate = e.unpaired_surrogate();
+                bytes.extend_from_slice(&[0xE0 | (surrogate >> 12) as u8, 0x80 | ((surrogate >> 6) & 0x3F) as u8, 0x80 | (surrogate & 0x3F) as u8]);
+            }
+        }
+    }
+    Cow::Owned(bytes)
+}
+
+/// path_to_bytes 在其他平台上的实现：只支持 UTF-8
+#[cfg(not(any(unix, windows)))]
+pub fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+        Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+    }
+}
+
+/// path_to_bytes 的逆操作，从文件中读到的一行字节转换为路径
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+/// path_from_bytes 的 Windows 实现：按 WTF-8 解码为 UTF-16，无效的字节替换为 U+FFFD
+#[cfg(windows)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::windows::ffi::OsStringExt;
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (code_point, len) = decode_wtf8(rest).unwrap_or((0xFFFD, 1));
+        rest = &rest[len..];
+        if code_point >= 0x10000 {
+            let offset = code_point - 0x10000;
+            units.push(0xD800 | (offset >> 10) as u16);
+            units.push(0xDC00 | (offset & 0x3FF) as u16);
+        } else {
+            units.push(code_point as u16);
+        }
+    }
+    PathBuf::from(std::ffi::OsString::from_wide(&units))
+}
+
+// 解码开头的一个 WTF-8 字符（与 UTF-8 相同，但允许编码代理项），返回 (码点, 字节数)
+#[cfg(windows)]
+fn decode_wtf8(bytes: &[u8]) -> Option<(u32, usize)> {
+    let first = bytes[0];
+    let (len, initial, min) = match first {
+        0x00..=0x7F => return Some((first as u32, 1)),
+        0xC2..=0xDF => (2, first & 0x1F, 0x80),
+        0xE0..=0xEF => (3, first & 0x0F, 0x800),
+        0xF0..=0xF4 => (4, first & 0x07, 0x10000),
+        _ => return None,
+    };
+    let mut code_point = initial as u32;
+    for &byte in bytes.get(1..len)? {
+        if byte & 0xC0 != 0x80 {
+            return None;
+        }
+        code_point = (code_point << 6) | (byte & 0x3F) as u32;
+    }
+    (min..=0x10FFFF).contains(&code_point).then_some((code_point, len))
+}
+
+/// path_from_bytes 在其他平台上的实现：只支持 UTF-8
+#[cfg(not(any(unix, windows)))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// 按键防抖：两次触发的间隔小于 min_interval 时忽略后一次
+pub struct Debouncer {
+    last_fire: Instant,
+    min_interval: Duration,
+}
+
+impl Debouncer {
+    /// 创建后第一次 trigger 总是成功
+    pub fn new(min_interval: Duration) -> Self {
+        Debouncer { last_fire: Instant::now() - min_interval, min_interval }
+    }
+
+    /// 距上次成功触发已超过最小间隔时返回 true 并记录本次触发时间，否则返回 false
+    pub fn trigger(&mut self) -> bool {
+        if self.last_fire.elapsed() < self.min_interval {
+            return false;
+        }
+        self.last_fire = Instant::now();
+        true
+    }
+}
+
+/// 在系统文件管理器中显示文件：Windows 和 macOS 打开所在文件夹并选中文件，Linux 打开所在文件夹。
+/// 文件管理器在后台启动，不等待其退出；启动失败时返回错误
+pub fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let mut child = reveal_command(&path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    // 在后台回收子进程，避免留下僵尸进程
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// 打开文件管理器的命令：explorer 自己解析命令行，“/select,” 和带引号的路径必须原样传入，
+// 不能让 Command::arg 给整个参数加引号；路径去掉 canonicalize 加上的 “\\?\” 前缀（explorer 不认）
+#[cfg(target_os = "windows")]
+fn reveal_command(path: &Path) -> std::process::Command {
+    use std::os::windows::process::CommandExt;
+    let mut select = std::ffi::OsString::from("/select,\"");
+    select.push(strip_verbatim_prefix(path));
+    select.push("\"");
+    let mut command = std::process::Command::new("explorer");
+    command.raw_arg(select);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Path) -> std::process::Command {
+    let mut command = std::process::Command::new("open");
+    command.arg("-R").arg(path);
+    command
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn reveal_command(path: &Path) -> std::process::Command {
+    let mut command = std::process::Command::new("xdg-open");
+    command.arg(path.parent().unwrap_or(path));
+    command
+}
+
+// 去掉 Windows 的 verbatim 路径前缀：“\\?\C:\music” 变为 “C:\music”，“\\?\UNC\server\share” 变为 “\\server\share”
+#[cfg(any(target_os = "windows", test))]
+fn strip_verbatim_prefix(path: &Path) -> PathBuf {
+    let Some(text) = path.to_str() else { return path.to_path_buf() };
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", rest))
+    } else if let Some(rest) = text.strip_prefix(r"\\?\") {
+        PathBuf::from(rest)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// 将字节数格式化为便于阅读的大小，例如 "128 KB"、"3.2 MB"、"4.7 GB"（小于 100 时保留一位小数）。
+/// 默认使用十进制前缀（1 KB = 1000 字节），binary 为 true 时（--binary-sizes）使用二进制前缀（1 KiB = 1024 字节）
+pub fn format_file_size(bytes: u64, binary: bool) -> String {
+    let (base, units) = if binary {
+        (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB"])
+    } else {
+        (1000.0, ["B", "KB", "MB", "GB", "TB", "PB"])
+    };
+    if (bytes as f64) < base {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    // 四舍五入后会显示为 base 时进到下一级单位（例如 999.96 KB 显示为 "1.0 MB"，而不是 "1000 KB"）
+    while value >= base - 0.5 && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    if value < 99.95 {
+        format!("{:.1} {}", value, units[unit])
+    } else {
+        format!("{:.0} {}", value, units[unit])
+    }
+}
+
 /// 将 Duration 格式化为 "MM:SS" 字符串。
 pub fn format_duration(duration: Duration) -> String {
     let secs = duration.as_secs();
@@ -154,4 +782,374 @@ pub fn format_duration(duration: Duration) -> String {
     } else {
         "??:??".to_string()
     }
-}
\ No newline at end of file
+}
+
+/// 将 Duration 格式化为精确到毫秒的 "MM:SS.mmm" 字符串（定位后短暂显示），0 显示为 "00:00.000"
+pub fn format_duration_precise(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}.{:03}", secs / 60, secs % 60, duration.subsec_millis())
+}
+
+/// 测试用的临时目录：每次调用都清空后重新创建，路径包含进程号，避免并行运行的测试互相干扰
+#[cfg(test)]
+pub fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mddplayer-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 在测试目录中创建文件（包括所在的子目录）
+#[cfg(test)]
+pub fn write_fixture(dir: &Path, relative: &str, content: impl AsRef<[u8]>) -> PathBuf {
+    let path = dir.join(relative);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, content).unwrap();
+    path
+}
+
+/// 在测试目录中创建 16 位 PCM 的 WAV 文件，samples 为交错排列的采样
+#[cfg(test)]
+pub fn write_wav_fixture(dir: &Path, relative: &str, sample_rate: u32, channels: u16, samples: &[i16]) -> PathBuf {
+    let data_len = samples.len() as u32 * 2;
+    let mut data = Vec::with_capacity(44 + data_len as usize);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + data_len).to_le_bytes());
+    data.extend_from_slice(b"WAVEfmt ");
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&channels.to_le_bytes());
+    data.extend_from_slice(&sample_rate.to_le_bytes());
+    data.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+    data.extend_from_slice(&(channels * 2).to_le_bytes());
+    data.extend_from_slice(&16u16.to_le_bytes());
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+    write_fixture(dir, relative, data)
+}
+
+/// 在测试目录中创建带 ID3v2.4 标签的 MP3（标签后是几帧静音），frames 为 (帧 ID, 文本)，例如 ("TIT2", "标题")
+#[cfg(test)]
+pub fn write_tagged_mp3(dir: &Path, relative: &str, frames: &[(&str, &str)]) -> PathBuf {
+    let synchsafe = |size: usize| [(size >> 21) as u8 & 0x7f, (size >> 14) as u8 & 0x7f, (size >> 7) as u8 & 0x7f, size as u8 & 0x7f];
+    let mut body = Vec::new();
+    for (id, text) in frames {
+        body.extend_from_slice(id.as_bytes());
+        body.extend_from_slice(&synchsafe(text.len() + 1));
+        // 帧标志，然后是文本编码（3 = UTF-8）
+        body.extend_from_slice(&[0, 0, 3]);
+        body.extend_from_slice(text.as_bytes());
+    }
+    let mut data = b"ID3\x04\x00\x00".to_vec();
+    data.extend_from_slice(&synchsafe(body.len()));
+    data.extend(body);
+    // MPEG-1 Layer III，128 kbps，44.1 kHz，每帧 417 字节
+    for _ in 0..4 {
+        data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+        data.extend(std::iter::repeat_n(0, 413));
+    }
+    write_fixture(dir, relative, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbatim_prefix_is_removed() {
+        assert_eq!(strip_verbatim_prefix(Path::new(r"\\?\C:\My Music\a.mp3")), PathBuf::from(r"C:\My Music\a.mp3"));
+        assert_eq!(strip_verbatim_prefix(Path::new(r"\\?\UNC\nas\music\a.mp3")), PathBuf::from(r"\\nas\music\a.mp3"));
+        assert_eq!(strip_verbatim_prefix(Path::new(r"C:\a.mp3")), PathBuf::from(r"C:\a.mp3"));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn reveal_selects_the_file_in_explorer() {
+        let command = reveal_command(Path::new(r"\\?\C:\My Music\a b.mp3"));
+        assert_eq!(command.get_program(), "explorer");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, [r#"/select,"C:\My Music\a b.mp3""#]);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn reveal_selects_the_file_in_finder() {
+        let command = reveal_command(Path::new("/Users/me/My Music/a b.mp3"));
+        assert_eq!(command.get_program(), "open");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["-R", "/Users/me/My Music/a b.mp3"]);
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    #[test]
+    fn reveal_opens_the_parent_folder() {
+        let command = reveal_command(Path::new("/home/me/My Music/a b.mp3"));
+        assert_eq!(command.get_program(), "xdg-open");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["/home/me/My Music"]);
+    }
+
+    fn names(entries: &[PlaylistEntry], base: &Path) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| entry.path.strip_prefix(base).unwrap_or(&entry.path).to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    // 文件名不是合法 UTF-8 时，扫描、列出、保存再读取播放列表都保持原始字节
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_file_names_survive_a_saved_playlist() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let dir = fixture_dir("non-utf8");
+        let name = OsStr::from_bytes(b"caf\xe9 \xff.mp3");
+        fs::write(dir.join(name), "").unwrap();
+        write_fixture(&dir, "b.mp3", "");
+
+        let scanned: Vec<PathBuf> = get_playlist_from_input(&dir, ScanOptions::default()).unwrap().into_iter().map(|entry| entry.path).collect();
+        assert_eq!(scanned, [dir.join("b.mp3"), dir.join(name)]);
+        let listed = crate::list::format_playlist(&scanned, None, false);
+        assert_eq!(listed.last().unwrap(), "共 2 首，不存在 0 首，扩展名不受支持 0 首。");
+
+        for file in ["saved.m3u", "saved.txt"] {
+            let target = dir.join(file);
+            save_playlist(&target, &scanned, &HashMap::new()).unwrap();
+            assert!(fs::read(&target).unwrap().windows(5).any(|bytes| bytes == b"caf\xe9 "));
+            let reloaded: Vec<PathBuf> = get_playlist_from_input(&target, ScanOptions::default()).unwrap().into_iter().map(|entry| entry.path).collect();
+            assert_eq!(reloaded, scanned, "{}", file);
+        }
+        assert_eq!(path_from_bytes(&path_to_bytes(&dir.join(name))), dir.join(name));
+    }
+
+    #[test]
+    fn saved_playlist_keeps_stream_urls() {
+        let dir = fixture_dir("save-streams");
+        let local = write_fixture(&dir, "a.mp3", "");
+        let url = "https://radio.example.com:8000/live.mp3?token=1";
+        let playlist = [local.clone(), PathBuf::from(url)];
+        for file in ["saved.m3u", "saved.txt"] {
+            let target = dir.join(file);
+            save_playlist(&target, &playlist, &HashMap::new()).unwrap();
+            let content = fs::read_to_string(&target).unwrap();
+            assert!(content.lines().any(|line| line == url), "{}", content);
+            assert!(content.lines().any(|line| line == "a.mp3"), "{}", content);
+            let reloaded = get_playlist_from_input(&target, ScanOptions::default()).unwrap();
+            assert_eq!(reloaded[0].path, local);
+            assert_eq!(reloaded[1].source_type, SourceType::NetworkStream(url.to_string()));
+        }
+    }
+
+    #[test]
+    fn truncation_on_grapheme_boundaries() {
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let accented = "e\u{301}";
+        let cases = [
+            // 刚好放下：原样返回，不加省略号
+            ("abc", 3, "abc".to_string()),
+            ("周杰伦", 6, "周杰伦".to_string()),
+            (family, 2, family.to_string()),
+            (accented, 1, accented.to_string()),
+            // 只多一列：去掉一列内容换成省略号
+            ("abcd", 3, "ab…".to_string()),
+            ("周杰伦", 5, "周杰…".to_string()),
+            // 截断处的宽字符放不下时整个去掉，宁可少占一列
+            ("a周b", 3, "a…".to_string()),
+            ("周杰伦x", 6, "周杰…".to_string()),
+            // ZWJ 序列和组合音标不会被拆开
+            (&format!("a{}", family), 2, "a…".to_string()),
+            (&format!("{}{}", family, family), 3, format!("{}…", family)),
+            (&accented.repeat(3), 2, format!("{}…", accented)),
+            ("cafe\u{301}s", 4, "caf…".to_string()),
+            // 宽度为 0 时什么都放不下，为 1 时只放得下省略号
+            ("abc", 0, String::new()),
+            ("", 0, String::new()),
+            ("abc", 1, "…".to_string()),
+            ("周", 1, "…".to_string()),
+            ("a", 1, "a".to_string()),
+        ];
+        for (text, max_width, expected) in cases {
+            let truncated = truncate_string(text, max_width);
+            assert_eq!(truncated, expected, "{:?} 截断到 {}", text, max_width);
+            assert!(truncated.width() <= max_width, "{:?} 截断到 {}", text, max_width);
+        }
+    }
+
+    #[test]
+    fn precise_durations() {
+        assert_eq!(format_duration_precise(Duration::from_millis(154321)), "02:34.321");
+        assert_eq!(format_duration_precise(Duration::ZERO), "00:00.000");
+        // 不足一毫秒的部分舍去
+        assert_eq!(format_duration_precise(Duration::from_micros(59_999_999)), "00:59.999");
+    }
+
+    #[test]
+    fn natural_order_of_tricky_names() {
+        let cases = [
+            ("Track 2.flac", "Track 10.flac", Ordering::Less),
+            ("第2首", "第10首", Ordering::Less),
+            ("第10首", "第9首", Ordering::Greater),
+            // 碟号-音轨号：逐段按数值比较
+            ("1-01", "1-2", Ordering::Less),
+            ("1-10", "2-01", Ordering::Less),
+            ("CD2/01.mp3", "CD1/12.mp3", Ordering::Greater),
+            ("Disc 1/9.mp3", "Disc 10/1.mp3", Ordering::Less),
+            // 前导零不影响数值，数值相同时按原始字符串区分，结果稳定
+            ("007", "8", Ordering::Less),
+            ("01", "1", Ordering::Less),
+            ("001", "01", Ordering::Less),
+            ("1", "1", Ordering::Equal),
+            // 超出整数范围的数字串同样按数值比较
+            ("99999999999999999999999", "100000000000000000000000", Ordering::Less),
+            // 字母不区分大小写，完全相同时才按原始字符串比较
+            ("track 2", "Track 10", Ordering::Less),
+            ("ABC", "abd", Ordering::Less),
+            ("a", "A", Ordering::Greater),
+            ("a", "a1", Ordering::Less),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(natural_cmp(a, b), expected, "{:?} vs {:?}", a, b);
+            assert_eq!(natural_cmp(b, a), expected.reverse(), "{:?} vs {:?}", b, a);
+        }
+    }
+
+    #[test]
+    fn multi_disc_folders_sort_naturally() {
+        let mut paths: Vec<PathBuf> = ["CD10/1.mp3", "CD2/10.mp3", "CD2/9.mp3", "cd1/02.mp3", "CD1/1.mp3"].iter().map(PathBuf::from).collect();
+        sort_naturally(&mut paths);
+        assert_eq!(paths, ["CD1/1.mp3", "cd1/02.mp3", "CD2/9.mp3", "CD2/10.mp3", "CD10/1.mp3"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn file_sizes() {
+        assert_eq!(format_file_size(0, false), "0 B");
+        assert_eq!(format_file_size(999, false), "999 B");
+        assert_eq!(format_file_size(1000, false), "1.0 KB");
+        assert_eq!(format_file_size(1023, true), "1023 B");
+        assert_eq!(format_file_size(1024, true), "1.0 KiB");
+        assert_eq!(format_file_size(1536, true), "1.5 KiB");
+        // 小于 100 时保留一位小数
+        assert_eq!(format_file_size(99_949, false), "99.9 KB");
+        assert_eq!(format_file_size(99_950, false), "100 KB");
+        // 四舍五入到 1000 时进到下一级单位
+        assert_eq!(format_file_size(999_499, false), "999 KB");
+        assert_eq!(format_file_size(999_960, false), "1.0 MB");
+        assert_eq!(format_file_size(1023 * 1024 + 512, true), "1.0 MiB");
+        assert_eq!(format_file_size(4_700_000_000, false), "4.7 GB");
+        // 超过最大的单位时不再进位
+        assert_eq!(format_file_size(u64::MAX, false), "18447 PB");
+    }
+
+    #[test]
+    fn audio_file_detection_matches_the_scan() {
+        let dir = fixture_dir("has-audio");
+        write_fixture(&dir, "list.m3u", "a.mp3\n");
+        write_fixture(&dir, "cover.jpg", "");
+        write_fixture(&dir, ".hidden.mp3", "");
+        write_fixture(&dir, "sub/a.mp3", "");
+        // 只有播放列表、隐藏文件和子目录中的歌曲
+        assert!(!has_audio_files(&dir, ScanOptions::default()));
+        assert!(has_audio_files(&dir, ScanOptions { include_hidden: true, ..ScanOptions::default() }));
+        write_fixture(&dir, "b.FLAC", "");
+        assert!(has_audio_files(&dir, ScanOptions::default()));
+        write_fixture(&dir, ".nomedia", "");
+        assert!(!has_audio_files(&dir, ScanOptions { ignore_nomedia: true, ..ScanOptions::default() }));
+    }
+
+    // 把目录下的相对路径拼成通配符模式
+    fn pattern(dir: &Path, relative: &str) -> PathBuf {
+        PathBuf::from(format!("{}/{}", dir.display(), relative))
+    }
+
+    #[test]
+    fn glob_expands_matched_directories_in_place() {
+        let dir = fixture_dir("glob-dirs");
+        write_fixture(&dir, "Music/周杰伦 2/b.mp3", "");
+        write_fixture(&dir, "Music/周杰伦 2/a.flac", "");
+        write_fixture(&dir, "Music/周杰伦 2/cover.jpg", "");
+        write_fixture(&dir, "Music/周杰伦 10/c.mp3", "");
+        write_fixture(&dir, "Music/周杰伦 1/d.mp3", "");
+        write_fixture(&dir, "Music/陈奕迅/e.mp3", "");
+        let entries = get_playlist_from_input(&pattern(&dir, "Music/周杰伦*"), ScanOptions::default()).unwrap();
+        // 目录按自然顺序展开，目录中的歌曲接在目录所在的位置
+        assert_eq!(
+            names(&entries, &dir),
+            ["Music/周杰伦 1/d.mp3", "Music/周杰伦 2/a.flac", "Music/周杰伦 2/b.mp3", "Music/周杰伦 10/c.mp3"]
+        );
+    }
+
+    #[test]
+    fn glob_mixes_files_and_directories() {
+        let dir = fixture_dir("glob-mixed");
+        write_fixture(&dir, "live/b.mp3", "");
+        write_fixture(&dir, "live/a.mp3", "");
+        write_fixture(&dir, "lament.mp3", "");
+        write_fixture(&dir, "lullaby.ogg", "");
+        write_fixture(&dir, ".lost.mp3", "");
+        let entries = get_playlist_from_input(&pattern(&dir, "l*"), ScanOptions::default()).unwrap();
+        assert_eq!(names(&entries, &dir), ["lament.mp3", "live/a.mp3", "live/b.mp3", "lullaby.ogg"]);
+        // 以 . 开头的文件只在包含隐藏文件时匹配
+        let hidden = ScanOptions { include_hidden: true, ..ScanOptions::default() };
+        assert_eq!(names(&get_playlist_from_input(&pattern(&dir, "*.mp3"), hidden).unwrap(), &dir), [".lost.mp3", "lament.mp3"]);
+    }
+
+    #[test]
+    fn glob_without_matches_is_an_error() {
+        let dir = fixture_dir("glob-none");
+        write_fixture(&dir, "a.mp3", "");
+        let error = get_playlist_from_input(&pattern(&dir, "*.flac"), ScanOptions::default()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(error.to_string(), "通配符未匹配到任何文件");
+    }
+
+    // 没有读取权限的目录被跳过，其他匹配结果照常返回（root 不受权限限制，无法模拟时跳过检查）
+    #[cfg(unix)]
+    #[test]
+    fn glob_skips_unreadable_directories() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = fixture_dir("glob-unreadable");
+        write_fixture(&dir, "a/song.mp3", "");
+        write_fixture(&dir, "b/song.mp3", "");
+        fs::set_permissions(dir.join("a"), fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = fs::read_dir(dir.join("a")).is_ok();
+        let entries = get_playlist_from_input(&pattern(&dir, "*"), ScanOptions::default());
+        fs::set_permissions(dir.join("a"), fs::Permissions::from_mode(0o755)).unwrap();
+        if !readable {
+            assert_eq!(names(&entries.unwrap(), &dir), ["b/song.mp3"]);
+        }
+    }
+
+    #[test]
+    fn nested_playlists_expand_in_file_order() {
+        let dir = fixture_dir("nested");
+        for file in ["album/01.mp3", "album/02.mp3", "inner/x.mp3", "inner/deep/y.mp3", "last.mp3"] {
+            write_fixture(&dir, file, "");
+        }
+        write_fixture(&dir, "inner/deep/deeper.txt", "y.mp3\n");
+        write_fixture(&dir, "inner/inner.m3u", "#EXTM3U\nx.mp3\ndeep/deeper.txt\n");
+        let master = write_fixture(&dir, "master.txt", "inner/inner.m3u\nalbum\nlast.mp3\n");
+
+        let entries = get_playlist_from_input(&master, ScanOptions::default()).unwrap();
+        assert_eq!(
+            names(&entries, &dir),
+            ["inner/x.mp3", "inner/deep/y.mp3", "album/01.mp3", "album/02.mp3", "last.mp3"]
+        );
+    }
+
+    #[test]
+    fn playlist_cycle_reports_the_chain() {
+        let dir = fixture_dir("cycle");
+        write_fixture(&dir, "song.mp3", "");
+        let a = write_fixture(&dir, "a.txt", "song.mp3\nb.txt\n");
+        write_fixture(&dir, "b.txt", "a.txt\n");
+
+        let error = get_playlist_from_input(&a, ScanOptions::default()).unwrap_err().to_string();
+        assert!(error.contains("播放列表循环引用"), "{}", error);
+        let chain: Vec<&str> = error.rsplit(": ").next().unwrap().split(" -> ").collect();
+        let file_names: Vec<String> = chain.iter().map(|p| Path::new(p).file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(file_names, ["a.txt", "b.txt", "a.txt"]);
+    }
+}
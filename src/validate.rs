@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use crate::metadata::quick_probe;
+use crate::utils::{has_audio_extension, parallel_map, stream_url};
+
+/// 播放列表条目存在的问题
+enum Problem {
+    Missing,
+    UnsupportedExtension,
+    Unrecognized(String),
+}
+
+impl Problem {
+    fn heading(&self) -> &'static str {
+        match self {
+            Problem::Missing => "不存在",
+            Problem::UnsupportedExtension => "扩展名不受支持",
+            Problem::Unrecognized(_) => "无法识别为音频",
+        }
+    }
+}
+
+/// 去掉播放列表中不存在的文件（网络流不检查），返回去掉的条目数
+pub fn drop_missing(playlist: &mut Vec<PathBuf>) -> usize {
+    let before = playlist.len();
+    playlist.retain(|path| stream_url(path).is_some() || path.exists());
+    before - playlist.len()
+}
+
+/// --strict：检查每个条目是否存在、扩展名是否受支持、能否探测到音轨（多线程，进度输出到标准错误）。
+/// 发现问题时按问题分组输出报告，返回 false
+pub fn check_playlist(playlist: &[PathBuf]) -> bool {
+    let results = parallel_map(playlist, "检查播放列表", check_entry);
+    let problems: Vec<(&PathBuf, Problem)> = playlist.iter().zip(results).filter_map(|(path, problem)| Some((path, problem?))).collect();
+    if problems.is_empty() {
+        return true;
+    }
+    eprintln!("[错误]播放列表中有 {} 个条目无法播放：", problems.len());
+    for heading in ["不存在", "扩展名不受支持", "无法识别为音频"] {
+        let group: Vec<&(&PathBuf, Problem)> = problems.iter().filter(|(_, problem)| problem.heading() == heading).collect();
+        if group.is_empty() {
+            continue;
+        }
+        eprintln!("{}（{} 个）：", heading, group.len());
+        for (path, problem) in group {
+            match problem {
+                Problem::Unrecognized(reason) => eprintln!("  {}: {}", path.display(), reason),
+                _ => eprintln!("  {}", path.display()),
+            }
+        }
+    }
+    false
+}
+
+/// 条目能否播放（--strict 的检查），不能时返回原因；用于 --watch-playlist 追加的条目
+pub fn entry_problem(path: &Path) -> Option<String> {
+    check_entry(path).map(|problem| match &problem {
+        Problem::Unrecognized(reason) => format!("{}: {}", problem.heading(), reason),
+        _ => problem.heading().to_string(),
+    })
+}
+
+fn check_entry(path: &Path) -> Option<Problem> {
+    if stream_url(path).is_some() {
+        return None;
+    }
+    if !path.exists() {
+        return Some(Problem::Missing);
+    }
+    if !has_audio_extension(path) {
+        return Some(Problem::UnsupportedExtension);
+    }
+    quick_probe(path).err().map(|e| Problem::Unrecognized(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::duration_in_range;
+    use crate::utils::{fixture_dir, write_fixture, write_wav_fixture};
+    use std::time::Duration;
+
+    // --strict 只检查条目能否播放，时长不在 --min-duration 范围内的歌曲在播放时才跳过
+    #[test]
+    fn strict_check_ignores_duration_limits() {
+        let dir = fixture_dir("strict-duration");
+        let jingle = write_wav_fixture(&dir, "jingle.wav", 8000, 1, &[0; 800]);
+        let playlist = vec![jingle.clone(), PathBuf::from("https://radio.example.com/live.mp3")];
+        assert!(check_playlist(&playlist));
+        let duration = crate::metadata::get_total_duration(&jingle);
+        assert!(!duration.is_zero());
+        assert!(!duration_in_range(duration, Some(Duration::from_secs(90)), None));
+    }
+
+    #[test]
+    fn strict_check_fails_on_any_problem() {
+        let dir = fixture_dir("strict-problems");
+        let good = write_wav_fixture(&dir, "good.wav", 8000, 1, &[0; 800]);
+        let missing = dir.join("missing.mp3");
+        let unsupported = write_fixture(&dir, "notes.txt", "x");
+        let fake = write_fixture(&dir, "fake.mp3", "not audio");
+        assert!(check_entry(&good).is_none());
+        assert!(matches!(check_entry(&missing), Some(Problem::Missing)));
+        assert!(matches!(check_entry(&unsupported), Some(Problem::UnsupportedExtension)));
+        assert!(matches!(check_entry(&fake), Some(Problem::Unrecognized(_))));
+        for problem in [missing, unsupported, fake] {
+            assert!(!check_playlist(&[good.clone(), problem]));
+        }
+    }
+
+    #[test]
+    fn drop_missing_keeps_streams() {
+        let dir = fixture_dir("drop-missing");
+        let good = write_fixture(&dir, "a.mp3", "");
+        let url = PathBuf::from("http://radio.example.com/live");
+        let mut playlist = vec![dir.join("gone.mp3"), good.clone(), url.clone()];
+        assert_eq!(drop_missing(&mut playlist), 1);
+        assert_eq!(playlist, [good, url]);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, warn};
+
+use crate::cli::{DedupMode, EncodingFix};
+use crate::dedup::{same_tags, tag_key, title_key, TagKey};
+use crate::excluded::{canonical, excluded_file_for, load_excluded};
+use crate::metadata::duration_in_range;
+use crate::utils::{read_playlist_file, PlaylistEntry, ScanOptions, SourceType};
+use crate::validate::entry_problem;
+
+/// 检查播放列表文件修改时间的间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 启动时对播放列表的过滤设置，追加的条目同样按这些设置过滤
+#[derive(Clone, Copy, Debug)]
+pub struct AppendOptions {
+    pub use_excluded: bool,
+    pub dedup: Option<DedupMode>,
+    pub skip_duplicates_by_title: bool,
+    pub fix_encoding: EncodingFix,
+    pub min_duration: Option<Duration>,
+    pub max_duration: Option<Duration>,
+    pub strict: bool,
+    pub max_tracks: Option<usize>,
+}
+
+/// --watch-playlist：后台线程每秒检查一次播放列表文件的修改时间，变化后重新读取，
+/// 把启动时文件中和 playlist（当前播放列表）中都没有的条目按文件中的顺序、按 options 过滤后发送给主线程
+/// （不输出“检测到播放列表文件”等提示，以免打乱状态栏）。
+/// 只追加不删除：文件中去掉的条目仍留在播放列表中，以免打断正在播放的歌曲。接收端关闭后线程结束
+pub fn watch_playlist(file: PathBuf, playlist: &[PathBuf], scan_options: ScanOptions, options: AppendOptions) -> Receiver<Vec<PlaylistEntry>> {
+    let (tx, rx) = mpsc::channel();
+    // 启动时文件中已有的条目即使被过滤掉（--max-tracks、--dedup 等）也不算新增
+    let mut known = initial_known(&file, playlist, scan_options);
+    let mut filter = AppendFilter::new(options, playlist.to_vec());
+    thread::spawn(move || {
+        let mut last_modified = modified_time(&file);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let modified = modified_time(&file);
+            if modified.is_none() || modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            let entries = match read_playlist_file(&file, &mut Vec::new(), scan_options) {
+                Ok(entries) => entries,
+                // 外部程序可能正在写入（例如先清空再写入），等下一次修改后再读取
+                Err(e) => {
+                    warn!(file = %file.display(), error = %e, "无法重新读取播放列表");
+                    continue;
+                }
+            };
+            // 还不存在的文件（例如仍在下载）暂不加入，文件再次修改时重新检查
+            let added: Vec<PlaylistEntry> = entries
+                .into_iter()
+                .filter(|entry| entry.source_type != SourceType::LocalFile || entry.path.exists())
+                .filter(|entry| known.insert(entry.path.clone()))
+                .collect();
+            let added = filter.retain(&file, added);
+            if added.is_empty() {
+                continue;
+            }
+            info!(file = %file.display(), count = added.len(), "播放列表文件新增歌曲");
+            if tx.send(added).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// 文件的修改时间，无法读取时（例如文件暂时被替换）返回 None
+fn modified_time(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|meta| meta.modified()).ok()
+}
+
+// 启动时已知的条目：当前播放列表和播放列表文件中的全部条目（还不存在的文件除外，之后出现时按新增处理）
+fn initial_known(file: &Path, playlist: &[PathBuf], scan_options: ScanOptions) -> HashSet<PathBuf> {
+    let mut known: HashSet<PathBuf> = playlist.iter().cloned().collect();
+    match read_playlist_file(file, &mut Vec::new(), scan_options) {
+        Ok(entries) => known.extend(
+            entries
+                .into_iter()
+                .filter(|entry| entry.source_type != SourceType::LocalFile || entry.path.exists())
+                .map(|entry| entry.path),
+        ),
+        Err(e) => warn!(file = %file.display(), error = %e, "无法读取播放列表"),
+    }
+    known
+}
+
+// 按启动时的设置过滤追加的条目。与播放列表中已有的歌曲重复时去掉新的条目（不替换正在播放的列表中的歌曲）。
+// 已有歌曲的路径和标签在第一次有新条目时才读取
+struct AppendFilter {
+    options: AppendOptions,
+    existing: Vec<PathBuf>,
+    seen: Option<Seen>,
+}
+
+// 播放列表中（包括已追加的）歌曲的规范化路径、标签和数量
+struct Seen {
+    paths: HashSet<PathBuf>,
+    tags: Vec<TagKey>,
+    titles: HashSet<(String, String)>,
+    count: usize,
+}
+
+impl AppendFilter {
+    fn new(options: AppendOptions, existing: Vec<PathBuf>) -> Self {
+        AppendFilter { options, existing, seen: None }
+    }
+
+    fn retain(&mut self, file: &Path, entries: Vec<PlaylistEntry>) -> Vec<PlaylistEntry> {
+        if entries.is_empty() {
+            return entries;
+        }
+        let options = self.options;
+        let by_path = options.dedup.is_some();
+        let by_tag = options.dedup == Some(DedupMode::Tag);
+        let existing = std::mem::take(&mut self.existing);
+        let seen = self.seen.get_or_insert_with(|| Seen {
+            paths: if by_path { existing.iter().map(|path| canonical(path)).collect() } else { HashSet::new() },
+            tags: if by_tag { existing.iter().filter_map(|path| tag_key(path, options.fix_encoding)).collect() } else { Vec::new() },
+            titles: if options.skip_duplicates_by_title {
+                existing.iter().filter_map(|path| title_key(path, options.fix_encoding)).collect()
+            } else {
+                HashSet::new()
+            },
+            count: existing.len(),
+        });
+        // 按 D 键移除的歌曲可能在运行期间写入排除列表，每次重新读取
+        let excluded = match excluded_file_for(file).filter(|_| options.use_excluded) {
+            Some(excluded_file) => load_excluded(&excluded_file),
+            None => HashSet::new(),
+        };
+
+        let mut kept = Vec::new();
+        for entry in entries {
+            if options.max_tracks.is_some_and(|max| seen.count >= max) {
+                break;
+            }
+            let path = canonical(&entry.path);
+            if excluded.contains(&path) || (by_path && seen.paths.contains(&path)) {
+                continue;
+            }
+            if entry.duration_hint.is_some_and(|duration| !duration_in_range(duration, options.min_duration, options.max_duration)) {
+                continue;
+            }
+            let tags = if by_tag { tag_key(&entry.path, options.fix_encoding) } else { None };
+            if tags.as_ref().is_some_and(|key| seen.tags.iter().any(|other| same_tags(other, key))) {
+                continue;
+            }
+            let title = if options.skip_duplicates_by_title { title_key(&entry.path, options.fix_encoding) } else { None };
+            if title.as_ref().is_some_and(|title| seen.titles.contains(title)) {
+                continue;
+            }
+            if let Some(problem) = options.strict.then(|| entry_problem(&entry.path)).flatten() {
+                warn!(path = %entry.path.display(), problem, "跳过播放列表文件新增的无法播放的条目");
+                continue;
+            }
+            if by_path {
+                seen.paths.insert(path);
+            }
+            seen.tags.extend(tags);
+            seen.titles.extend(title);
+            seen.count += 1;
+            kept.push(entry);
+        }
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{fixture_dir, write_fixture, write_wav_fixture};
+
+    // 不做任何过滤的设置
+    fn no_filters() -> AppendOptions {
+        AppendOptions {
+            use_excluded: false,
+            dedup: None,
+            skip_duplicates_by_title: false,
+            fix_encoding: EncodingFix::Off,
+            min_duration: None,
+            max_duration: None,
+            strict: false,
+            max_tracks: None,
+        }
+    }
+
+    fn entries(paths: &[&PathBuf]) -> Vec<PlaylistEntry> {
+        paths.iter().map(|path| PlaylistEntry::new((*path).clone())).collect()
+    }
+
+    #[test]
+    fn known_entries_include_the_whole_file() {
+        let dir = fixture_dir("watch-known");
+        let a = write_fixture(&dir, "a.mp3", "");
+        let b = write_fixture(&dir, "b.mp3", "");
+        let file = write_fixture(&dir, "list.m3u", "a.mp3\nb.mp3\ndownloading.mp3\n");
+        // b.mp3 在启动时被过滤掉（例如 --max-tracks 1），之后修改文件时不应作为新增的歌曲
+        let known = initial_known(&file, std::slice::from_ref(&a), ScanOptions::default());
+        assert!(known.contains(&a) && known.contains(&b));
+        // 还不存在的文件之后出现时按新增处理
+        assert!(!known.contains(&dir.join("downloading.mp3")));
+    }
+
+    #[test]
+    fn appended_entries_go_through_the_startup_filters() {
+        let dir = fixture_dir("watch-filter");
+        let a = write_fixture(&dir, "a.mp3", "");
+        let b = write_fixture(&dir, "b.mp3", "");
+        let c = write_fixture(&dir, "c.mp3", "");
+        let file = write_fixture(&dir, "list.m3u", "");
+        write_fixture(&dir, "excluded.txt", "b.mp3\n");
+
+        let options = AppendOptions { use_excluded: true, dedup: Some(DedupMode::Path), max_tracks: Some(3), ..no_filters() };
+        let mut filter = AppendFilter::new(options, vec![a.clone()]);
+        // 已有的 a.mp3（写法不同的同一个文件）和排除列表中的 b.mp3 被去掉
+        let same_a = dir.join(".").join("a.mp3");
+        let kept = filter.retain(&file, entries(&[&same_a, &b, &c]));
+        assert_eq!(kept.iter().map(|entry| &entry.path).collect::<Vec<_>>(), [&c]);
+        // 之前追加的歌曲同样参与去重；达到 --max-tracks 后不再追加
+        let d = write_fixture(&dir, "d.mp3", "");
+        let e = write_fixture(&dir, "e.mp3", "");
+        let kept = filter.retain(&file, entries(&[&c, &d, &e]));
+        assert_eq!(kept.iter().map(|entry| &entry.path).collect::<Vec<_>>(), [&d]);
+    }
+
+    #[test]
+    fn appended_entries_are_checked_like_the_playlist() {
+        let dir = fixture_dir("watch-strict");
+        let good = write_wav_fixture(&dir, "good.wav", 8000, 1, &[0; 800]);
+        let broken = write_fixture(&dir, "broken.mp3", "not audio");
+        let short = PlaylistEntry { duration_hint: Some(Duration::from_secs(20)), ..PlaylistEntry::new(good.clone()) };
+        let options = AppendOptions { strict: true, min_duration: Some(Duration::from_secs(60)), ..no_filters() };
+        let mut filter = AppendFilter::new(options, Vec::new());
+        let kept = filter.retain(&dir.join("list.m3u"), vec![short, PlaylistEntry::new(broken), PlaylistEntry::new(good.clone())]);
+        // 播放列表给出的时长不在范围内的条目和无法识别的文件被去掉
+        assert_eq!(kept.iter().map(|entry| &entry.path).collect::<Vec<_>>(), [&good]);
+    }
+}
@@ -0,0 +1,87 @@
+// 收到外部信号（kill、父脚本退出）时，播放器应走正常的退出流程：恢复终端、删除 PID 文件并以成功状态退出
+#![cfg(unix)]
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 写入 3 秒静音的单声道 WAV
+fn write_silent_wav(path: &Path) {
+    let data_len: u32 = 8000 * 3 * 2;
+    let mut data = Vec::with_capacity(44 + data_len as usize);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + data_len).to_le_bytes());
+    data.extend_from_slice(b"WAVEfmt ");
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&8000u32.to_le_bytes());
+    data.extend_from_slice(&16000u32.to_le_bytes());
+    data.extend_from_slice(&2u16.to_le_bytes());
+    data.extend_from_slice(&16u16.to_le_bytes());
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&data_len.to_le_bytes());
+    data.resize(44 + data_len as usize, 0);
+    fs::write(path, data).unwrap();
+}
+
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mddplayer-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn sigterm_exits_cleanly_in_headless_mode() {
+    let dir = fixture_dir("sigterm");
+    let track = dir.join("silence.wav");
+    write_silent_wav(&track);
+    let pid_file = dir.join("mddplayer.pid");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mddplayer"))
+        .arg("--headless")
+        .arg("--loop")
+        .arg("--pid-file")
+        .arg(&pid_file)
+        .arg(&track)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // 等到开始播放。没有可用的音频设备时播放器会直接报错退出，这时跳过测试
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    match lines.next() {
+        Some(Ok(line)) if line.starts_with("正在播放") => {}
+        _ => {
+            let status = child.wait().unwrap();
+            eprintln!("跳过：播放器没有开始播放（{}），可能没有可用的音频设备", status);
+            return;
+        }
+    }
+    // 继续读取之后的输出，避免管道写满后播放器阻塞在输出上
+    let drain = thread::spawn(move || lines.count());
+    assert!(pid_file.exists(), "播放中应写入 PID 文件");
+
+    let killed = Command::new("kill").arg("-TERM").arg(child.id().to_string()).status().unwrap();
+    assert!(killed.success());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("收到 SIGTERM 后 5 秒内没有退出");
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    drain.join().unwrap();
+    // 正常退出：退出码为 0（不是被信号杀死），PID 文件已删除
+    assert_eq!(status.code(), Some(0), "{}", status);
+    assert!(!pid_file.exists(), "退出后应删除 PID 文件");
+}